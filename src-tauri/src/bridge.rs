@@ -0,0 +1,165 @@
+use crate::models::{AppError, BridgeMarkerStyle, Directive};
+use std::sync::OnceLock;
+
+// ============================================================
+// BRIDGE PAYLOAD
+// ============================================================
+
+/// One staged entry as it appears in the payload
+pub struct ContextBlock {
+    pub entry_id: String,
+    pub role: String,
+    pub sequence_id: i32,
+    /// Entry content rendered as Markdown
+    pub body: String,
+}
+
+/// The marker `extract_bridge_key` looks for in pasted responses
+pub fn marker(style: BridgeMarkerStyle, bridge_key: &str) -> String {
+    match style {
+        BridgeMarkerStyle::HtmlComment => format!("<!-- bridge:{} -->", bridge_key),
+        BridgeMarkerStyle::WikiLink => format!("[[bridge:{}]]", bridge_key),
+    }
+}
+
+/// Matches markers of `style` as they come back from AI tools, capturing the key.
+/// Both tolerate Markdown backslash escapes and markers wrapped in code spans or fences.
+/// HTML comments may also come back with escaped brackets (`&lt;`/`&gt;`) or `&#45;` dashes.
+pub fn marker_regex(style: BridgeMarkerStyle) -> Result<&'static regex::Regex, AppError> {
+    static HTML_COMMENT_RE: OnceLock<Result<regex::Regex, String>> = OnceLock::new();
+    static WIKI_LINK_RE: OnceLock<Result<regex::Regex, String>> = OnceLock::new();
+
+    let (cell, pattern) = match style {
+        BridgeMarkerStyle::HtmlComment => (
+            &HTML_COMMENT_RE,
+            r#"(?:\\?<|&lt;)!(?:\\?-|&#45;){2}\s*bridge\s*:\s*([a-zA-Z0-9]+)\s*(?:\\?-|&#45;){2}(?:\\?>|&gt;)"#,
+        ),
+        BridgeMarkerStyle::WikiLink => (
+            &WIKI_LINK_RE,
+            r#"\\?\[\\?\[\s*bridge\s*:\s*([a-zA-Z0-9]+)\s*\\?\]\\?\]"#,
+        ),
+    };
+
+    cell.get_or_init(|| regex::Regex::new(pattern).map_err(|e| e.to_string()))
+        .as_ref()
+        .map_err(|e| AppError::new("INTERNAL", e))
+}
+
+fn instructions(directive: Directive) -> &'static str {
+    match directive {
+        Directive::Dump => {
+            "You are a thinking partner helping to refactor and restructure notes.\n\
+             Improve the organization, clarity and coherence of the context below \
+             without dropping any of its information."
+        }
+        Directive::Critique => {
+            "You are a critical thinking partner analyzing these notes.\n\
+             Point out strengths, gaps, inconsistencies and open questions in the \
+             context below, then recommend specific next steps."
+        }
+        Directive::Generate => {
+            "You are a creative thinking partner helping to expand these notes.\n\
+             Build on the context below with examples, details and implications, \
+             marking speculative ideas as such."
+        }
+    }
+}
+
+/// Directive instructions, then each block wrapped as `<role_entry>`, then the marker.
+/// The AI is asked to repeat the marker so its reply can be matched to the pending block.
+pub fn assemble(
+    directive: Directive,
+    blocks: &[ContextBlock],
+    bridge_key: &str,
+    style: BridgeMarkerStyle,
+) -> String {
+    let context = blocks
+        .iter()
+        .map(|block| {
+            format!(
+                "<{role}_entry id=\"{id}\" sequence=\"{seq}\">\n{body}\n</{role}_entry>",
+                role = block.role,
+                id = block.entry_id,
+                seq = block.sequence_id,
+                body = block.body.trim()
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    format!(
+        "<directive type=\"{}\">\n{}\n</directive>\n\n<context>\n{}\n</context>\n\n\
+         End your response with the line below, unchanged:\n{}\n",
+        directive.as_str(),
+        instructions(directive),
+        context,
+        marker(style, bridge_key)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captured(style: BridgeMarkerStyle, text: &str) -> Option<String> {
+        marker_regex(style)
+            .unwrap()
+            .captures(text)
+            .map(|caps| caps[1].to_string())
+    }
+
+    #[test]
+    fn html_comment_markers_survive_common_encodings() {
+        let variants = [
+            "<!-- bridge:ab12CD -->",
+            "<!--bridge: ab12CD-->",
+            "&lt;!-- bridge:ab12CD --&gt;",
+            "<!&#45;&#45; bridge:ab12CD &#45;&#45;>",
+            "\\<!\\-\\- bridge:ab12CD \\-\\-\\>",
+            "Reply text.\n\n`<!-- bridge:ab12CD -->`",
+            "```\n<!-- bridge:ab12CD -->\n```",
+        ];
+
+        for text in variants {
+            assert_eq!(
+                captured(BridgeMarkerStyle::HtmlComment, text).as_deref(),
+                Some("ab12CD"),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn wiki_link_markers_survive_escapes_and_code() {
+        let variants = [
+            "[[bridge:ab12CD]]",
+            "[[ bridge : ab12CD ]]",
+            "\\[\\[bridge:ab12CD\\]\\]",
+            "`[[bridge:ab12CD]]`",
+            "```text\n[[bridge:ab12CD]]\n```",
+        ];
+
+        for text in variants {
+            assert_eq!(
+                captured(BridgeMarkerStyle::WikiLink, text).as_deref(),
+                Some("ab12CD"),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn markers_only_match_their_own_style() {
+        let html = marker(BridgeMarkerStyle::HtmlComment, "ab12CD");
+        let wiki = marker(BridgeMarkerStyle::WikiLink, "ab12CD");
+
+        assert_eq!(captured(BridgeMarkerStyle::WikiLink, &html), None);
+        assert_eq!(captured(BridgeMarkerStyle::HtmlComment, &wiki), None);
+        assert_eq!(
+            captured(BridgeMarkerStyle::HtmlComment, "<!-- bridge: -->"),
+            None
+        );
+    }
+}
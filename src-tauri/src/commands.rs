@@ -1,15 +1,22 @@
-use crate::database::Database;
+use crate::bridge;
+use crate::content;
+use crate::database::{self, Database};
+use crate::diff;
+use crate::fuzzy;
 use crate::models::*;
-use rusqlite::params;
-use tauri::State;
+use crate::providers;
+use crate::templates;
+use rusqlite::{params, OptionalExtension};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use tauri::{Manager, State};
 
 // ============================================================
 // PROFILE COMMANDS
 // ============================================================
 
 #[tauri::command]
-pub fn create_profile(db: State<Database>, input: CreateProfileInput) -> Result<Profile, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn create_profile(db: State<Database>, input: CreateProfileInput) -> Result<Profile, AppError> {
+    let conn = db.pool.get()?;
     let now = chrono::Utc::now().timestamp_millis();
     let id = uuid::Uuid::new_v4().to_string();
 
@@ -39,8 +46,7 @@ pub fn create_profile(db: State<Database>, input: CreateProfileInput) -> Result<
             now,
             now
         ],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
 
     Ok(Profile {
         id,
@@ -58,8 +64,8 @@ pub fn create_profile(db: State<Database>, input: CreateProfileInput) -> Result<
 }
 
 #[tauri::command]
-pub fn get_all_profiles(db: State<Database>, user_id: String) -> Result<Vec<Profile>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn get_all_profiles(db: State<Database>, user_id: String) -> Result<Vec<Profile>, AppError> {
+    let conn = db.pool.get()?;
 
     let mut stmt = conn
         .prepare(
@@ -67,8 +73,7 @@ pub fn get_all_profiles(db: State<Database>, user_id: String) -> Result<Vec<Prof
              FROM profiles 
              WHERE user_id = ?
              ORDER BY is_default DESC, name ASC",
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
 
     let profiles = stmt
         .query_map([user_id], |row| {
@@ -85,17 +90,15 @@ pub fn get_all_profiles(db: State<Database>, user_id: String) -> Result<Vec<Prof
                 created_at: row.get(9)?,
                 updated_at: row.get(10)?,
             })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(profiles)
 }
 
 #[tauri::command]
-pub fn get_profile(db: State<Database>, profile_id: String) -> Result<Option<Profile>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn get_profile(db: State<Database>, profile_id: String) -> Result<Option<Profile>, AppError> {
+    let conn = db.pool.get()?;
 
     let result = conn.query_row(
         "SELECT id, user_id, name, role, avatar_url, color, initials, bio, is_default, created_at, updated_at 
@@ -122,7 +125,7 @@ pub fn get_profile(db: State<Database>, profile_id: String) -> Result<Option<Pro
     match result {
         Ok(profile) => Ok(Some(profile)),
         Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.to_string()),
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -131,56 +134,98 @@ pub fn update_profile(
     db: State<Database>,
     profile_id: String,
     input: UpdateProfileInput,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
     let now = chrono::Utc::now().timestamp_millis();
 
+    // Field updates and the default switch commit together
+    let tx = conn.transaction()?;
+
     if let Some(name) = input.name {
-        conn.execute(
+        tx.execute(
             "UPDATE profiles SET name = ?1, updated_at = ?2 WHERE id = ?3",
             params![name, now, profile_id],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
     if let Some(role) = input.role {
-        conn.execute(
+        tx.execute(
             "UPDATE profiles SET role = ?1, updated_at = ?2 WHERE id = ?3",
             params![role, now, profile_id],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
     if let Some(color) = input.color {
-        conn.execute(
+        tx.execute(
             "UPDATE profiles SET color = ?1, updated_at = ?2 WHERE id = ?3",
             params![color, now, profile_id],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
     if let Some(initials) = input.initials {
-        conn.execute(
+        tx.execute(
             "UPDATE profiles SET initials = ?1, updated_at = ?2 WHERE id = ?3",
             params![initials, now, profile_id],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
     if let Some(bio) = input.bio {
-        conn.execute(
+        tx.execute(
             "UPDATE profiles SET bio = ?1, updated_at = ?2 WHERE id = ?3",
             params![bio, now, profile_id],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
     if let Some(avatar_url) = input.avatar_url {
-        conn.execute(
+        tx.execute(
             "UPDATE profiles SET avatar_url = ?1, updated_at = ?2 WHERE id = ?3",
             params![avatar_url, now, profile_id],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
+    }
+
+    if input.is_default == Some(true) {
+        make_default_profile(&tx, &profile_id, now)?;
+    }
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn set_default_profile(db: State<Database>, profile_id: String) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction()?;
+    make_default_profile(&tx, &profile_id, now)?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Swaps the default flag onto `profile_id`. Runs inside the caller's transaction so
+/// there is never zero or two defaults; the unique index on `is_default` backs this up.
+fn make_default_profile(
+    tx: &rusqlite::Transaction,
+    profile_id: &str,
+    now: i64,
+) -> Result<(), AppError> {
+    tx.execute(
+        "UPDATE profiles SET is_default = 0, updated_at = ?1 WHERE is_default = 1 AND id != ?2",
+        params![now, profile_id],
+    )?;
+
+    let rows_affected = tx.execute(
+        "UPDATE profiles SET is_default = 1, updated_at = ?1 WHERE id = ?2",
+        params![now, profile_id],
+    )?;
+
+    if rows_affected == 0 {
+        return Err(AppError::new(
+            "NOT_FOUND",
+            &format!("Profile '{}' does not exist", profile_id),
+        ));
     }
 
     Ok(())
@@ -191,179 +236,150 @@ pub fn delete_profile(
     db: State<Database>,
     profile_id: String,
     reassign_to_id: Option<String>,
-) -> Result<(), String> {
-    println!(
-        "DEBUG: delete_profile START - profile_id: '{}', reassign_to_id: {:?}",
-        profile_id, reassign_to_id
-    );
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+
+    // Reassignment and deletion must succeed or fail together
+    let tx = conn.transaction()?;
 
     // Check if this is the default profile
-    let is_default: i32 = conn
-        .query_row(
-            "SELECT is_default FROM profiles WHERE id = ?1",
-            params![profile_id],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+    let is_default: i32 = tx.query_row(
+        "SELECT is_default FROM profiles WHERE id = ?1",
+        params![profile_id],
+        |row| row.get(0),
+    )?;
 
     if is_default != 0 {
-        return Err("Cannot delete the default profile".to_string());
+        return Err("Cannot delete the default profile".into());
     }
 
     // Check if profile has entries
-    let entry_count: i64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM entries WHERE profile_id = ?1",
-            params![profile_id],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
-
-    println!(
-        "DEBUG: entry_count in DB for profile_id: {}: {}",
-        profile_id, entry_count
-    );
+    let entry_count: i64 = tx.query_row(
+        "SELECT COUNT(*) FROM entries WHERE profile_id = ?1",
+        params![profile_id],
+        |row| row.get(0),
+    )?;
 
     if entry_count > 0 {
         match reassign_to_id {
             Some(new_profile_id) => {
-                println!("DEBUG: Reassigning to: '{}'", new_profile_id);
-                // Verify new profile exists
-                let exists: i32 = conn
-                    .query_row(
-                        "SELECT COUNT(*) FROM profiles WHERE id = ?1",
-                        params![new_profile_id],
-                        |row| row.get(0),
-                    )
-                    .map_err(|e| e.to_string())?;
-
-                if exists == 0 {
-                    println!("DEBUG: New profile '{}' NOT FOUND", new_profile_id);
-                    return Err("Reassignment profile does not exist".to_string());
-                }
-
-                if new_profile_id == profile_id {
-                    return Err("Cannot reassign to the profile being deleted".to_string());
-                }
-
-                // Reassign entries
-                let now = chrono::Utc::now().timestamp_millis();
-                let rows_affected = conn
-                    .execute(
-                        "UPDATE entries SET profile_id = ?1, updated_at = ?2 WHERE profile_id = ?3",
-                        params![new_profile_id, now, profile_id],
-                    )
-                    .map_err(|e| e.to_string())?;
-                println!("DEBUG: entries reassigned rows_affected: {}", rows_affected);
+                let rows_affected = reassign_entries(&tx, &profile_id, &new_profile_id)?;
 
                 if rows_affected == 0 && entry_count > 0 {
                     return Err(format!(
                         "Failed to reassign {} entries. The UPDATE query matched 0 rows (profile_id: '{}').",
                         entry_count, profile_id
-                    ));
+                    ).into());
                 }
             }
             None => {
-                println!("DEBUG: ERROR - NO reassign_to_id provided despite entry_count > 0");
                 return Err(format!(
                     "Cannot delete profile with {} associated entries. Reassign entries first.",
                     entry_count
-                ));
+                )
+                .into());
             }
         }
     }
 
-    println!("DEBUG: Deleting profile '{}'", profile_id);
-    conn.execute("DELETE FROM profiles WHERE id = ?1", params![profile_id])
-        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM profiles WHERE id = ?1", params![profile_id])?;
+
+    tx.commit()?;
 
-    println!("DEBUG: delete_profile SUCCESS");
     Ok(())
 }
 
+/// Moves every entry attributed to one profile over to another
 #[tauri::command]
-pub fn get_default_profile(db: State<Database>) -> Result<Profile, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn reassign_profile_entries(
+    db: State<Database>,
+    from_profile_id: String,
+    to_profile_id: String,
+) -> Result<usize, AppError> {
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    tx.query_row(
+        "SELECT 1 FROM profiles WHERE id = ?1",
+        params![from_profile_id],
+        |_| Ok(()),
+    )
+    .map_err(|e| AppError::from_lookup(e, || format!("Profile not found: {}", from_profile_id)))?;
 
-    // Try to get existing default profile
-    let result = conn.query_row(
-        "SELECT id, user_id, name, role, avatar_url, color, initials, bio, is_default, created_at, updated_at 
-         FROM profiles 
-         WHERE is_default = 1
-         LIMIT 1",
-        [],
-        |row| {
-            Ok(Profile {
-                id: row.get(0)?,
-                user_id: row.get(1)?,
-                name: row.get(2)?,
-                role: row.get(3)?,
-                avatar_url: row.get(4)?,
-                color: row.get(5)?,
-                initials: row.get(6)?,
-                bio: row.get(7)?,
-                is_default: true,
-                created_at: row.get(9)?,
-                updated_at: row.get(10)?,
-            })
-        },
-    );
+    let rows_affected = reassign_entries(&tx, &from_profile_id, &to_profile_id)?;
 
-    match result {
-        Ok(profile) => Ok(profile),
-        Err(rusqlite::Error::QueryReturnedNoRows) => {
-            // Create default profile
-            let now = chrono::Utc::now().timestamp_millis();
-            let id = uuid::Uuid::new_v4().to_string();
+    tx.commit()?;
 
-            conn.execute(
-                "INSERT INTO profiles (id, user_id, name, role, color, initials, bio, is_default, created_at, updated_at) 
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-                params![
-                    id,
-                    "default-user",
-                    "You",
-                    "self",
-                    "#3B82F6", // Blue
-                    "ME",
-                    None::<String>,
-                    1, // is_default = true
-                    now,
-                    now
-                ],
-            )
-            .map_err(|e| e.to_string())?;
+    Ok(rows_affected)
+}
 
-            Ok(Profile {
-                id,
-                user_id: "default-user".to_string(),
-                name: "You".to_string(),
-                role: "self".to_string(),
-                avatar_url: None,
-                color: Some("#3B82F6".to_string()),
-                initials: Some("ME".to_string()),
-                bio: None,
-                is_default: true,
-                created_at: now,
-                updated_at: now,
-            })
-        }
-        Err(e) => Err(e.to_string()),
+fn reassign_entries(
+    tx: &rusqlite::Transaction,
+    from_profile_id: &str,
+    to_profile_id: &str,
+) -> Result<usize, AppError> {
+    if from_profile_id == to_profile_id {
+        return Err("Cannot reassign entries to the same profile".into());
     }
+
+    tx.query_row(
+        "SELECT 1 FROM profiles WHERE id = ?1",
+        params![to_profile_id],
+        |_| Ok(()),
+    )
+    .map_err(|e| AppError::from_lookup(e, || "Reassignment profile does not exist".to_string()))?;
+
+    let now = chrono::Utc::now().timestamp_millis();
+    let rows_affected = tx.execute(
+        "UPDATE entries SET profile_id = ?1, updated_at = ?2 WHERE profile_id = ?3",
+        params![to_profile_id, now, from_profile_id],
+    )?;
+
+    Ok(rows_affected)
 }
 
+/// The profile marked as default, or `None` if no profile is
 #[tauri::command]
-pub fn get_profile_entry_count(db: State<Database>, profile_id: String) -> Result<i64, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn get_default_profile(db: State<Database>) -> Result<Option<Profile>, AppError> {
+    let conn = db.pool.get()?;
 
-    let count: i64 = conn
+    let profile = conn
         .query_row(
-            "SELECT COUNT(*) FROM entries WHERE profile_id = ?1",
-            params![profile_id],
-            |row| row.get(0),
+            "SELECT id, user_id, name, role, avatar_url, color, initials, bio, is_default, created_at, updated_at 
+             FROM profiles 
+             WHERE is_default = 1
+             LIMIT 1",
+            [],
+            |row| {
+                Ok(Profile {
+                    id: row.get(0)?,
+                    user_id: row.get(1)?,
+                    name: row.get(2)?,
+                    role: row.get(3)?,
+                    avatar_url: row.get(4)?,
+                    color: row.get(5)?,
+                    initials: row.get(6)?,
+                    bio: row.get(7)?,
+                    is_default: true,
+                    created_at: row.get(9)?,
+                    updated_at: row.get(10)?,
+                })
+            },
         )
-        .map_err(|e| e.to_string())?;
+        .optional()?;
+
+    Ok(profile)
+}
+
+#[tauri::command]
+pub fn get_profile_entry_count(db: State<Database>, profile_id: String) -> Result<i64, AppError> {
+    let conn = db.pool.get()?;
+
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM entries WHERE profile_id = ?1",
+        params![profile_id],
+        |row| row.get(0),
+    )?;
 
     Ok(count)
 }
@@ -372,15 +388,62 @@ pub fn get_profile_entry_count(db: State<Database>, profile_id: String) -> Resul
 // STREAM COMMANDS
 // ============================================================
 
+/// Column list matching `stream_from_row`
+const STREAM_COLUMNS: &str =
+    "id, user_id, title, description, tags, color, pinned, created_at, updated_at, archived, default_profile_id, word_goal, locked";
+
+/// Maps a row selected with `STREAM_COLUMNS`
+fn stream_from_row(row: &rusqlite::Row) -> rusqlite::Result<Stream> {
+    let tags_str: Option<String> = row.get(4)?;
+    let tags: Vec<String> = tags_str
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+
+    Ok(Stream {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        title: row.get(2)?,
+        description: row.get(3)?,
+        tags,
+        color: row.get(5)?,
+        pinned: row.get::<_, i32>(6)? != 0,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+        archived: row.get::<_, i32>(9)? != 0,
+        default_profile_id: row.get(10)?,
+        word_goal: row.get(11)?,
+        locked: row.get::<_, i32>(12)? != 0,
+    })
+}
+
+/// Accepts CSS hex colors in `#RGB` or `#RRGGBB` form, in either case
+fn validate_hex_color(color: &str) -> Result<(), AppError> {
+    let is_valid = color.strip_prefix('#').is_some_and(|hex| {
+        matches!(hex.len(), 3 | 6) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    });
+
+    if is_valid {
+        Ok(())
+    } else {
+        Err(format!("Invalid color '{}': expected #RGB or #RRGGBB", color).into())
+    }
+}
+
 #[tauri::command]
-pub fn create_stream(db: State<Database>, input: CreateStreamInput) -> Result<Stream, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn create_stream(db: State<Database>, input: CreateStreamInput) -> Result<Stream, AppError> {
+    if let Some(color) = &input.color {
+        validate_hex_color(color)?;
+    }
+
+    let mut conn = db.pool.get()?;
     let now = chrono::Utc::now().timestamp_millis();
     let id = uuid::Uuid::new_v4().to_string();
     let tags = input.tags.unwrap_or_default();
-    let tags_json = serde_json::to_string(&tags).map_err(|e| e.to_string())?;
+    let tags_json = serde_json::to_string(&tags)?;
 
-    conn.execute(
+    let tx = conn.transaction()?;
+
+    tx.execute(
         "INSERT INTO streams (id, user_id, title, description, tags, color, pinned, created_at, updated_at) 
          VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
         params![
@@ -394,8 +457,11 @@ pub fn create_stream(db: State<Database>, input: CreateStreamInput) -> Result<St
             now,
             now
         ],
-    )
-    .map_err(|e| e.to_string())?;
+    )?;
+
+    Database::sync_stream_tags(&tx, &id, &tags)?;
+
+    tx.commit()?;
 
     Ok(Stream {
         id,
@@ -407,6 +473,37 @@ pub fn create_stream(db: State<Database>, input: CreateStreamInput) -> Result<St
         pinned: false,
         created_at: now,
         updated_at: now,
+        archived: false,
+        default_profile_id: None,
+        word_goal: None,
+        locked: false,
+    })
+}
+
+/// Select and join shared by the stream list queries; callers append WHERE and GROUP BY
+const STREAM_METADATA_SELECT: &str = "SELECT 
+        s.id, s.user_id, s.title, s.pinned, s.color, s.tags, s.updated_at,
+        COUNT(e.id) as entry_count, s.archived
+    FROM streams s
+    LEFT JOIN entries e ON s.id = e.stream_id AND e.deleted_at IS NULL";
+
+/// Maps a row selected with `STREAM_METADATA_SELECT`
+fn stream_metadata_from_row(row: &rusqlite::Row) -> rusqlite::Result<StreamMetadata> {
+    let tags_str: Option<String> = row.get(5)?;
+    let tags: Vec<String> = tags_str
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default();
+
+    Ok(StreamMetadata {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        title: row.get(2)?,
+        pinned: row.get::<_, i32>(3)? != 0,
+        color: row.get(4)?,
+        tags,
+        last_updated: row.get(6)?,
+        entry_count: row.get(7)?,
+        archived: row.get::<_, i32>(8)? != 0,
     })
 }
 
@@ -414,168 +511,199 @@ pub fn create_stream(db: State<Database>, input: CreateStreamInput) -> Result<St
 pub fn get_all_streams(
     db: State<Database>,
     user_id: String,
-) -> Result<Vec<StreamMetadata>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    let mut stmt = conn
-        .prepare(
-            r#"
-            SELECT 
-                s.id, s.user_id, s.title, s.pinned, s.color, s.tags, s.updated_at,
-                COUNT(e.id) as entry_count
-            FROM streams s
-            LEFT JOIN entries e ON s.id = e.stream_id
-            WHERE s.user_id = ?
-            GROUP BY s.id
-            ORDER BY s.pinned DESC, s.updated_at DESC
-            "#,
-        )
-        .map_err(|e| e.to_string())?;
+    include_archived: Option<bool>,
+) -> Result<Vec<StreamMetadata>, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "{}
+             WHERE s.user_id = ?1 AND (?2 OR s.archived = 0)
+             GROUP BY s.id
+             ORDER BY s.pinned DESC, s.pin_order ASC, s.updated_at DESC",
+        STREAM_METADATA_SELECT
+    ))?;
 
     let streams = stmt
-        .query_map([user_id], |row| {
-            let tags_str: Option<String> = row.get(5)?;
-            let tags: Vec<String> = tags_str
-                .and_then(|s| serde_json::from_str(&s).ok())
-                .unwrap_or_default();
+        .query_map(
+            params![user_id, include_archived.unwrap_or(false)],
+            stream_metadata_from_row,
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
 
-            Ok(StreamMetadata {
-                id: row.get(0)?,
-                user_id: row.get(1)?,
-                title: row.get(2)?,
-                pinned: row.get::<_, i32>(3)? != 0,
-                color: row.get(4)?,
-                tags,
-                last_updated: row.get(6)?,
-                entry_count: row.get(7)?,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+    Ok(streams)
+}
+
+#[tauri::command]
+pub fn get_streams_by_tag(
+    db: State<Database>,
+    tag: String,
+) -> Result<Vec<StreamMetadata>, AppError> {
+    let conn = db.pool.get()?;
+
+    // Tags match case-insensitively
+    let mut stmt = conn.prepare(&format!(
+        "{}
+             WHERE s.archived = 0
+               AND EXISTS (
+                   SELECT 1 FROM stream_tags t
+                   WHERE t.stream_id = s.id AND LOWER(t.tag) = LOWER(?1)
+               )
+             GROUP BY s.id
+             ORDER BY s.pinned DESC, s.pin_order ASC, s.updated_at DESC",
+        STREAM_METADATA_SELECT
+    ))?;
+
+    let streams = stmt
+        .query_map(params![tag.trim()], stream_metadata_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
 
     Ok(streams)
 }
 
+/// `app_meta` key holding the id of the stream last opened by the user
+const LAST_OPENED_STREAM_KEY: &str = "last_opened_stream";
+
 #[tauri::command]
 pub fn get_stream_details(
     db: State<Database>,
     stream_id: String,
-) -> Result<StreamWithEntries, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<StreamWithEntries, AppError> {
+    let conn = db.pool.get()?;
 
     // Get stream
     let stream = conn
-        .query_row(
-            "SELECT id, user_id, title, description, tags, color, pinned, created_at, updated_at 
-             FROM streams WHERE id = ?1",
-            params![stream_id],
-            |row| {
-                let tags_str: Option<String> = row.get(3)?;
-                let tags: Vec<String> = tags_str
-                    .and_then(|s| serde_json::from_str(&s).ok())
-                    .unwrap_or_default();
-
-                Ok(Stream {
-                    id: row.get(0)?,
-                    user_id: row.get(1)?,
-                    title: row.get(2)?,
-                    description: row.get(3)?,
-                    tags,
-                    color: row.get(5)?,
-                    pinned: row.get::<_, i32>(6)? != 0,
-                    created_at: row.get(7)?,
-                    updated_at: row.get(8)?,
-                })
-            },
-        )
-        .map_err(|e| e.to_string())?;
+        .prepare_cached(&format!(
+            "SELECT {} FROM streams WHERE id = ?1",
+            STREAM_COLUMNS
+        ))
+        .and_then(|mut stmt| stmt.query_row(params![stream_id], stream_from_row))
+        .map_err(|e| AppError::from_lookup(e, || format!("Stream '{}' was deleted", stream_id)))?;
 
     // Get entries with full profile data
     let mut stmt = conn
-        .prepare(
-            "SELECT 
-                e.id, 
-                e.user_id,
-                e.stream_id, 
-                e.profile_id, 
-                e.role, 
-                e.content, 
-                e.sequence_id, 
-                e.version_head, 
-                e.is_staged, 
-                e.parent_context_ids, 
-                e.ai_metadata, 
-                e.created_at, 
-                e.updated_at,
-                p.id, p.user_id, p.name, p.role, p.avatar_url, p.color, p.initials, p.bio, p.is_default, p.created_at, p.updated_at
-             FROM entries e
-             LEFT JOIN profiles p ON e.profile_id = p.id
-             WHERE e.stream_id = ?1 
-             ORDER BY e.sequence_id ASC",
-        )
-        .map_err(|e| e.to_string())?;
-
+        .prepare_cached(
+            &format!(
+                "SELECT {}, 
+                    p.id, p.user_id, p.name, p.role, p.avatar_url, p.color, p.initials, p.bio, p.is_default, p.created_at, p.updated_at
+                 FROM entries e
+                 LEFT JOIN profiles p ON e.profile_id = p.id
+                 WHERE e.stream_id = ?1 AND e.deleted_at IS NULL
+                 ORDER BY e.sequence_id ASC, e.id ASC
+                 LIMIT ?2 OFFSET ?3",
+                ENTRY_COLUMNS
+            ),
+        )?;
+
+    // A negative LIMIT means "no limit" in SQLite, which keeps the old load-everything behavior
     let entries = stmt
-        .query_map(params![stream_id], |row| {
-            let content_str: String = row.get(5)?;
-            let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
-            let parent_ids_str: Option<String> = row.get(9)?;
-            let parent_context_ids: Option<Vec<String>> =
-                parent_ids_str.and_then(|s| serde_json::from_str(&s).ok());
-            let ai_metadata_str: Option<String> = row.get(10)?;
-            let ai_metadata: Option<AiMetadata> =
-                ai_metadata_str.and_then(|s| serde_json::from_str(&s).ok());
+        .query_map(
+            params![stream_id, limit.unwrap_or(-1), offset.unwrap_or(0)],
+            |row| {
+                let mut entry = entry_from_row(row)?;
+
+                // Construct profile if joined successfully
+                // Profile columns follow the entry columns
+                let p = ENTRY_COLUMN_COUNT;
+                entry.profile = if let Ok(id) = row.get::<_, String>(p) {
+                    Some(Profile {
+                        id,
+                        user_id: row.get(p + 1)?,
+                        name: row.get(p + 2)?,
+                        role: row.get(p + 3)?,
+                        avatar_url: row.get(p + 4)?,
+                        color: row.get(p + 5)?,
+                        initials: row.get(p + 6)?,
+                        bio: row.get(p + 7)?,
+                        is_default: row.get::<_, i32>(p + 8)? != 0,
+                        created_at: row.get(p + 9)?,
+                        updated_at: row.get(p + 10)?,
+                    })
+                } else {
+                    None
+                };
+
+                Ok(entry)
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
 
-            // Construct profile if joined successfully
-            let profile = if let Ok(id) = row.get::<_, String>(13) {
-                Some(Profile {
-                    id,
-                    user_id: row.get(14)?,
-                    name: row.get(15)?,
-                    role: row.get(16)?,
-                    avatar_url: row.get(17)?,
-                    color: row.get(18)?,
-                    initials: row.get(19)?,
-                    bio: row.get(20)?,
-                    is_default: row.get::<_, i32>(21)? != 0,
-                    created_at: row.get(22)?,
-                    updated_at: row.get(23)?,
-                })
-            } else {
-                None
-            };
+    Ok(StreamWithEntries { stream, entries })
+}
 
-            Ok(Entry {
-                id: row.get(0)?,
-                user_id: row.get(1)?,
-                stream_id: row.get(2)?,
-                profile_id: row.get(3)?,
-                role: row.get(4)?,
-                content,
-                sequence_id: row.get(6)?,
-                version_head: row.get(7)?,
-                is_staged: row.get::<_, i32>(8)? != 0,
-                parent_context_ids,
-                ai_metadata,
-                created_at: row.get(11)?,
-                updated_at: row.get(12)?,
-                profile,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+/// Reopens the stream the user last viewed. Returns None if there is none or it was
+/// deleted since, in which case the stale id is forgotten.
+#[tauri::command]
+pub fn get_last_opened_stream(db: State<Database>) -> Result<Option<StreamWithEntries>, AppError> {
+    let stream_id = {
+        let conn = db.pool.get()?;
+
+        let stream_id = match Database::get_meta(&conn, LAST_OPENED_STREAM_KEY)? {
+            Some(stream_id) => stream_id,
+            None => return Ok(None),
+        };
+
+        let exists = conn
+            .prepare_cached("SELECT 1 FROM streams WHERE id = ?1")
+            .and_then(|mut stmt| stmt.exists(params![stream_id]))?;
+
+        if !exists {
+            Database::delete_meta(&conn, LAST_OPENED_STREAM_KEY)?;
+            return Ok(None);
+        }
 
-    Ok(StreamWithEntries { stream, entries })
+        stream_id
+    };
+
+    get_stream_details(db, stream_id, None, None).map(Some)
+}
+
+/// Remembers `stream_id` as the stream the user has open, so the next launch reopens it
+#[tauri::command]
+pub fn set_last_opened_stream(db: State<Database>, stream_id: String) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+
+    conn.prepare_cached("SELECT 1 FROM streams WHERE id = ?1")
+        .and_then(|mut stmt| stmt.query_row(params![stream_id], |_| Ok(())))
+        .map_err(|e| {
+            AppError::from_lookup(e, || format!("Stream '{}' does not exist", stream_id))
+        })?;
+
+    Database::set_meta(&conn, LAST_OPENED_STREAM_KEY, &stream_id)?;
+
+    Ok(())
 }
 
 #[tauri::command]
-pub fn delete_stream(db: State<Database>, stream_id: String) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn delete_stream(db: State<Database>, stream_id: String) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    let exists = tx
+        .prepare_cached("SELECT 1 FROM streams WHERE id = ?1")
+        .and_then(|mut stmt| stmt.exists(params![stream_id]))?;
+
+    if exists {
+        let stream = read_exported_stream(&tx, &stream_id, true)?;
+        let deleted_at = tx
+            .prepare(
+                "SELECT id, deleted_at FROM entries WHERE stream_id = ?1 AND deleted_at IS NOT NULL",
+            )?
+            .query_map(params![stream_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+
+        record_undo(
+            &tx,
+            &UndoSnapshot::DeleteStream {
+                stream: Box::new(stream),
+                deleted_at,
+            },
+        )?;
+        tx.execute("DELETE FROM streams WHERE id = ?1", params![stream_id])?;
+    }
 
-    conn.execute("DELETE FROM streams WHERE id = ?1", params![stream_id])
-        .map_err(|e| e.to_string())?;
+    tx.commit()?;
 
     Ok(())
 }
@@ -587,679 +715,6623 @@ pub fn update_stream(
     title: Option<String>,
     description: Option<String>,
     pinned: Option<bool>,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    color: Option<String>,
+    tags: Option<Vec<String>>,
+) -> Result<(), AppError> {
+    if let Some(c) = color.as_deref().filter(|c| !c.is_empty()) {
+        validate_hex_color(c)?;
+    }
+
+    let mut conn = db.pool.get()?;
     let now = chrono::Utc::now().timestamp_millis();
 
     if let Some(t) = title {
         conn.execute(
             "UPDATE streams SET title = ?1, updated_at = ?2 WHERE id = ?3",
             params![t, now, stream_id],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
     if let Some(d) = description {
         conn.execute(
             "UPDATE streams SET description = ?1, updated_at = ?2 WHERE id = ?3",
             params![d, now, stream_id],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
     }
 
     if let Some(p) = pinned {
+        let was_pinned: bool = conn
+            .query_row(
+                "SELECT pinned FROM streams WHERE id = ?1",
+                params![stream_id],
+                |row| row.get::<_, i32>(0),
+            )
+            .optional()?
+            .is_some_and(|v| v != 0);
+
         conn.execute(
             "UPDATE streams SET pinned = ?1, updated_at = ?2 WHERE id = ?3",
             params![if p { 1 } else { 0 }, now, stream_id],
-        )
-        .map_err(|e| e.to_string())?;
+        )?;
+
+        // Newly pinned streams go last; re-pinning keeps the existing position
+        if p && !was_pinned {
+            Database::append_pin_order(&conn, &stream_id)?;
+        } else if !p {
+            conn.execute(
+                "UPDATE streams SET pin_order = NULL WHERE id = ?1",
+                params![stream_id],
+            )?;
+        }
+    }
+
+    // An empty color clears it
+    if let Some(c) = color {
+        conn.execute(
+            "UPDATE streams SET color = ?1, updated_at = ?2 WHERE id = ?3",
+            params![(!c.is_empty()).then_some(c), now, stream_id],
+        )?;
+    }
+
+    if let Some(tags) = tags {
+        let tx = conn.transaction()?;
+        let updated = tx.execute(
+            "UPDATE streams SET tags = ?1, updated_at = ?2 WHERE id = ?3",
+            params![serde_json::to_string(&tags)?, now, stream_id],
+        )?;
+        if updated > 0 {
+            Database::sync_stream_tags(&tx, &stream_id, &tags)?;
+        }
+        tx.commit()?;
     }
 
     Ok(())
 }
 
-// ============================================================
-// ENTRY COMMANDS
-// ============================================================
+/// Sets the order of pinned streams to match `ordered_stream_ids`.
+/// Ids of streams that aren't pinned are ignored.
+#[tauri::command]
+pub fn reorder_pinned_streams(
+    db: State<Database>,
+    ordered_stream_ids: Vec<String>,
+) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    for (position, stream_id) in ordered_stream_ids.iter().enumerate() {
+        tx.execute(
+            "UPDATE streams SET pin_order = ?1 WHERE id = ?2 AND pinned = 1",
+            params![position as i64, stream_id],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(())
+}
 
+/// Sets or clears (`None` or an empty string, as with `update_stream`) a stream's color
+/// without touching its other fields
 #[tauri::command]
-pub fn create_entry(db: State<Database>, input: CreateEntryInput) -> Result<Entry, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn update_stream_color(
+    db: State<Database>,
+    stream_id: String,
+    color: Option<String>,
+) -> Result<(), AppError> {
+    let color = color.filter(|c| !c.is_empty());
+    if let Some(c) = &color {
+        validate_hex_color(c)?;
+    }
+
+    let conn = db.pool.get()?;
     let now = chrono::Utc::now().timestamp_millis();
-    let id = uuid::Uuid::new_v4().to_string();
 
-    // Determine sequence_id and handle insertion logic
-    let sequence_id = if let Some(after_id) = input.insert_after_id {
-        // Find sequence_id of the target entry
-        let target_seq: i32 = conn
-            .query_row(
-                "SELECT sequence_id FROM entries WHERE id = ?1",
-                params![after_id],
-                |row| row.get(0),
-            )
-            .map_err(|e| e.to_string())?;
+    conn.execute(
+        "UPDATE streams SET color = ?1, updated_at = ?2 WHERE id = ?3",
+        params![color, now, stream_id],
+    )?;
 
-        // Shift following entries
-        conn.execute(
-            "UPDATE entries SET sequence_id = sequence_id + 1 WHERE stream_id = ?1 AND sequence_id > ?2",
-            params![input.stream_id, target_seq],
-        ).map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-        target_seq + 1
-    } else if let Some(before_id) = input.insert_before_id {
-        // Find sequence_id of the target entry
-        let target_seq: i32 = conn
-            .query_row(
-                "SELECT sequence_id FROM entries WHERE id = ?1",
-                params![before_id],
-                |row| row.get(0),
-            )
-            .map_err(|e| e.to_string())?;
+/// Sets or clears (`None`) the profile that new entries in the stream inherit
+/// when they are created without one
+#[tauri::command]
+pub fn set_stream_default_profile(
+    db: State<Database>,
+    stream_id: String,
+    profile_id: Option<String>,
+) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
 
-        // Shift target and following entries
-        conn.execute(
-            "UPDATE entries SET sequence_id = sequence_id + 1 WHERE stream_id = ?1 AND sequence_id >= ?2",
-            params![input.stream_id, target_seq],
-        ).map_err(|e| e.to_string())?;
+    if let Some(profile_id) = &profile_id {
+        let exists = conn
+            .prepare_cached("SELECT 1 FROM profiles WHERE id = ?1")
+            .and_then(|mut stmt| stmt.exists(params![profile_id]))?;
 
-        target_seq
-    } else {
-        // Get next sequence ID (append at the end)
-        let max_seq: i32 = conn
-            .query_row(
-                "SELECT COALESCE(MAX(sequence_id), 0) FROM entries WHERE stream_id = ?1",
-                params![input.stream_id],
-                |row| row.get(0),
-            )
-            .unwrap_or(0);
-        max_seq + 1
-    };
-
-    let content_str = serde_json::to_string(&input.content).map_err(|e| e.to_string())?;
-    let ai_metadata_str = input
-        .ai_metadata
-        .as_ref()
-        .map(|m| serde_json::to_string(m))
-        .transpose()
-        .map_err(|e| e.to_string())?;
-
-    // Serialize parent_context_ids if provided
-    let parent_context_ids_str = input
-        .parent_context_ids
-        .as_ref()
-        .map(|ids| serde_json::to_string(ids))
-        .transpose()
-        .map_err(|e| e.to_string())?;
+        if !exists {
+            return Err(AppError::new(
+                "NOT_FOUND",
+                &format!("Profile '{}' does not exist", profile_id),
+            ));
+        }
+    }
 
-    conn.execute(
-        "INSERT INTO entries (id, user_id, stream_id, profile_id, role, content, sequence_id, version_head, is_staged, parent_context_ids, ai_metadata, created_at, updated_at) 
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
-        params![id, input.user_id, input.stream_id, input.profile_id, input.role, content_str, sequence_id, 0, 0, parent_context_ids_str, ai_metadata_str, now, now],
-    )
-    .map_err(|e| e.to_string())?;
+    let updated = conn.execute(
+        "UPDATE streams SET default_profile_id = ?1, updated_at = ?2 WHERE id = ?3",
+        params![profile_id, now, stream_id],
+    )?;
 
-    // Update stream's updated_at
-    conn.execute(
-        "UPDATE streams SET updated_at = ?1 WHERE id = ?2",
-        params![now, input.stream_id],
-    )
-    .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(AppError::new(
+            "NOT_FOUND",
+            &format!("Stream '{}' does not exist", stream_id),
+        ));
+    }
 
-    Ok(Entry {
-        id,
-        user_id: input.user_id,
-        stream_id: input.stream_id,
-        profile_id: input.profile_id,
-        role: input.role,
-        content: input.content,
-        sequence_id,
-        version_head: 0,
-        is_staged: false,
-        parent_context_ids: input.parent_context_ids,
-        ai_metadata: input.ai_metadata,
-        created_at: now,
-        updated_at: now,
-        profile: None,
-    })
+    Ok(())
 }
 
+/// Sets the stream's target word count; `None` or 0 clears it
 #[tauri::command]
-pub fn update_entry_content(
+pub fn set_stream_goal(
     db: State<Database>,
-    entry_id: String,
-    content: serde_json::Value,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    stream_id: String,
+    goal: Option<i64>,
+) -> Result<(), AppError> {
+    if goal.is_some_and(|goal| goal < 0) {
+        return Err("Word goal must not be negative".into());
+    }
+    let goal = goal.filter(|goal| *goal > 0);
+
+    let conn = db.pool.get()?;
     let now = chrono::Utc::now().timestamp_millis();
-    let content_str = serde_json::to_string(&content).map_err(|e| e.to_string())?;
 
-    conn.execute(
-        "UPDATE entries SET content = ?1, updated_at = ?2 WHERE id = ?3",
-        params![content_str, now, entry_id],
-    )
-    .map_err(|e| e.to_string())?;
+    let updated = conn.execute(
+        "UPDATE streams SET word_goal = ?1, updated_at = ?2 WHERE id = ?3",
+        params![goal, now, stream_id],
+    )?;
 
-    // Update stream's updated_at
-    conn.execute(
-        r#"UPDATE streams SET updated_at = ?1 
-           WHERE id = (SELECT stream_id FROM entries WHERE id = ?2)"#,
-        params![now, entry_id],
-    )
-    .map_err(|e| e.to_string())?;
+    if updated == 0 {
+        return Err(AppError::new(
+            "NOT_FOUND",
+            &format!("Stream '{}' does not exist", stream_id),
+        ));
+    }
 
     Ok(())
 }
 
+/// Locks or unlocks a stream. Locked streams reject entry creation, edits,
+/// deletion and reordering with a `LOCKED` error.
 #[tauri::command]
-pub fn toggle_entry_staging(
+pub fn set_stream_locked(
     db: State<Database>,
-    entry_id: String,
-    is_staged: bool,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    stream_id: String,
+    locked: bool,
+) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
 
-    conn.execute(
-        "UPDATE entries SET is_staged = ?1 WHERE id = ?2",
-        params![if is_staged { 1 } else { 0 }, entry_id],
-    )
-    .map_err(|e| e.to_string())?;
+    let updated = conn.execute(
+        "UPDATE streams SET locked = ?1, updated_at = ?2 WHERE id = ?3",
+        params![if locked { 1 } else { 0 }, now, stream_id],
+    )?;
+
+    if updated == 0 {
+        return Err(AppError::new(
+            "NOT_FOUND",
+            &format!("Stream '{}' does not exist", stream_id),
+        ));
+    }
 
     Ok(())
 }
 
-#[tauri::command]
-pub fn update_entry_profile(
-    db: State<Database>,
-    entry_id: String,
-    profile_id: Option<String>,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let now = chrono::Utc::now().timestamp_millis();
+/// Fails with `LOCKED` if the stream is locked. Missing streams are left to the caller.
+fn ensure_stream_unlocked(conn: &rusqlite::Connection, stream_id: &str) -> Result<(), AppError> {
+    let locked: Option<bool> = conn
+        .query_row(
+            "SELECT locked != 0 FROM streams WHERE id = ?1",
+            params![stream_id],
+            |row| row.get(0),
+        )
+        .optional()?;
 
-    conn.execute(
-        "UPDATE entries SET profile_id = ?1, updated_at = ?2 WHERE id = ?3",
-        params![profile_id, now, entry_id],
-    )
-    .map_err(|e| e.to_string())?;
+    if locked == Some(true) {
+        return Err(AppError::new(
+            "LOCKED",
+            &format!("Stream '{}' is locked", stream_id),
+        ));
+    }
 
     Ok(())
 }
 
+/// `ensure_stream_unlocked` for the stream an entry belongs to
+fn ensure_entry_stream_unlocked(
+    conn: &rusqlite::Connection,
+    entry_id: &str,
+) -> Result<(), AppError> {
+    let stream_id: Option<String> = conn
+        .query_row(
+            "SELECT stream_id FROM entries WHERE id = ?1",
+            params![entry_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    match stream_id {
+        Some(stream_id) => ensure_stream_unlocked(conn, &stream_id),
+        None => Ok(()),
+    }
+}
+
 #[tauri::command]
-pub fn bulk_update_entry_profile(
+pub fn archive_stream(db: State<Database>, stream_id: String) -> Result<(), AppError> {
+    set_stream_archived(&db, &stream_id, true)
+}
+
+#[tauri::command]
+pub fn unarchive_stream(db: State<Database>, stream_id: String) -> Result<(), AppError> {
+    set_stream_archived(&db, &stream_id, false)
+}
+
+#[tauri::command]
+pub fn duplicate_stream(
     db: State<Database>,
-    entry_ids: Vec<String>,
-    profile_id: Option<String>,
-) -> Result<(), String> {
-    let mut conn = db.conn.lock().map_err(|e| e.to_string())?;
+    stream_id: String,
+    new_title: Option<String>,
+    include_versions: Option<bool>,
+) -> Result<Stream, AppError> {
+    let mut conn = db.pool.get()?;
     let now = chrono::Utc::now().timestamp_millis();
 
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let tx = conn.transaction()?;
 
-    for entry_id in entry_ids {
-        tx.execute(
-            "UPDATE entries SET profile_id = ?1, updated_at = ?2 WHERE id = ?3",
-            params![profile_id, now, entry_id],
-        )
-        .map_err(|e| e.to_string())?;
-    }
+    // Clone through the export shape so ids and parent_context_ids get remapped the same way
+    let mut exported = read_exported_stream(&tx, &stream_id, false)?;
 
-    tx.commit().map_err(|e| e.to_string())?;
+    exported.stream.title =
+        new_title.unwrap_or_else(|| format!("{} (Copy)", exported.stream.title));
+    exported.stream.pinned = false;
+    exported.stream.archived = false;
+    exported.stream.locked = false;
+    exported.stream.created_at = now;
+    exported.stream.updated_at = now;
 
-    Ok(())
-}
+    if !include_versions.unwrap_or(true) {
+        for exported_entry in &mut exported.entries {
+            exported_entry.versions.clear();
+            exported_entry.entry.version_head = 0;
+        }
+    }
 
-#[tauri::command]
-pub fn delete_entry(db: State<Database>, entry_id: String) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    let stream = insert_exported_stream(&tx, exported, false)?;
 
-    conn.execute("DELETE FROM entries WHERE id = ?1", params![entry_id])
-        .map_err(|e| e.to_string())?;
+    tx.commit()?;
 
-    Ok(())
+    Ok(stream)
 }
 
-#[tauri::command]
-pub fn bulk_delete_entries(db: State<Database>, entry_ids: Vec<String>) -> Result<(), String> {
-    let mut conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
+fn set_stream_archived(db: &Database, stream_id: &str, archived: bool) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
 
-    for entry_id in entry_ids {
-        tx.execute("DELETE FROM entries WHERE id = ?1", params![entry_id])
-            .map_err(|e| e.to_string())?;
-    }
+    let rows_affected = conn.execute(
+        "UPDATE streams SET archived = ?1, updated_at = ?2 WHERE id = ?3",
+        params![if archived { 1 } else { 0 }, now, stream_id],
+    )?;
 
-    tx.commit().map_err(|e| e.to_string())?;
+    if rows_affected == 0 {
+        return Err(format!("Stream '{}' does not exist", stream_id).into());
+    }
 
     Ok(())
 }
 
+/// Moves every entry of `source_stream_id` to the end of `target_stream_id`, keeping their
+/// relative order, unions the tags and deletes the emptied source stream.
 #[tauri::command]
-pub fn get_staged_entries(db: State<Database>, stream_id: String) -> Result<Vec<Entry>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn merge_streams(
+    db: State<Database>,
+    source_stream_id: String,
+    target_stream_id: String,
+) -> Result<(), AppError> {
+    if source_stream_id == target_stream_id {
+        return Err("Cannot merge a stream into itself".into());
+    }
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, user_id, stream_id, profile_id, role, content, sequence_id, version_head, is_staged, 
-                    parent_context_ids, ai_metadata, created_at, updated_at 
-             FROM entries 
-             WHERE stream_id = ?1 AND is_staged = 1
-             ORDER BY sequence_id ASC",
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction()?;
+
+    let stream_tags = |stream_id: &str| -> Result<Vec<String>, AppError> {
+        tx.query_row(
+            "SELECT tags FROM streams WHERE id = ?1",
+            params![stream_id],
+            |row| row.get::<_, Option<String>>(0),
         )
-        .map_err(|e| e.to_string())?;
+        .map(parse_tags)
+        .map_err(|e| AppError::from_lookup(e, || format!("Stream '{}' does not exist", stream_id)))
+    };
+    let source_tags = stream_tags(&source_stream_id)?;
+    let mut tags = stream_tags(&target_stream_id)?;
 
-    let entries = stmt
-        .query_map(params![stream_id], |row| {
-            let content_str: String = row.get(5)?;
-            let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
-            let parent_ids_str: Option<String> = row.get(9)?;
-            let parent_context_ids: Option<Vec<String>> =
-                parent_ids_str.and_then(|s| serde_json::from_str(&s).ok());
-            let ai_metadata_str: Option<String> = row.get(10)?;
-            let ai_metadata: Option<AiMetadata> =
-                ai_metadata_str.and_then(|s| serde_json::from_str(&s).ok());
+    ensure_stream_unlocked(&tx, &source_stream_id)?;
+    ensure_stream_unlocked(&tx, &target_stream_id)?;
 
-            Ok(Entry {
-                id: row.get(0)?,
-                user_id: row.get(1)?,
-                stream_id: row.get(2)?,
-                profile_id: row.get(3)?,
-                role: row.get(4)?,
-                content,
-                sequence_id: row.get(6)?,
-                version_head: row.get(7)?,
-                is_staged: true,
-                parent_context_ids,
-                ai_metadata,
-                created_at: row.get(11)?,
-                updated_at: row.get(12)?,
-                profile: None,
-            })
-        })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+    for tag in source_tags {
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
 
-    Ok(entries)
-}
+    let max_seq: i32 = tx.query_row(
+        "SELECT COALESCE(MAX(sequence_id), 0) FROM entries WHERE stream_id = ?1",
+        params![target_stream_id],
+        |row| row.get(0),
+    )?;
+
+    // Soft-deleted entries move too, so they can still be restored after the merge
+    let source_entry_ids: Vec<String> = {
+        let mut stmt = tx.prepare(
+            "SELECT id FROM entries WHERE stream_id = ?1 ORDER BY sequence_id ASC, id ASC",
+        )?;
+        let ids = stmt
+            .query_map(params![source_stream_id], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        ids
+    };
 
-#[tauri::command]
-pub fn clear_all_staging(db: State<Database>, stream_id: String) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    // Entry ids are kept, so parent_context_ids, versions and spotlights stay valid as-is
+    {
+        let mut update =
+            tx.prepare("UPDATE entries SET stream_id = ?1, sequence_id = ?2 WHERE id = ?3")?;
 
-    conn.execute(
-        "UPDATE entries SET is_staged = 0 WHERE stream_id = ?1",
-        params![stream_id],
-    )
-    .map_err(|e| e.to_string())?;
+        for (i, entry_id) in source_entry_ids.iter().enumerate() {
+            update.execute(params![target_stream_id, max_seq + 1 + i as i32, entry_id])?;
+        }
+    }
+
+    let tags_json = serde_json::to_string(&tags)?;
+    tx.execute(
+        "UPDATE streams SET tags = ?1, updated_at = ?2 WHERE id = ?3",
+        params![tags_json, now, target_stream_id],
+    )?;
+    Database::sync_stream_tags(&tx, &target_stream_id, &tags)?;
+
+    // Only the source's pending blocks remain to cascade
+    tx.execute(
+        "DELETE FROM streams WHERE id = ?1",
+        params![source_stream_id],
+    )?;
+
+    tx.commit()?;
 
     Ok(())
 }
 
-// ============================================================
-// VERSION COMMANDS
-// ============================================================
-
+/// Moves entries from `at_sequence_id` onward into a new stream, renumbered from 1.
+/// The new stream keeps the source's tags and color. Parent context links between the two
+/// halves are dropped; links within a half, or to other streams, are kept.
 #[tauri::command]
-pub fn commit_entry_version(
+pub fn split_stream(
     db: State<Database>,
-    entry_id: String,
-    commit_message: Option<String>,
-) -> Result<EntryVersion, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    stream_id: String,
+    at_sequence_id: i32,
+    new_title: String,
+) -> Result<Stream, AppError> {
+    let mut conn = db.pool.get()?;
     let now = chrono::Utc::now().timestamp_millis();
-    let version_id = uuid::Uuid::new_v4().to_string();
 
-    // Get current entry content and version
-    let (content_str, current_version): (String, i32) = conn
+    let tx = conn.transaction()?;
+
+    let source = tx
         .query_row(
-            "SELECT content, version_head FROM entries WHERE id = ?1",
-            params![entry_id],
-            |row| Ok((row.get(0)?, row.get(1)?)),
+            &format!("SELECT {} FROM streams WHERE id = ?1", STREAM_COLUMNS),
+            params![stream_id],
+            stream_from_row,
         )
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| {
+            AppError::from_lookup(e, || format!("Stream '{}' does not exist", stream_id))
+        })?;
+
+    ensure_stream_unlocked(&tx, &stream_id)?;
+
+    // Soft-deleted entries are split too, so they can still be restored in their half
+    let entries: Vec<(String, i32, Option<String>)> = {
+        let mut stmt = tx.prepare(
+            "SELECT id, sequence_id, parent_context_ids FROM entries 
+                 WHERE stream_id = ?1 
+                 ORDER BY sequence_id ASC, id ASC",
+        )?;
+        let rows = stmt
+            .query_map(params![stream_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
 
-    let new_version = current_version + 1;
+    let moved: HashSet<&str> = entries
+        .iter()
+        .filter(|(_, seq, _)| *seq >= at_sequence_id)
+        .map(|(id, _, _)| id.as_str())
+        .collect();
+    let kept: HashSet<&str> = entries
+        .iter()
+        .filter(|(_, seq, _)| *seq < at_sequence_id)
+        .map(|(id, _, _)| id.as_str())
+        .collect();
+
+    if moved.is_empty() {
+        return Err(format!(
+            "No entries at or after sequence {} to split off",
+            at_sequence_id
+        )
+        .into());
+    }
 
-    // Create version snapshot
-    conn.execute(
-        "INSERT INTO entry_versions (id, entry_id, version_number, content_snapshot, commit_message, committed_at) 
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        params![version_id, entry_id, new_version, content_str, commit_message, now],
-    )
-    .map_err(|e| e.to_string())?;
+    let new_stream = Stream {
+        id: uuid::Uuid::new_v4().to_string(),
+        user_id: source.user_id,
+        title: new_title,
+        description: None,
+        tags: source.tags,
+        color: source.color,
+        pinned: false,
+        created_at: now,
+        updated_at: now,
+        archived: false,
+        default_profile_id: source.default_profile_id,
+        word_goal: None,
+        locked: false,
+    };
+    let tags_json = serde_json::to_string(&new_stream.tags)?;
 
-    // Update entry's version_head
-    conn.execute(
-        "UPDATE entries SET version_head = ?1 WHERE id = ?2",
-        params![new_version, entry_id],
-    )
-    .map_err(|e| e.to_string())?;
+    tx.execute(
+        "INSERT INTO streams (id, user_id, title, description, tags, color, pinned, created_at, updated_at, default_profile_id) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![
+            new_stream.id,
+            new_stream.user_id,
+            new_stream.title,
+            new_stream.description,
+            tags_json,
+            new_stream.color,
+            0,
+            now,
+            now,
+            new_stream.default_profile_id
+        ],
+    )?;
+    Database::sync_stream_tags(&tx, &new_stream.id, &new_stream.tags)?;
+
+    {
+        let mut move_stmt = tx.prepare(
+            "UPDATE entries SET stream_id = ?1, sequence_id = ?2, parent_context_ids = ?3 
+                 WHERE id = ?4",
+        )?;
+        let mut parents_stmt =
+            tx.prepare("UPDATE entries SET parent_context_ids = ?1 WHERE id = ?2")?;
+
+        let mut next_sequence_id = 1;
+        for (entry_id, sequence_id, parent_ids_str) in &entries {
+            let is_moved = *sequence_id >= at_sequence_id;
+            let other_half = if is_moved { &kept } else { &moved };
+
+            let parent_ids: Option<Vec<String>> = parent_ids_str
+                .as_deref()
+                .and_then(|s| serde_json::from_str(s).ok());
+            let remaining: Option<Vec<String>> = parent_ids.as_ref().map(|ids| {
+                ids.iter()
+                    .filter(|id| !other_half.contains(context_entry_id(id)))
+                    .cloned()
+                    .collect()
+            });
+            let changed = remaining.as_ref().map(Vec::len) != parent_ids.as_ref().map(Vec::len);
+
+            // A list emptied by the split is stored as no parents at all
+            let remaining_str = remaining
+                .filter(|ids| !ids.is_empty())
+                .map(|ids| serde_json::to_string(&ids))
+                .transpose()?;
+
+            if is_moved {
+                move_stmt.execute(params![
+                    new_stream.id,
+                    next_sequence_id,
+                    if changed {
+                        remaining_str
+                    } else {
+                        parent_ids_str.clone()
+                    },
+                    entry_id
+                ])?;
+                next_sequence_id += 1;
+            } else if changed {
+                parents_stmt.execute(params![remaining_str, entry_id])?;
+            }
+        }
+    }
 
-    let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
+    tx.execute(
+        "UPDATE streams SET updated_at = ?1 WHERE id = ?2",
+        params![now, stream_id],
+    )?;
 
-    Ok(EntryVersion {
-        id: version_id,
-        entry_id,
-        version_number: new_version,
-        content_snapshot: content,
-        commit_message,
-        committed_at: now,
-    })
+    tx.commit()?;
+
+    Ok(new_stream)
 }
 
 #[tauri::command]
-pub fn get_entry_versions(
-    db: State<Database>,
-    entry_id: String,
-) -> Result<Vec<EntryVersion>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, entry_id, version_number, content_snapshot, commit_message, committed_at 
-             FROM entry_versions 
-             WHERE entry_id = ?1 
-             ORDER BY version_number DESC",
-        )
-        .map_err(|e| e.to_string())?;
-
-    let versions = stmt
-        .query_map(params![entry_id], |row| {
-            let content_str: String = row.get(3)?;
-            let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
-
-            Ok(EntryVersion {
-                id: row.get(0)?,
-                entry_id: row.get(1)?,
-                version_number: row.get(2)?,
-                content_snapshot: content,
-                commit_message: row.get(4)?,
-                committed_at: row.get(5)?,
-            })
+pub fn get_available_templates() -> Result<Vec<TemplateInfo>, AppError> {
+    Ok(templates::TEMPLATES
+        .iter()
+        .map(|template| TemplateInfo {
+            id: template.id.to_string(),
+            name: template.name.to_string(),
+            description: template.description.to_string(),
         })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        .collect())
+}
 
-    Ok(versions)
+/// Recreates the welcome stream on demand
+#[tauri::command]
+pub fn reset_tutorial(db: State<Database>) -> Result<Stream, AppError> {
+    db.reset_tutorial().map_err(AppError::from)
 }
 
 #[tauri::command]
-pub fn get_latest_version(
+pub fn create_stream_from_template(
     db: State<Database>,
-    entry_id: String,
-) -> Result<Option<EntryVersion>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-
-    let result = conn.query_row(
-        "SELECT id, entry_id, version_number, content_snapshot, commit_message, committed_at 
-         FROM entry_versions 
-         WHERE entry_id = ?1 
-         ORDER BY version_number DESC 
-         LIMIT 1",
-        params![entry_id],
-        |row| {
-            let content_str: String = row.get(3)?;
-            let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
-
-            Ok(EntryVersion {
-                id: row.get(0)?,
-                entry_id: row.get(1)?,
-                version_number: row.get(2)?,
-                content_snapshot: content,
-                commit_message: row.get(4)?,
-                committed_at: row.get(5)?,
-            })
-        },
-    );
-
-    match result {
-        Ok(version) => Ok(Some(version)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.to_string()),
-    }
+    user_id: String,
+    template_id: String,
+    title: String,
+) -> Result<Stream, AppError> {
+    let template = templates::find(&template_id)
+        .ok_or_else(|| format!("Unknown template '{}'", template_id))?;
+
+    // A blank title falls back to the template name
+    let title = title.trim();
+    db.create_stream_from_template(template, &user_id, (!title.is_empty()).then_some(title))
+        .map_err(AppError::from)
 }
 
 #[tauri::command]
-pub fn get_version_by_number(
+pub fn get_stream_stats(
     db: State<Database>,
-    entry_id: String,
-    version_number: i32,
-) -> Result<Option<EntryVersion>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    stream_id: String,
+    include_ai: Option<bool>,
+) -> Result<StreamStats, AppError> {
+    let conn = db.pool.get()?;
 
-    let result = conn.query_row(
-        "SELECT id, entry_id, version_number, content_snapshot, commit_message, committed_at 
-         FROM entry_versions 
-         WHERE entry_id = ?1 AND version_number = ?2",
-        params![entry_id, version_number],
-        |row| {
-            let content_str: String = row.get(3)?;
-            let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
+    let (entry_count, first_entry_at, last_updated_at): (i64, Option<i64>, Option<i64>) = conn
+        .query_row(
+            "SELECT COUNT(*), MIN(created_at), MAX(updated_at) 
+             FROM entries 
+             WHERE stream_id = ?1 AND deleted_at IS NULL",
+            params![stream_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+
+    let version_count: i64 = conn.query_row(
+        "SELECT COUNT(*) 
+             FROM entry_versions v 
+             JOIN entries e ON e.id = v.entry_id 
+             WHERE e.stream_id = ?1 AND e.deleted_at IS NULL",
+        params![stream_id],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT content FROM entries 
+             WHERE stream_id = ?1 AND deleted_at IS NULL AND is_staged = 0 
+               AND (?2 OR role != 'ai')",
+    )?;
+
+    let contents = stmt
+        .query_map(params![stream_id, include_ai.unwrap_or(false)], |row| {
+            row.get::<_, String>(0)
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut word_count = 0;
+    let mut character_count = 0;
+    for content_str in &contents {
+        let text = content::extract_plain_text_from_str(content_str);
+        word_count += content::word_count(&text) as i64;
+        character_count += content::character_count(&text) as i64;
+    }
 
-            Ok(EntryVersion {
-                id: row.get(0)?,
-                entry_id: row.get(1)?,
-                version_number: row.get(2)?,
-                content_snapshot: content,
-                commit_message: row.get(4)?,
-                committed_at: row.get(5)?,
-            })
-        },
-    );
+    let goal: Option<i64> = conn
+        .query_row(
+            "SELECT word_goal FROM streams WHERE id = ?1",
+            params![stream_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten();
+    let progress_ratio = goal.map(|goal| (word_count as f64 / goal as f64).min(1.0));
+
+    Ok(StreamStats {
+        entry_count,
+        word_count,
+        character_count,
+        version_count,
+        first_entry_at,
+        last_updated_at,
+        goal,
+        progress_ratio,
+    })
+}
 
-    match result {
-        Ok(version) => Ok(Some(version)),
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.to_string()),
-    }
+/// Words in user entries created since `since_timestamp`, for the focus-mode counter.
+/// An approximation: without history snapshots, edits to older entries aren't counted.
+#[tauri::command]
+pub fn get_session_word_delta(
+    db: State<Database>,
+    stream_id: String,
+    since_timestamp: i64,
+) -> Result<i64, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT content FROM entries 
+             WHERE stream_id = ?1 AND created_at >= ?2 
+               AND deleted_at IS NULL AND role != 'ai'",
+    )?;
+
+    let contents = stmt
+        .query_map(params![stream_id, since_timestamp], |row| {
+            row.get::<_, String>(0)
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(contents
+        .iter()
+        .map(|content_str| {
+            content::word_count(&content::extract_plain_text_from_str(content_str)) as i64
+        })
+        .sum())
 }
 
+/// Milliseconds in a day, for bucketing `created_at` timestamps
+const DAY_MS: i64 = 86_400_000;
+
 #[tauri::command]
-pub fn revert_to_version(
+pub fn get_stream_role_breakdown(
     db: State<Database>,
-    entry_id: String,
-    version_number: i32,
-) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let now = chrono::Utc::now().timestamp_millis();
+    stream_id: String,
+) -> Result<RoleBreakdown, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut user_count = 0;
+    let mut ai_count = 0;
+    {
+        let mut stmt = conn.prepare(
+            "SELECT role, COUNT(*) FROM entries 
+                 WHERE stream_id = ?1 AND deleted_at IS NULL 
+                 GROUP BY role",
+        )?;
+        let rows = stmt.query_map(params![stream_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            match row? {
+                (role, count) if role == "ai" => ai_count = count,
+                (_, count) => user_count += count,
+            }
+        }
+    }
 
-    // Get the version's content
-    let content_str: String = conn
-        .query_row(
-            "SELECT content_snapshot FROM entry_versions WHERE entry_id = ?1 AND version_number = ?2",
-            params![entry_id, version_number],
-            |row| row.get(0),
-        )
-        .map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare(
+        "SELECT e.profile_id, COALESCE(p.name, 'Unassigned'), COUNT(*) 
+             FROM entries e 
+             LEFT JOIN profiles p ON p.id = e.profile_id 
+             WHERE e.stream_id = ?1 AND e.deleted_at IS NULL 
+             GROUP BY e.profile_id 
+             ORDER BY COUNT(*) DESC, 2 ASC",
+    )?;
 
-    // Update entry with reverted content
-    conn.execute(
-        "UPDATE entries SET content = ?1, updated_at = ?2 WHERE id = ?3",
-        params![content_str, now, entry_id],
-    )
-    .map_err(|e| e.to_string())?;
+    let by_profile = stmt
+        .query_map(params![stream_id], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(RoleBreakdown {
+        user_count,
+        ai_count,
+        by_profile,
+    })
+}
 
-    Ok(())
+#[tauri::command]
+pub fn get_workspace_stats(db: State<Database>) -> Result<WorkspaceStats, AppError> {
+    let conn = db.pool.get()?;
+
+    let (total_streams, total_entries, total_ai_entries, total_commits): (i64, i64, i64, i64) =
+        conn.query_row(
+            "SELECT 
+                (SELECT COUNT(*) FROM streams),
+                (SELECT COUNT(*) FROM entries WHERE deleted_at IS NULL),
+                (SELECT COUNT(*) FROM entries WHERE deleted_at IS NULL AND role = 'ai'),
+                (SELECT COUNT(*) FROM entry_versions v 
+                 JOIN entries e ON e.id = v.entry_id 
+                 WHERE e.deleted_at IS NULL)",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT created_at / ?1, content FROM entries 
+             WHERE deleted_at IS NULL AND role != 'ai'",
+    )?;
+
+    let rows = stmt
+        .query_map(params![DAY_MS], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut words_per_day: BTreeMap<i64, i64> = BTreeMap::new();
+    for (day, content_str) in &rows {
+        let text = content::extract_plain_text_from_str(content_str);
+        *words_per_day.entry(*day).or_insert(0) += content::word_count(&text) as i64;
+    }
+
+    let total_words = words_per_day.values().sum();
+    let words_by_day = words_per_day
+        .into_iter()
+        .map(|(day, words)| (day * DAY_MS, words))
+        .collect();
+
+    Ok(WorkspaceStats {
+        total_streams,
+        total_entries,
+        total_words,
+        total_ai_entries,
+        total_commits,
+        words_by_day,
+    })
 }
 
 // ============================================================
-// BRIDGE COMMANDS
+// TAG COMMANDS
 // ============================================================
 
+fn parse_tags(tags_str: Option<String>) -> Vec<String> {
+    tags_str
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
 #[tauri::command]
-pub fn generate_bridge_key() -> String {
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
-    let chars: Vec<char> = "abcdefghijklmnopqrstuvwxyz0123456789".chars().collect();
-    (0..4)
-        .map(|_| chars[rng.gen_range(0..chars.len())])
-        .collect()
+pub fn get_all_tags(db: State<Database>) -> Result<Vec<TagCount>, AppError> {
+    let conn = db.pool.get()?;
+
+    // stream_tags holds each tag once per stream, even if the array repeats it
+    let mut stmt = conn.prepare(
+        "SELECT tag, COUNT(*) FROM stream_tags 
+             GROUP BY tag 
+             ORDER BY COUNT(*) DESC, tag ASC",
+    )?;
+
+    let tags = stmt
+        .query_map([], |row| {
+            Ok(TagCount {
+                tag: row.get(0)?,
+                count: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(tags)
 }
 
 #[tauri::command]
-pub fn validate_bridge_key(input_text: String, expected_key: String) -> bool {
-    // Robust regex to handle HTML entities
-    let pattern =
-        regex::Regex::new(r#"(?:<|&lt;)!-{2}\s*bridge\s*:\s*([a-zA-Z0-9]+)\s*-{2}(?:>|&gt;)"#)
-            .unwrap();
+pub fn rename_tag(db: State<Database>, old: String, new: String) -> Result<usize, AppError> {
+    let new = new.trim().to_string();
+    if new.is_empty() {
+        return Err("Tag name cannot be empty".into());
+    }
+
+    let mut conn = db.pool.get()?;
+
+    rewrite_tags(&mut conn, &old, |tags| {
+        let mut seen = HashSet::new();
+        tags.into_iter()
+            .map(|tag| if tag == old { new.clone() } else { tag })
+            .filter(|tag| seen.insert(tag.clone()))
+            .collect()
+    })
+}
+
+#[tauri::command]
+pub fn delete_tag(db: State<Database>, tag: String) -> Result<usize, AppError> {
+    let mut conn = db.pool.get()?;
+
+    rewrite_tags(&mut conn, &tag, |tags| {
+        tags.into_iter().filter(|t| *t != tag).collect()
+    })
+}
+
+/// Applies `rewrite` to the tag array of every stream tagged `tag`, in one transaction.
+/// Returns how many streams changed.
+fn rewrite_tags(
+    conn: &mut rusqlite::Connection,
+    tag: &str,
+    rewrite: impl Fn(Vec<String>) -> Vec<String>,
+) -> Result<usize, AppError> {
+    let tx = conn.transaction()?;
+
+    let streams: Vec<(String, Option<String>)> = {
+        let mut stmt = tx.prepare(
+            "SELECT s.id, s.tags FROM streams s 
+                 JOIN stream_tags t ON t.stream_id = s.id 
+                 WHERE t.tag = ?1",
+        )?;
+        let rows = stmt
+            .query_map(params![tag], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
 
-    if let Some(captures) = pattern.captures(&input_text) {
-        if let Some(found_key) = captures.get(1) {
-            return found_key.as_str().to_lowercase() == expected_key.to_lowercase();
+    let changed = streams.len();
+    {
+        let mut update = tx.prepare("UPDATE streams SET tags = ?1 WHERE id = ?2")?;
+
+        for (stream_id, tags_str) in streams {
+            let tags = rewrite(parse_tags(tags_str));
+            let tags_json = serde_json::to_string(&tags)?;
+            update.execute(params![tags_json, stream_id])?;
+            Database::sync_stream_tags(&tx, &stream_id, &tags)?;
         }
     }
 
-    false
+    tx.commit()?;
+
+    Ok(changed)
+}
+
+// ============================================================
+// ENTRY COMMANDS
+// ============================================================
+
+/// Column list matching `entry_from_row`; queries alias `entries` as `e`
+const ENTRY_COLUMNS: &str =
+    "e.id, e.user_id, e.stream_id, e.profile_id, e.role, e.content, e.sequence_id, \
+     e.version_head, e.is_staged, e.parent_context_ids, e.ai_metadata, e.created_at, e.updated_at, \
+     e.is_pinned, e.color, e.label";
+
+/// Number of columns in `ENTRY_COLUMNS`, i.e. the index of the first column selected after it
+const ENTRY_COLUMN_COUNT: usize = 16;
+
+/// Maps a row selected with `ENTRY_COLUMNS`. The profile is left unset.
+fn entry_from_row(row: &rusqlite::Row) -> rusqlite::Result<Entry> {
+    let content_str: String = row.get(5)?;
+    let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
+    let parent_ids_str: Option<String> = row.get(9)?;
+    let parent_context_ids: Option<Vec<String>> =
+        parent_ids_str.and_then(|s| serde_json::from_str(&s).ok());
+    let ai_metadata_str: Option<String> = row.get(10)?;
+    let ai_metadata: Option<AiMetadata> =
+        ai_metadata_str.and_then(|s| serde_json::from_str(&s).ok());
+
+    Ok(Entry {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        stream_id: row.get(2)?,
+        profile_id: row.get(3)?,
+        role: row.get(4)?,
+        content,
+        sequence_id: row.get(6)?,
+        version_head: row.get(7)?,
+        is_staged: row.get::<_, i32>(8)? != 0,
+        parent_context_ids,
+        ai_metadata,
+        created_at: row.get(11)?,
+        updated_at: row.get(12)?,
+        is_pinned: row.get::<_, i32>(13)? != 0,
+        color: row.get(14)?,
+        label: row.get(15)?,
+        profile: None,
+    })
 }
 
 #[tauri::command]
-pub fn extract_bridge_key(input_text: String) -> Option<String> {
-    let pattern =
-        regex::Regex::new(r#"(?:<|&lt;)!-{2}\s*bridge\s*:\s*([a-zA-Z0-9]+)\s*-{2}(?:>|&gt;)"#)
-            .unwrap();
+pub fn create_entry(db: State<Database>, input: CreateEntryInput) -> Result<Entry, AppError> {
+    content::validate_doc(&input.content)?;
 
-    pattern
-        .captures(&input_text)
-        .and_then(|c| c.get(1))
-        .map(|m| m.as_str().to_lowercase())
+    if let Some(metadata) = &input.ai_metadata {
+        warn_unknown_provider(metadata);
+    }
+
+    let mut conn = db.pool.get()?;
+
+    // Sequence shifting, insert, and stream bump must succeed or fail together
+    let tx = conn.transaction()?;
+
+    ensure_stream_unlocked(&tx, &input.stream_id)?;
+
+    // Determine sequence_id and handle insertion logic
+    let sequence_id = if let Some(after_id) = &input.insert_after_id {
+        // Find sequence_id of the target entry
+        let target_seq: i32 = tx.query_row(
+            "SELECT sequence_id FROM entries WHERE id = ?1",
+            params![after_id],
+            |row| row.get(0),
+        )?;
+
+        // Shift following entries
+        tx.execute(
+            "UPDATE entries SET sequence_id = sequence_id + 1 WHERE stream_id = ?1 AND sequence_id > ?2",
+            params![input.stream_id, target_seq],
+        )?;
+
+        target_seq + 1
+    } else if let Some(before_id) = &input.insert_before_id {
+        // Find sequence_id of the target entry
+        let target_seq: i32 = tx.query_row(
+            "SELECT sequence_id FROM entries WHERE id = ?1",
+            params![before_id],
+            |row| row.get(0),
+        )?;
+
+        // Shift target and following entries
+        tx.execute(
+            "UPDATE entries SET sequence_id = sequence_id + 1 WHERE stream_id = ?1 AND sequence_id >= ?2",
+            params![input.stream_id, target_seq],
+        )?;
+
+        target_seq
+    } else {
+        // Get next sequence ID (append at the end)
+        let max_seq: i32 = tx
+            .prepare_cached(
+                "SELECT COALESCE(MAX(sequence_id), 0) FROM entries WHERE stream_id = ?1",
+            )
+            .and_then(|mut stmt| stmt.query_row(params![input.stream_id], |row| row.get(0)))
+            .unwrap_or(0);
+        max_seq + 1
+    };
+
+    let entry = insert_entry(&tx, input, sequence_id)?;
+
+    tx.commit()?;
+
+    Ok(entry)
 }
 
 #[tauri::command]
-pub fn create_pending_block(
+pub fn create_entry_at(
+    db: State<Database>,
+    input: CreateEntryInput,
+    after_sequence_id: i32,
+) -> Result<Entry, AppError> {
+    content::validate_doc(&input.content)?;
+
+    if let Some(metadata) = &input.ai_metadata {
+        warn_unknown_provider(metadata);
+    }
+
+    let mut conn = db.pool.get()?;
+
+    let tx = conn.transaction()?;
+
+    ensure_stream_unlocked(&tx, &input.stream_id)?;
+
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM entries WHERE stream_id = ?1 AND sequence_id = ?2)",
+        params![input.stream_id, after_sequence_id],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        return Err(format!(
+            "No entry at sequence {} in stream {}",
+            after_sequence_id, input.stream_id
+        )
+        .into());
+    }
+
+    tx.execute(
+        "UPDATE entries SET sequence_id = sequence_id + 1 WHERE stream_id = ?1 AND sequence_id > ?2",
+        params![input.stream_id, after_sequence_id],
+    )?;
+
+    let entry = insert_entry(&tx, input, after_sequence_id + 1)?;
+
+    tx.commit()?;
+
+    Ok(entry)
+}
+
+/// Appends many entries to a stream in one transaction, for importing notes from
+/// other apps. Each input's `stream_id` and insert position are ignored; entries
+/// take consecutive sequence ids after the current last one, in the given order.
+/// Nothing is inserted if any entry fails.
+#[tauri::command]
+pub fn bulk_create_entries(
     db: State<Database>,
-    user_id: String,
     stream_id: String,
-    bridge_key: String,
-    staged_context_ids: Vec<String>,
-    directive: String,
-) -> Result<PendingBlock, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+    entries: Vec<CreateEntryInput>,
+) -> Result<Vec<Entry>, AppError> {
+    for input in &entries {
+        content::validate_doc(&input.content)?;
+
+        if let Some(metadata) = &input.ai_metadata {
+            warn_unknown_provider(metadata);
+        }
+    }
+
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    ensure_stream_unlocked(&tx, &stream_id)?;
+
+    let max_seq: i32 = tx
+        .query_row(
+            "SELECT (SELECT COALESCE(MAX(sequence_id), 0) FROM entries WHERE stream_id = ?1)
+             FROM streams WHERE id = ?1",
+            params![stream_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| {
+            AppError::from_lookup(e, || format!("Stream '{}' does not exist", stream_id))
+        })?;
+
+    let mut created = Vec::with_capacity(entries.len());
+    for (sequence_id, input) in (max_seq + 1..).zip(entries) {
+        let input = CreateEntryInput {
+            stream_id: stream_id.clone(),
+            insert_after_id: None,
+            insert_before_id: None,
+            ..input
+        };
+        created.push(insert_entry(&tx, input, sequence_id)?);
+    }
+
+    tx.commit()?;
+
+    Ok(created)
+}
+
+/// Unknown providers are still stored; the registry only covers the common ones
+fn warn_unknown_provider(metadata: &AiMetadata) {
+    if providers::find(&metadata.provider).is_none() {
+        log::warn!(
+            "Unrecognized AI provider '{}' (model '{}')",
+            metadata.provider,
+            metadata.model
+        );
+    }
+}
+
+/// Inserts a new entry at `sequence_id` and bumps its stream. Callers make room
+/// in the sequence first. Entries without a profile take the stream's default,
+/// and explicit timestamps from importers are kept as given.
+fn insert_entry(
+    tx: &rusqlite::Transaction,
+    mut input: CreateEntryInput,
+    sequence_id: i32,
+) -> Result<Entry, AppError> {
     let now = chrono::Utc::now().timestamp_millis();
     let id = uuid::Uuid::new_v4().to_string();
-    let context_ids_json = serde_json::to_string(&staged_context_ids).map_err(|e| e.to_string())?;
 
-    conn.execute(
-        "INSERT INTO pending_blocks (id, user_id, stream_id, bridge_key, staged_context_ids, directive, created_at) 
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-        params![id, user_id, stream_id, bridge_key, context_ids_json, directive, now],
-    )
-    .map_err(|e| e.to_string())?;
+    let created_at = input.created_at.unwrap_or(now);
+    let updated_at = input.updated_at.unwrap_or(now);
+    if created_at <= 0 || updated_at <= 0 {
+        return Err("Entry timestamps must be positive".into());
+    }
+    if created_at > updated_at {
+        return Err("Entry created_at must not be after updated_at".into());
+    }
 
-    Ok(PendingBlock {
+    if input.profile_id.is_none() {
+        input.profile_id = tx
+            .prepare_cached("SELECT default_profile_id FROM streams WHERE id = ?1")?
+            .query_row(params![input.stream_id], |row| row.get(0))
+            .optional()?
+            .flatten();
+    }
+
+    let content_str = serde_json::to_string(&input.content)?;
+    let ai_metadata_str = input
+        .ai_metadata
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+
+    // Serialize parent_context_ids if provided
+    let parent_context_ids_str = input
+        .parent_context_ids
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+
+    // Cached so repeated inserts, as in bulk_create_entries, reuse one prepared statement
+    tx.prepare_cached(
+        "INSERT INTO entries (id, user_id, stream_id, profile_id, role, content, sequence_id, version_head, is_staged, parent_context_ids, ai_metadata, created_at, updated_at) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+    )?
+    .execute(params![
         id,
-        user_id,
-        stream_id,
-        bridge_key,
-        staged_context_ids,
-        directive,
-        created_at: now,
+        input.user_id,
+        input.stream_id,
+        input.profile_id,
+        input.role,
+        content_str,
+        sequence_id,
+        0,
+        0,
+        parent_context_ids_str,
+        ai_metadata_str,
+        created_at,
+        updated_at
+    ])?;
+
+    // Update stream's updated_at
+    tx.prepare_cached("UPDATE streams SET updated_at = ?1 WHERE id = ?2")?
+        .execute(params![now, input.stream_id])?;
+
+    Ok(Entry {
+        id,
+        user_id: input.user_id,
+        stream_id: input.stream_id,
+        profile_id: input.profile_id,
+        role: input.role,
+        content: input.content,
+        sequence_id,
+        version_head: 0,
+        is_staged: false,
+        parent_context_ids: input.parent_context_ids,
+        ai_metadata: input.ai_metadata,
+        created_at,
+        updated_at,
+        is_pinned: false,
+        color: None,
+        label: None,
+        profile: None,
     })
 }
 
 #[tauri::command]
-pub fn get_pending_block(
-    db: State<Database>,
-    stream_id: String,
-) -> Result<Option<PendingBlock>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn get_entry(db: State<Database>, entry_id: String) -> Result<Option<Entry>, AppError> {
+    let conn = db.pool.get()?;
 
     let result = conn.query_row(
-        "SELECT id, user_id, stream_id, bridge_key, staged_context_ids, directive, created_at 
-         FROM pending_blocks 
-         WHERE stream_id = ?1 
-         ORDER BY created_at DESC 
-         LIMIT 1",
-        params![stream_id],
-        |row| {
-            let context_ids_str: String = row.get(3)?;
-            let staged_context_ids: Vec<String> =
-                serde_json::from_str(&context_ids_str).unwrap_or_default();
-
-            Ok(PendingBlock {
-                id: row.get(0)?,
-                user_id: row.get(1)?,
-                stream_id: row.get(2)?,
-                bridge_key: row.get(3)?,
-                staged_context_ids,
-                directive: row.get(5)?,
-                created_at: row.get(6)?,
-            })
-        },
+        &format!(
+            "SELECT {} FROM entries e WHERE e.id = ?1 AND e.deleted_at IS NULL",
+            ENTRY_COLUMNS
+        ),
+        params![entry_id],
+        entry_from_row,
     );
 
     match result {
-        Ok(block) => Ok(Some(block)),
+        Ok(entry) => Ok(Some(entry)),
         Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.to_string()),
+        Err(e) => Err(e.into()),
     }
 }
 
+/// Fetches several entries in one query, in the order requested.
+/// Missing or deleted ids are skipped, and repeated ids come back once.
 #[tauri::command]
-pub fn delete_pending_block(db: State<Database>, pending_block_id: String) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
+pub fn get_entries_by_ids(
+    db: State<Database>,
+    entry_ids: Vec<String>,
+) -> Result<Vec<Entry>, AppError> {
+    if entry_ids.is_empty() {
+        return Ok(Vec::new());
+    }
 
-    conn.execute(
-        "DELETE FROM pending_blocks WHERE id = ?1",
-        params![pending_block_id],
-    )
-    .map_err(|e| e.to_string())?;
+    let conn = db.pool.get()?;
 
-    Ok(())
-}
+    let placeholders = (1..=entry_ids.len())
+        .map(|i| format!("?{}", i))
+        .collect::<Vec<_>>()
+        .join(", ");
 
-// ============================================================
-// SEARCH COMMANDS
-// ============================================================
+    let mut entries = conn
+        .prepare(&format!(
+            "SELECT {} FROM entries e WHERE e.deleted_at IS NULL AND e.id IN ({})",
+            ENTRY_COLUMNS, placeholders
+        ))?
+        .query_map(rusqlite::params_from_iter(&entry_ids), entry_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut position = HashMap::new();
+    for (index, entry_id) in entry_ids.iter().enumerate() {
+        position.entry(entry_id.as_str()).or_insert(index);
+    }
+    entries.sort_by_key(|entry| position[entry.id.as_str()]);
+
+    Ok(entries)
+}
 
+/// The live entries just before and after `entry_id` in its stream's sequence order,
+/// for keyboard navigation. Either side is `None` at the ends of the stream.
 #[tauri::command]
-pub fn search_entries(db: State<Database>, query: String) -> Result<Vec<Entry>, String> {
-    let conn = db.conn.lock().map_err(|e| e.to_string())?;
-    let search_pattern = format!("%{}%", query);
+pub fn get_adjacent_entries(db: State<Database>, entry_id: String) -> Result<Adjacent, AppError> {
+    let conn = db.pool.get()?;
 
-    let mut stmt = conn
-        .prepare(
-            "SELECT id, user_id, stream_id, profile_id, role, content, sequence_id, version_head, is_staged, 
-                    parent_context_ids, ai_metadata, created_at, updated_at 
-             FROM entries 
-             WHERE content LIKE ?1
-             ORDER BY updated_at DESC
-             LIMIT 50",
+    let (stream_id, sequence_id): (String, i32) = conn
+        .query_row(
+            "SELECT stream_id, sequence_id FROM entries WHERE id = ?1",
+            params![entry_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| AppError::from_lookup(e, || format!("Entry '{}' does not exist", entry_id)))?;
+
+    // Ties on sequence_id fall back to id, matching the stream's display order
+    let neighbor = |comparison: &str, direction: &str| -> Result<Option<Entry>, AppError> {
+        conn.query_row(
+            &format!(
+                "SELECT {} FROM entries e 
+                 WHERE e.stream_id = ?1 AND e.deleted_at IS NULL 
+                   AND (e.sequence_id, e.id) {} (?2, ?3)
+                 ORDER BY e.sequence_id {}, e.id {} 
+                 LIMIT 1",
+                ENTRY_COLUMNS, comparison, direction, direction
+            ),
+            params![stream_id, sequence_id, entry_id],
+            entry_from_row,
+        )
+        .optional()
+        .map_err(AppError::from)
+    };
+
+    Ok(Adjacent {
+        prev: neighbor("<", "DESC")?,
+        next: neighbor(">", "ASC")?,
+    })
+}
+
+#[tauri::command]
+pub fn update_entry_content(
+    db: State<Database>,
+    entry_id: String,
+    content: serde_json::Value,
+) -> Result<(), AppError> {
+    content::validate_doc(&content)?;
+
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let content_str = serde_json::to_string(&content)?;
+
+    let tx = conn.transaction()?;
+
+    ensure_entry_stream_unlocked(&tx, &entry_id)?;
+
+    tx.execute(
+        "UPDATE entries SET content = ?1, updated_at = ?2 WHERE id = ?3",
+        params![content_str, now, entry_id],
+    )?;
+
+    // Update stream's updated_at
+    tx.execute(
+        r#"UPDATE streams SET updated_at = ?1 
+           WHERE id = (SELECT stream_id FROM entries WHERE id = ?2)"#,
+        params![now, entry_id],
+    )?;
+
+    tx.commit()?;
+
+    db.pending_autosaves.lock()?.insert(entry_id, now);
+
+    Ok(())
+}
+
+/// Appends `text` as a new paragraph at the end of the entry's document
+#[tauri::command]
+pub fn append_paragraph_to_entry(
+    db: State<Database>,
+    entry_id: String,
+    text: String,
+) -> Result<(), AppError> {
+    edit_entry_doc(&db, &entry_id, |doc| {
+        if let Some(nodes) = doc
+            .get_mut("content")
+            .and_then(serde_json::Value::as_array_mut)
+        {
+            nodes.push(content::paragraph(&text));
+        }
+    })
+}
+
+/// Appends streamed text to the end of the entry's last block, so AI responses can
+/// be saved chunk by chunk without sending the whole document each time
+#[tauri::command]
+pub fn append_text_to_last_node(
+    db: State<Database>,
+    entry_id: String,
+    text: String,
+) -> Result<(), AppError> {
+    edit_entry_doc(&db, &entry_id, |doc| content::append_text(doc, &text))
+}
+
+/// Loads a live entry's document, checks it is a `doc` node, applies `edit` and saves
+/// it, bumping the entry and its stream like `update_entry_content`
+fn edit_entry_doc(
+    db: &Database,
+    entry_id: &str,
+    edit: impl FnOnce(&mut serde_json::Value),
+) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction()?;
+
+    ensure_entry_stream_unlocked(&tx, entry_id)?;
+
+    let content_str: String = tx
+        .query_row(
+            "SELECT content FROM entries WHERE id = ?1 AND deleted_at IS NULL",
+            params![entry_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| AppError::from_lookup(e, || format!("Entry '{}' does not exist", entry_id)))?;
+
+    let mut content: serde_json::Value = serde_json::from_str(&content_str)?;
+    content::validate_doc(&content)?;
+    edit(&mut content);
+
+    tx.execute(
+        "UPDATE entries SET content = ?1, updated_at = ?2 WHERE id = ?3",
+        params![serde_json::to_string(&content)?, now, entry_id],
+    )?;
+
+    tx.execute(
+        r#"UPDATE streams SET updated_at = ?1 
+           WHERE id = (SELECT stream_id FROM entries WHERE id = ?2)"#,
+        params![now, entry_id],
+    )?;
+
+    tx.commit()?;
+
+    db.pending_autosaves
+        .lock()?
+        .insert(entry_id.to_string(), now);
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn toggle_entry_staging(
+    db: State<Database>,
+    entry_id: String,
+    is_staged: bool,
+) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+
+    conn.execute(
+        "UPDATE entries SET is_staged = ?1 WHERE id = ?2",
+        params![if is_staged { 1 } else { 0 }, entry_id],
+    )?;
+
+    Ok(())
+}
+
+/// Sets an entry's color and label. `None` leaves a field as is; an empty string clears it.
+#[tauri::command]
+pub fn update_entry_appearance(
+    db: State<Database>,
+    entry_id: String,
+    color: Option<String>,
+    label: Option<String>,
+) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    for (column, value) in [("color", color), ("label", label)] {
+        if let Some(value) = value {
+            let value = value.trim();
+            conn.execute(
+                &format!(
+                    "UPDATE entries SET {} = ?1, updated_at = ?2 WHERE id = ?3",
+                    column
+                ),
+                params![(!value.is_empty()).then_some(value), now, entry_id],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Pins or unpins an entry. Pins are independent of staging.
+#[tauri::command]
+pub fn toggle_entry_pin(
+    db: State<Database>,
+    entry_id: String,
+    pinned: bool,
+) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+
+    conn.execute(
+        "UPDATE entries SET is_pinned = ?1 WHERE id = ?2",
+        params![if pinned { 1 } else { 0 }, entry_id],
+    )?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn update_entry_profile(
+    db: State<Database>,
+    entry_id: String,
+    profile_id: Option<String>,
+) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    conn.execute(
+        "UPDATE entries SET profile_id = ?1, updated_at = ?2 WHERE id = ?3",
+        params![profile_id, now, entry_id],
+    )?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn bulk_update_entry_profile(
+    db: State<Database>,
+    entry_ids: Vec<String>,
+    profile_id: Option<String>,
+) -> Result<usize, AppError> {
+    if entry_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction()?;
+    let mut rows_affected = 0;
+
+    {
+        let mut stmt =
+            tx.prepare("UPDATE entries SET profile_id = ?1, updated_at = ?2 WHERE id = ?3")?;
+
+        for entry_id in entry_ids {
+            rows_affected += stmt.execute(params![profile_id, now, entry_id])?;
+        }
+    }
+
+    tx.commit()?;
+
+    Ok(rows_affected)
+}
+
+/// Sets an entry's role, profile and AI metadata together, e.g. when a pasted bridge
+/// response came in as a user entry. `None` clears the profile or metadata.
+#[tauri::command]
+pub fn reclassify_entry(
+    db: State<Database>,
+    entry_id: String,
+    role: String,
+    profile_id: Option<String>,
+    ai_metadata: Option<AiMetadata>,
+) -> Result<Entry, AppError> {
+    if role != "user" && role != "ai" {
+        return Err(format!("Invalid role '{}': expected user or ai", role).into());
+    }
+    if let Some(metadata) = &ai_metadata {
+        warn_unknown_provider(metadata);
+    }
+
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction()?;
+
+    ensure_entry_stream_unlocked(&tx, &entry_id)?;
+
+    if let Some(profile_id) = &profile_id {
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS(SELECT 1 FROM profiles WHERE id = ?1)",
+            params![profile_id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(AppError::new(
+                "NOT_FOUND",
+                &format!("Profile '{}' does not exist", profile_id),
+            ));
+        }
+    }
+
+    let ai_metadata_str = ai_metadata
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()?;
+
+    let updated = tx.execute(
+        "UPDATE entries SET role = ?1, profile_id = ?2, ai_metadata = ?3, updated_at = ?4 
+         WHERE id = ?5 AND deleted_at IS NULL",
+        params![role, profile_id, ai_metadata_str, now, entry_id],
+    )?;
+    if updated == 0 {
+        return Err(AppError::new(
+            "NOT_FOUND",
+            &format!("Entry '{}' does not exist", entry_id),
+        ));
+    }
+
+    let entry = tx.query_row(
+        &format!("SELECT {} FROM entries e WHERE e.id = ?1", ENTRY_COLUMNS),
+        params![entry_id],
+        entry_from_row,
+    )?;
+
+    tx.commit()?;
+
+    Ok(entry)
+}
+
+#[tauri::command]
+pub fn delete_entry(db: State<Database>, entry_id: String) -> Result<(), AppError> {
+    delete_entries(db, vec![entry_id]).map(|_| ())
+}
+
+#[tauri::command]
+pub fn bulk_delete_entries(db: State<Database>, entry_ids: Vec<String>) -> Result<(), AppError> {
+    delete_entries(db, entry_ids).map(|_| ())
+}
+
+#[tauri::command]
+pub fn delete_entries(db: State<Database>, entry_ids: Vec<String>) -> Result<i32, AppError> {
+    if entry_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    let deleted = soft_delete_entries(&tx, entry_ids)?;
+
+    tx.commit()?;
+
+    Ok(deleted as i32)
+}
+
+/// Moves entries to the trash, recording them for undo and bumping their streams.
+/// Fails with `LOCKED` if any of them is in a locked stream. Returns how many were deleted.
+fn soft_delete_entries(
+    tx: &rusqlite::Transaction,
+    entry_ids: Vec<String>,
+) -> Result<usize, AppError> {
+    if entry_ids.is_empty() {
+        return Ok(0);
+    }
+
+    let now = chrono::Utc::now().timestamp_millis();
+
+    // ?1 is the timestamp; the ids follow as ?2..?N
+    let placeholders = (2..entry_ids.len() + 2)
+        .map(|i| format!("?{}", i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values: Vec<rusqlite::types::Value> = std::iter::once(now.into())
+        .chain(entry_ids.into_iter().map(Into::into))
+        .collect();
+
+    // Reuses the bound values; ?1 simply goes unused
+    let locked_stream: Option<String> = tx
+        .query_row(
+            &format!(
+                "SELECT id FROM streams WHERE locked != 0 
+                 AND id IN (SELECT stream_id FROM entries WHERE id IN ({}))
+                 LIMIT 1",
+                placeholders
+            ),
+            rusqlite::params_from_iter(&values),
+            |row| row.get(0),
         )
-        .map_err(|e| e.to_string())?;
+        .optional()?;
+    if let Some(stream_id) = locked_stream {
+        return Err(AppError::new(
+            "LOCKED",
+            &format!("Stream '{}' is locked", stream_id),
+        ));
+    }
+
+    // Staging is cleared on delete, so remember it for undo
+    let entries = tx
+        .prepare(&format!(
+            "SELECT id, is_staged FROM entries WHERE deleted_at IS NULL AND id IN ({})",
+            placeholders
+        ))?
+        .query_map(rusqlite::params_from_iter(&values), |row| {
+            Ok((row.get(0)?, row.get::<_, i32>(1)? != 0))
+        })?
+        .collect::<Result<Vec<(String, bool)>, _>>()?;
+
+    if !entries.is_empty() {
+        record_undo(tx, &UndoSnapshot::DeleteEntries { entries })?;
+    }
+
+    // Soft delete: entries move to the trash until restored or purged
+    let deleted = tx.execute(
+        &format!(
+            "UPDATE entries SET deleted_at = ?1, is_staged = 0 
+                 WHERE deleted_at IS NULL AND id IN ({})",
+            placeholders
+        ),
+        rusqlite::params_from_iter(&values),
+    )?;
+
+    // Only streams that actually lost an entry in this call
+    tx.execute(
+        &format!(
+            "UPDATE streams SET updated_at = ?1 
+             WHERE id IN (SELECT stream_id FROM entries WHERE deleted_at = ?1 AND id IN ({}))",
+            placeholders
+        ),
+        rusqlite::params_from_iter(&values),
+    )?;
+
+    Ok(deleted)
+}
+
+/// Ids of live entries in `stream_id` whose plain text is blank, in sequence order
+fn empty_entry_ids(conn: &rusqlite::Connection, stream_id: &str) -> Result<Vec<String>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, content FROM entries 
+             WHERE stream_id = ?1 AND deleted_at IS NULL 
+             ORDER BY sequence_id ASC, id ASC",
+    )?;
+
+    let rows = stmt
+        .query_map(params![stream_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(rows
+        .into_iter()
+        .filter(|(_, content_str)| {
+            content::extract_plain_text_from_str(content_str)
+                .trim()
+                .is_empty()
+        })
+        .map(|(id, _)| id)
+        .collect())
+}
+
+/// Live entries in the stream with no text, for cleanup
+#[tauri::command]
+pub fn find_empty_entries(db: State<Database>, stream_id: String) -> Result<Vec<String>, AppError> {
+    let conn = db.pool.get()?;
+    empty_entry_ids(&conn, &stream_id)
+}
+
+/// Moves the stream's empty entries to the trash in one transaction. If every entry is
+/// empty, the first is kept so the stream isn't left without one. Returns how many were deleted.
+#[tauri::command]
+pub fn delete_empty_entries(db: State<Database>, stream_id: String) -> Result<usize, AppError> {
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    let mut entry_ids = empty_entry_ids(&tx, &stream_id)?;
+
+    let live_count: usize = tx.query_row(
+        "SELECT COUNT(*) FROM entries WHERE stream_id = ?1 AND deleted_at IS NULL",
+        params![stream_id],
+        |row| row.get(0),
+    )?;
+    if !entry_ids.is_empty() && entry_ids.len() == live_count {
+        entry_ids.remove(0);
+    }
+
+    let deleted = soft_delete_entries(&tx, entry_ids)?;
+
+    tx.commit()?;
+
+    Ok(deleted)
+}
+
+#[tauri::command]
+pub fn restore_entry(db: State<Database>, entry_id: String) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    ensure_entry_stream_unlocked(&tx, &entry_id)?;
+    untrash_entry(&tx, &entry_id)?;
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Takes an entry out of the trash. It keeps its old position unless a live entry
+/// has taken that `sequence_id` since (reorders only renumber live entries), in
+/// which case it goes to the end of the stream.
+fn untrash_entry(conn: &rusqlite::Connection, entry_id: &str) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE entries SET
+             deleted_at = NULL,
+             sequence_id = CASE
+                 WHEN EXISTS (
+                     SELECT 1 FROM entries live
+                     WHERE live.stream_id = entries.stream_id AND live.deleted_at IS NULL
+                       AND live.sequence_id = entries.sequence_id
+                 )
+                 THEN (SELECT MAX(other.sequence_id) + 1 FROM entries other
+                       WHERE other.stream_id = entries.stream_id)
+                 ELSE sequence_id
+             END
+         WHERE id = ?1 AND deleted_at IS NOT NULL",
+        params![entry_id],
+    )?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn purge_deleted_entries(db: State<Database>, stream_id: String) -> Result<usize, AppError> {
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    ensure_stream_unlocked(&tx, &stream_id)?;
+
+    let purged = tx.execute(
+        "DELETE FROM entries WHERE stream_id = ?1 AND deleted_at IS NOT NULL",
+        params![stream_id],
+    )?;
+
+    tx.commit()?;
+
+    Ok(purged)
+}
+
+#[tauri::command]
+pub fn get_deleted_entries(db: State<Database>, stream_id: String) -> Result<Vec<Entry>, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM entries e 
+                 WHERE e.stream_id = ?1 AND e.deleted_at IS NOT NULL
+                 ORDER BY e.deleted_at DESC",
+        ENTRY_COLUMNS
+    ))?;
 
     let entries = stmt
-        .query_map(params![search_pattern], |row| {
-            let content_str: String = row.get(5)?;
-            let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
-            let parent_ids_str: Option<String> = row.get(9)?;
-            let parent_context_ids: Option<Vec<String>> =
-                parent_ids_str.and_then(|s| serde_json::from_str(&s).ok());
-            let ai_metadata_str: Option<String> = row.get(10)?;
-            let ai_metadata: Option<AiMetadata> =
-                ai_metadata_str.and_then(|s| serde_json::from_str(&s).ok());
+        .query_map(params![stream_id], entry_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
 
-            Ok(Entry {
-                id: row.get(0)?,
-                user_id: row.get(1)?,
-                stream_id: row.get(2)?,
-                profile_id: row.get(3)?,
-                role: row.get(4)?,
-                content,
-                sequence_id: row.get(6)?,
-                version_head: row.get(7)?,
-                is_staged: row.get::<_, i32>(8)? != 0,
-                parent_context_ids,
-                ai_metadata,
-                created_at: row.get(11)?,
-                updated_at: row.get(12)?,
-                profile: None,
+    Ok(entries)
+}
+
+#[tauri::command]
+pub fn get_staged_entries(db: State<Database>, stream_id: String) -> Result<Vec<Entry>, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {} FROM entries e 
+                 WHERE e.stream_id = ?1 AND e.is_staged = 1 AND e.deleted_at IS NULL
+                 ORDER BY e.sequence_id ASC",
+        ENTRY_COLUMNS
+    ))?;
+
+    let entries = stmt
+        .query_map(params![stream_id], entry_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(entries)
+}
+
+#[tauri::command]
+pub fn get_pinned_entries(db: State<Database>, stream_id: String) -> Result<Vec<Entry>, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM entries e 
+                 WHERE e.stream_id = ?1 AND e.is_pinned = 1 AND e.deleted_at IS NULL
+                 ORDER BY e.sequence_id ASC",
+        ENTRY_COLUMNS
+    ))?;
+
+    let entries = stmt
+        .query_map(params![stream_id], entry_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(entries)
+}
+
+/// Entries that list `entry_id` in their `parent_context_ids`, i.e. the blocks it helped
+/// generate. Parent ids may carry a `:version` suffix. Searches every stream, since
+/// entries can be moved after being generated.
+#[tauri::command]
+pub fn get_children_of_entry(
+    db: State<Database>,
+    entry_id: String,
+) -> Result<Vec<Entry>, AppError> {
+    let conn = db.pool.get()?;
+
+    // `instr` only narrows the scan; the exact match happens on the parsed ids below,
+    // which also skips rows whose parent list doesn't parse.
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM entries e 
+                 WHERE e.deleted_at IS NULL AND e.parent_context_ids IS NOT NULL
+                   AND instr(e.parent_context_ids, ?1) > 0
+                 ORDER BY e.created_at ASC, e.sequence_id ASC, e.id ASC",
+        ENTRY_COLUMNS
+    ))?;
+
+    let entries = stmt
+        .query_map(params![entry_id], entry_from_row)?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|entry| {
+            entry.parent_context_ids.as_ref().is_some_and(|ids| {
+                ids.iter()
+                    .any(|id| context_entry_id(id) == entry_id.as_str())
             })
         })
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())?;
+        .collect();
 
     Ok(entries)
 }
+
+/// Builds the parent-context graph of a stream. Versioned parent ids are reduced to
+/// their entry id; edges to deleted entries or to entries in other streams are skipped.
+#[tauri::command]
+pub fn get_context_graph(db: State<Database>, stream_id: String) -> Result<ContextGraph, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, parent_context_ids FROM entries 
+             WHERE stream_id = ?1 AND deleted_at IS NULL 
+             ORDER BY sequence_id ASC, id ASC",
+    )?;
+
+    let rows = stmt
+        .query_map(params![stream_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let node_ids: HashSet<&str> = rows.iter().map(|(id, _)| id.as_str()).collect();
+
+    let mut edges = Vec::new();
+    for (entry_id, parent_ids_str) in &rows {
+        let parent_ids: Vec<String> = parent_ids_str
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or_default();
+
+        for parent_id in &parent_ids {
+            // Bridge-generated parents carry a `:version` suffix; edges use the bare id
+            let parent_id = context_entry_id(parent_id);
+            let edge = (parent_id.to_string(), entry_id.clone());
+            if node_ids.contains(parent_id) && !edges.contains(&edge) {
+                edges.push(edge);
+            }
+        }
+    }
+
+    Ok(ContextGraph {
+        nodes: rows.into_iter().map(|(id, _)| id).collect(),
+        edges,
+    })
+}
+
+/// Groups live entries whose plain text is identical once whitespace is normalized.
+/// Groups and the ids within them follow sequence order; empty entries are ignored.
+#[tauri::command]
+pub fn find_duplicate_entries(
+    db: State<Database>,
+    stream_id: String,
+) -> Result<Vec<Vec<String>>, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, content FROM entries 
+             WHERE stream_id = ?1 AND deleted_at IS NULL 
+             ORDER BY sequence_id ASC, id ASC",
+    )?;
+
+    let rows = stmt
+        .query_map(params![stream_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut groups: Vec<Vec<String>> = Vec::new();
+    let mut group_by_text: HashMap<String, usize> = HashMap::new();
+    for (id, content_str) in rows {
+        let text = content::extract_plain_text_from_str(&content_str)
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if text.is_empty() {
+            continue;
+        }
+        match group_by_text.get(&text) {
+            Some(&index) => groups[index].push(id),
+            None => {
+                group_by_text.insert(text, groups.len());
+                groups.push(vec![id]);
+            }
+        }
+    }
+
+    groups.retain(|group| group.len() > 1);
+    Ok(groups)
+}
+
+/// Soft-deletes `remove_ids` and points any `parent_context_ids` that referenced them
+/// at `keep_id` instead. Only entries in the kept entry's stream are removed.
+/// Returns how many entries were removed.
+#[tauri::command]
+pub fn merge_duplicate_entries(
+    db: State<Database>,
+    keep_id: String,
+    remove_ids: Vec<String>,
+) -> Result<usize, AppError> {
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let tx = conn.transaction()?;
+
+    let stream_id: String = tx
+        .query_row(
+            "SELECT stream_id FROM entries WHERE id = ?1 AND deleted_at IS NULL",
+            params![keep_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| AppError::from_lookup(e, || format!("Entry '{}' does not exist", keep_id)))?;
+
+    ensure_stream_unlocked(&tx, &stream_id)?;
+
+    let mut removed = Vec::new();
+    {
+        let mut stmt = tx.prepare(
+            "SELECT is_staged FROM entries 
+                 WHERE id = ?1 AND stream_id = ?2 AND deleted_at IS NULL",
+        )?;
+        for id in remove_ids {
+            if id == keep_id || removed.iter().any(|(removed_id, _)| *removed_id == id) {
+                continue;
+            }
+            let is_staged: Option<i32> = stmt
+                .query_row(params![id, stream_id], |row| row.get(0))
+                .optional()?;
+            if let Some(is_staged) = is_staged {
+                removed.push((id, is_staged != 0));
+            }
+        }
+    }
+
+    if removed.is_empty() {
+        return Ok(0);
+    }
+
+    {
+        let mut stmt =
+            tx.prepare("UPDATE entries SET deleted_at = ?1, is_staged = 0 WHERE id = ?2")?;
+        for (id, _) in &removed {
+            stmt.execute(params![now, id])?;
+        }
+    }
+
+    // Rewire children in every stream, since entries can be moved after being generated
+    let removed_ids: HashSet<&str> = removed.iter().map(|(id, _)| id.as_str()).collect();
+    let children = tx
+        .prepare("SELECT id, parent_context_ids FROM entries WHERE parent_context_ids IS NOT NULL")?
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    {
+        let mut stmt = tx.prepare("UPDATE entries SET parent_context_ids = ?1 WHERE id = ?2")?;
+        for (child_id, parent_ids_str) in children {
+            let Ok(parent_ids) = serde_json::from_str::<Vec<String>>(&parent_ids_str) else {
+                continue;
+            };
+            if !parent_ids
+                .iter()
+                .any(|id| removed_ids.contains(context_entry_id(id)))
+            {
+                continue;
+            }
+
+            let mut rewired: Vec<String> = Vec::with_capacity(parent_ids.len());
+            for parent_id in parent_ids {
+                let parent_id = if removed_ids.contains(context_entry_id(&parent_id)) {
+                    keep_id.clone()
+                } else {
+                    parent_id
+                };
+                if !rewired.contains(&parent_id) {
+                    rewired.push(parent_id);
+                }
+            }
+            stmt.execute(params![serde_json::to_string(&rewired)?, child_id])?;
+        }
+    }
+
+    tx.execute(
+        "UPDATE streams SET updated_at = ?1 WHERE id = ?2",
+        params![now, stream_id],
+    )?;
+
+    let count = removed.len();
+    record_undo(&tx, &UndoSnapshot::DeleteEntries { entries: removed })?;
+
+    tx.commit()?;
+
+    Ok(count)
+}
+
+#[tauri::command]
+pub fn clear_all_staging(db: State<Database>, stream_id: String) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    let entry_ids = tx
+        .prepare("SELECT id FROM entries WHERE stream_id = ?1 AND is_staged = 1")?
+        .query_map(params![stream_id], |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+
+    if !entry_ids.is_empty() {
+        record_undo(&tx, &UndoSnapshot::ClearStaging { entry_ids })?;
+        tx.execute(
+            "UPDATE entries SET is_staged = 0 WHERE stream_id = ?1",
+            params![stream_id],
+        )?;
+    }
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_entry_preview(
+    db: State<Database>,
+    entry_id: String,
+    max_chars: usize,
+) -> Result<String, AppError> {
+    let conn = db.pool.get()?;
+
+    let content_str: String = conn
+        .query_row(
+            "SELECT content FROM entries WHERE id = ?1 AND deleted_at IS NULL",
+            params![entry_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| AppError::from_lookup(e, || format!("Entry '{}' does not exist", entry_id)))?;
+
+    let text = content::extract_plain_text_from_str(&content_str);
+
+    Ok(text.chars().take(max_chars).collect())
+}
+
+#[tauri::command]
+pub fn reorder_entries(
+    db: State<Database>,
+    stream_id: String,
+    ordered_entry_ids: Vec<String>,
+) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction()?;
+
+    ensure_stream_unlocked(&tx, &stream_id)?;
+
+    // The new order must cover exactly the entries currently in the stream
+    let existing_ids: HashSet<String> = tx
+        .prepare("SELECT id FROM entries WHERE stream_id = ?1 AND deleted_at IS NULL")?
+        .query_map(params![stream_id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    let requested_ids: HashSet<String> = ordered_entry_ids.iter().cloned().collect();
+
+    if requested_ids.len() != ordered_entry_ids.len() {
+        return Err("Entry order contains duplicate ids".into());
+    }
+
+    let missing = existing_ids.difference(&requested_ids).count();
+    let unknown = requested_ids.difference(&existing_ids).count();
+    if missing > 0 || unknown > 0 {
+        return Err(format!(
+            "Entry order does not match stream '{}': {} missing, {} not in stream",
+            stream_id, missing, unknown
+        )
+        .into());
+    }
+
+    {
+        let mut stmt = tx.prepare("UPDATE entries SET sequence_id = ?1 WHERE id = ?2")?;
+
+        for (index, entry_id) in ordered_entry_ids.iter().enumerate() {
+            stmt.execute(params![index as i32 + 1, entry_id])?;
+        }
+    }
+
+    // Update stream's updated_at
+    tx.execute(
+        "UPDATE streams SET updated_at = ?1 WHERE id = ?2",
+        params![now, stream_id],
+    )?;
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Renumbers a stream's entries to 1..N in their current order, closing gaps left by
+/// deletes and moves. Trashed entries follow the live ones so restoring them can't collide.
+/// Order is unchanged, so locked streams may be compacted too.
+#[tauri::command]
+pub fn compact_sequence_ids(db: State<Database>, stream_id: String) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    let entry_ids: Vec<String> = tx
+        .prepare(
+            "SELECT id FROM entries WHERE stream_id = ?1 
+                 ORDER BY deleted_at IS NOT NULL, sequence_id ASC, id ASC",
+        )?
+        .query_map(params![stream_id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    {
+        let mut stmt = tx.prepare("UPDATE entries SET sequence_id = ?1 WHERE id = ?2")?;
+
+        for (index, entry_id) in entry_ids.iter().enumerate() {
+            stmt.execute(params![index as i32 + 1, entry_id])?;
+        }
+    }
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn move_entry(
+    db: State<Database>,
+    entry_id: String,
+    target_stream_id: String,
+) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction()?;
+
+    let target_exists: bool = tx
+        .prepare("SELECT 1 FROM streams WHERE id = ?1")?
+        .exists(params![target_stream_id])?;
+
+    if !target_exists {
+        return Err(format!("Target stream '{}' does not exist", target_stream_id).into());
+    }
+
+    let source_stream_id: String = tx.query_row(
+        "SELECT stream_id FROM entries WHERE id = ?1",
+        params![entry_id],
+        |row| row.get(0),
+    )?;
+
+    ensure_stream_unlocked(&tx, &source_stream_id)?;
+    ensure_stream_unlocked(&tx, &target_stream_id)?;
+
+    if source_stream_id == target_stream_id {
+        return Ok(());
+    }
+
+    // Append at the end of the target stream
+    let max_seq: i32 = tx.query_row(
+        "SELECT COALESCE(MAX(sequence_id), 0) FROM entries WHERE stream_id = ?1",
+        params![target_stream_id],
+        |row| row.get(0),
+    )?;
+
+    // Versions and spotlights reference the entry id, so they move with it
+    tx.execute(
+        "UPDATE entries SET stream_id = ?1, sequence_id = ?2, updated_at = ?3 WHERE id = ?4",
+        params![target_stream_id, max_seq + 1, now, entry_id],
+    )?;
+
+    // Update both streams' updated_at
+    tx.execute(
+        "UPDATE streams SET updated_at = ?1 WHERE id IN (?2, ?3)",
+        params![now, source_stream_id, target_stream_id],
+    )?;
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+// ============================================================
+// VERSION COMMANDS
+// ============================================================
+
+/// Column list matching `version_from_row`
+const VERSION_COLUMNS: &str =
+    "id, entry_id, version_number, content_snapshot, commit_message, committed_at, label, bookmarked";
+
+/// Maps a row selected with `VERSION_COLUMNS`
+fn version_from_row(row: &rusqlite::Row) -> rusqlite::Result<EntryVersion> {
+    let content_str: String = row.get(3)?;
+    let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
+
+    Ok(EntryVersion {
+        id: row.get(0)?,
+        entry_id: row.get(1)?,
+        version_number: row.get(2)?,
+        content_snapshot: content,
+        commit_message: row.get(4)?,
+        committed_at: row.get(5)?,
+        label: row.get(6)?,
+        bookmarked: row.get::<_, i32>(7)? != 0,
+    })
+}
+
+#[tauri::command]
+pub fn commit_entry_version(
+    db: State<Database>,
+    entry_id: String,
+    commit_message: Option<String>,
+) -> Result<EntryVersion, AppError> {
+    let mut conn = db.pool.get()?;
+
+    // Snapshot insert and head bump must land together
+    let tx = conn.transaction()?;
+
+    let version = commit_version(&tx, &entry_id, commit_message)?;
+
+    tx.commit()?;
+
+    Ok(version)
+}
+
+#[tauri::command]
+pub fn commit_if_changed(
+    db: State<Database>,
+    entry_id: String,
+    min_interval_ms: i64,
+) -> Result<Option<EntryVersion>, AppError> {
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction()?;
+
+    let content_str: String = tx.query_row(
+        "SELECT content FROM entries WHERE id = ?1",
+        params![entry_id],
+        |row| row.get(0),
+    )?;
+
+    let latest: Option<(String, i64)> = match tx.query_row(
+        "SELECT content_snapshot, committed_at FROM entry_versions 
+         WHERE entry_id = ?1 
+         ORDER BY version_number DESC 
+         LIMIT 1",
+        params![entry_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ) {
+        Ok(latest) => Some(latest),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.into()),
+    };
+
+    if let Some((snapshot, committed_at)) = latest {
+        if snapshot == content_str || now - committed_at < min_interval_ms {
+            return Ok(None);
+        }
+    }
+
+    let version = commit_version(&tx, &entry_id, None)?;
+
+    tx.commit()?;
+
+    Ok(Some(version))
+}
+
+/// Commits a version for every entry edited through `update_entry_content` whose last
+/// edit is more than `idle_threshold_ms` old and whose content differs from its head.
+/// Entries still being edited stay pending for a later flush.
+#[tauri::command]
+pub fn flush_autosave_versions(
+    db: State<Database>,
+    idle_threshold_ms: i64,
+) -> Result<Vec<EntryVersion>, AppError> {
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let idle: Vec<(String, i64)> = db
+        .pending_autosaves
+        .lock()?
+        .iter()
+        .filter(|(_, edited_at)| now - **edited_at > idle_threshold_ms)
+        .map(|(entry_id, edited_at)| (entry_id.clone(), *edited_at))
+        .collect();
+
+    if idle.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+    let mut versions = Vec::new();
+
+    {
+        // None when the entry is gone or in the trash
+        let mut changed_stmt = tx.prepare(
+            "SELECT NOT EXISTS (
+                 SELECT 1 FROM entry_versions v
+                 WHERE v.entry_id = e.id AND v.version_number = e.version_head
+                   AND v.content_hash = content_hash(e.content)
+             )
+             FROM entries e
+             WHERE e.id = ?1 AND e.deleted_at IS NULL",
+        )?;
+
+        for (entry_id, _) in &idle {
+            let changed: Option<bool> = changed_stmt
+                .query_row(params![entry_id], |row| row.get(0))
+                .optional()?;
+
+            if changed == Some(true) {
+                versions.push(commit_version(&tx, entry_id, Some("Autosave".to_string()))?);
+            }
+        }
+    }
+
+    tx.commit()?;
+
+    // Keep markers for entries edited again while the flush ran
+    let mut pending = db.pending_autosaves.lock()?;
+    for (entry_id, edited_at) in idle {
+        if pending.get(&entry_id) == Some(&edited_at) {
+            pending.remove(&entry_id);
+        }
+    }
+
+    Ok(versions)
+}
+
+/// Snapshots the entry's current content as the next version and moves its head
+fn commit_version(
+    tx: &rusqlite::Transaction,
+    entry_id: &str,
+    commit_message: Option<String>,
+) -> Result<EntryVersion, AppError> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let version_id = uuid::Uuid::new_v4().to_string();
+
+    // Get current entry content and version
+    let (content_str, current_version): (String, i32) = tx
+        .query_row(
+            "SELECT content, version_head FROM entries WHERE id = ?1",
+            params![entry_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| AppError::from_lookup(e, || format!("Entry not found: {}", entry_id)))?;
+
+    let content_hash = content::content_hash(&content_str);
+
+    // Committing unchanged content returns the existing head instead of a duplicate
+    let head = tx.query_row(
+        &format!(
+            "SELECT {} FROM entry_versions 
+             WHERE entry_id = ?1 AND version_number = ?2 AND content_hash = ?3",
+            VERSION_COLUMNS
+        ),
+        params![entry_id, current_version, content_hash],
+        version_from_row,
+    );
+
+    match head {
+        Ok(version) => return Ok(version),
+        Err(rusqlite::Error::QueryReturnedNoRows) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let new_version = current_version + 1;
+
+    // Create version snapshot
+    tx.execute(
+        "INSERT INTO entry_versions (id, entry_id, version_number, content_snapshot, commit_message, committed_at, content_hash) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![version_id, entry_id, new_version, content_str, commit_message, now, content_hash],
+    )?;
+
+    // Update entry's version_head
+    tx.execute(
+        "UPDATE entries SET version_head = ?1 WHERE id = ?2",
+        params![new_version, entry_id],
+    )?;
+
+    let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
+
+    Ok(EntryVersion {
+        id: version_id,
+        entry_id: entry_id.to_string(),
+        version_number: new_version,
+        content_snapshot: content,
+        commit_message,
+        committed_at: now,
+        label: None,
+        bookmarked: false,
+    })
+}
+
+#[tauri::command]
+pub fn get_entry_versions(
+    db: State<Database>,
+    entry_id: String,
+) -> Result<Vec<EntryVersion>, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} 
+             FROM entry_versions 
+             WHERE entry_id = ?1 
+             ORDER BY version_number DESC",
+        VERSION_COLUMNS
+    ))?;
+
+    let versions = stmt
+        .query_map(params![entry_id], version_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(versions)
+}
+
+#[tauri::command]
+pub fn get_latest_version(
+    db: State<Database>,
+    entry_id: String,
+) -> Result<Option<EntryVersion>, AppError> {
+    let conn = db.pool.get()?;
+
+    let result = conn.query_row(
+        &format!(
+            "SELECT {} 
+         FROM entry_versions 
+         WHERE entry_id = ?1 
+         ORDER BY version_number DESC 
+         LIMIT 1",
+            VERSION_COLUMNS
+        ),
+        params![entry_id],
+        version_from_row,
+    );
+
+    match result {
+        Ok(version) => Ok(Some(version)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[tauri::command]
+pub fn get_version_by_number(
+    db: State<Database>,
+    entry_id: String,
+    version_number: i32,
+) -> Result<Option<EntryVersion>, AppError> {
+    let conn = db.pool.get()?;
+
+    let result = conn.query_row(
+        &format!(
+            "SELECT {} 
+         FROM entry_versions 
+         WHERE entry_id = ?1 AND version_number = ?2",
+            VERSION_COLUMNS
+        ),
+        params![entry_id, version_number],
+        version_from_row,
+    );
+
+    match result {
+        Ok(version) => Ok(Some(version)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Id given to the pseudo-version returned by `get_working_snapshot`
+const WORKING_SNAPSHOT_ID: &str = "working";
+
+/// Returns the live, possibly uncommitted content of an entry shaped as a version.
+/// The id is `WORKING_SNAPSHOT_ID`, `version_number` is the current head and
+/// `committed_at` is the entry's last edit.
+#[tauri::command]
+pub fn get_working_snapshot(
+    db: State<Database>,
+    entry_id: String,
+) -> Result<EntryVersion, AppError> {
+    let conn = db.pool.get()?;
+
+    let (content_str, version_head, updated_at): (String, i32, i64) = conn.query_row(
+        "SELECT content, version_head, updated_at FROM entries WHERE id = ?1",
+        params![entry_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    Ok(EntryVersion {
+        id: WORKING_SNAPSHOT_ID.to_string(),
+        entry_id,
+        version_number: version_head,
+        content_snapshot: serde_json::from_str(&content_str).unwrap_or_default(),
+        commit_message: None,
+        committed_at: updated_at,
+        label: None,
+        bookmarked: false,
+    })
+}
+
+#[tauri::command]
+pub fn label_version(
+    db: State<Database>,
+    entry_id: String,
+    version_number: i32,
+    label: String,
+) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+
+    // An empty label clears it
+    let label = label.trim();
+    let label = (!label.is_empty()).then_some(label);
+
+    let updated = conn.execute(
+        "UPDATE entry_versions SET label = ?1 WHERE entry_id = ?2 AND version_number = ?3",
+        params![label, entry_id, version_number],
+    )?;
+
+    if updated == 0 {
+        return Err(format!(
+            "Version {} not found for entry {}",
+            version_number, entry_id
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_labeled_versions(
+    db: State<Database>,
+    entry_id: String,
+) -> Result<Vec<EntryVersion>, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM entry_versions 
+             WHERE entry_id = ?1 AND label IS NOT NULL 
+             ORDER BY version_number DESC",
+        VERSION_COLUMNS
+    ))?;
+
+    let versions = stmt
+        .query_map(params![entry_id], version_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(versions)
+}
+
+#[tauri::command]
+pub fn revert_to_version(
+    db: State<Database>,
+    entry_id: String,
+    version_number: i32,
+) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let tx = conn.transaction()?;
+
+    ensure_entry_stream_unlocked(&tx, &entry_id)?;
+
+    // Get the version's content
+    let content_str: String = tx
+        .query_row(
+            "SELECT content_snapshot FROM entry_versions WHERE entry_id = ?1 AND version_number = ?2",
+            params![entry_id, version_number],
+            |row| row.get(0),
+        )
+        .map_err(|e| {
+            AppError::from_lookup(e, || {
+                format!("Version {} not found for entry {}", version_number, entry_id)
+            })
+        })?;
+
+    // Update entry with reverted content
+    tx.execute(
+        "UPDATE entries SET content = ?1, updated_at = ?2 WHERE id = ?3",
+        params![content_str, now, entry_id],
+    )?;
+
+    tx.commit()?;
+
+    Ok(())
+}
+
+#[tauri::command]
+pub fn toggle_version_bookmark(
+    db: State<Database>,
+    version_id: String,
+    bookmarked: bool,
+) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+
+    let updated = conn.execute(
+        "UPDATE entry_versions SET bookmarked = ?1 WHERE id = ?2",
+        params![if bookmarked { 1 } else { 0 }, version_id],
+    )?;
+
+    if updated == 0 {
+        return Err(AppError::new(
+            "NOT_FOUND",
+            &format!("Version '{}' does not exist", version_id),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reverts an entry to its newest bookmarked version and returns that version
+#[tauri::command]
+pub fn revert_to_bookmark(db: State<Database>, entry_id: String) -> Result<EntryVersion, AppError> {
+    let version = {
+        let conn = db.pool.get()?;
+        conn.query_row(
+            &format!(
+                "SELECT {} FROM entry_versions 
+                 WHERE entry_id = ?1 AND bookmarked = 1 
+                 ORDER BY version_number DESC 
+                 LIMIT 1",
+                VERSION_COLUMNS
+            ),
+            params![entry_id],
+            version_from_row,
+        )
+        .map_err(|e| {
+            AppError::from_lookup(e, || {
+                format!("No bookmarked version for entry {}", entry_id)
+            })
+        })?
+    };
+
+    revert_to_version(db, entry_id, version.version_number)?;
+
+    Ok(version)
+}
+
+#[tauri::command]
+pub fn prune_entry_versions(
+    db: State<Database>,
+    entry_id: String,
+    keep_last: i32,
+) -> Result<i32, AppError> {
+    if keep_last < 0 {
+        return Err("keep_last must not be negative".into());
+    }
+
+    let conn = db.pool.get()?;
+
+    let removed = prune_versions(&conn, &entry_id, keep_last)?;
+
+    Ok(removed as i32)
+}
+
+#[tauri::command]
+pub fn prune_all_versions(db: State<Database>, keep_last: i32) -> Result<i32, AppError> {
+    if keep_last < 0 {
+        return Err("keep_last must not be negative".into());
+    }
+
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    let entry_ids: Vec<String> = {
+        let mut stmt = tx.prepare("SELECT DISTINCT entry_id FROM entry_versions")?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        ids
+    };
+
+    let mut removed = 0;
+    for entry_id in &entry_ids {
+        removed += prune_versions(&tx, entry_id, keep_last)?;
+    }
+
+    tx.commit()?;
+
+    Ok(removed as i32)
+}
+
+/// Deletes all but the newest `keep_last` versions of an entry.
+/// The version matching the entry's `version_head` and bookmarked versions are always kept.
+fn prune_versions(
+    conn: &rusqlite::Connection,
+    entry_id: &str,
+    keep_last: i32,
+) -> rusqlite::Result<usize> {
+    conn.execute(
+        "DELETE FROM entry_versions
+         WHERE entry_id = ?1
+           AND version_number != COALESCE((SELECT version_head FROM entries WHERE id = ?1), -1)
+           AND bookmarked = 0
+           AND version_number NOT IN (
+               SELECT version_number FROM entry_versions
+               WHERE entry_id = ?1
+               ORDER BY version_number DESC
+               LIMIT ?2
+           )",
+        params![entry_id, keep_last],
+    )
+}
+
+#[tauri::command]
+pub fn diff_entry_versions(
+    db: State<Database>,
+    entry_id: String,
+    from_version: i32,
+    to_version: i32,
+) -> Result<Vec<DiffOp>, AppError> {
+    let conn = db.pool.get()?;
+
+    let snapshot_text = |version_number: i32| -> Result<String, AppError> {
+        let content_str: String = conn
+            .query_row(
+                "SELECT content_snapshot FROM entry_versions WHERE entry_id = ?1 AND version_number = ?2",
+                params![entry_id, version_number],
+                |row| row.get(0),
+            )
+            .map_err(|e| {
+                AppError::from_lookup(e, || {
+                    format!("Version {} not found for entry {}", version_number, entry_id)
+                })
+            })?;
+
+        Ok(content::extract_plain_text_from_str(&content_str))
+    };
+
+    let from_text = snapshot_text(from_version)?;
+    let to_text = snapshot_text(to_version)?;
+
+    Ok(diff::diff_lines(&from_text, &to_text))
+}
+
+/// Diffs the entry's live content against its latest committed version.
+/// Empty when nothing has been committed yet or nothing changed since.
+#[tauri::command]
+pub fn get_uncommitted_diff(
+    db: State<Database>,
+    entry_id: String,
+) -> Result<Vec<DiffOp>, AppError> {
+    let conn = db.pool.get()?;
+
+    let content_str: String = conn
+        .query_row(
+            "SELECT content FROM entries WHERE id = ?1",
+            params![entry_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| AppError::from_lookup(e, || format!("Entry not found: {}", entry_id)))?;
+
+    let snapshot_str: Option<String> = conn
+        .query_row(
+            "SELECT content_snapshot FROM entry_versions 
+             WHERE entry_id = ?1 
+             ORDER BY version_number DESC 
+             LIMIT 1",
+            params![entry_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    let Some(snapshot_str) = snapshot_str else {
+        return Ok(Vec::new());
+    };
+
+    let committed_text = content::extract_plain_text_from_str(&snapshot_str);
+    let working_text = content::extract_plain_text_from_str(&content_str);
+
+    if committed_text == working_text {
+        return Ok(Vec::new());
+    }
+
+    Ok(diff::diff_lines(&committed_text, &working_text))
+}
+
+// ============================================================
+// SPOTLIGHT COMMANDS
+// ============================================================
+
+#[tauri::command]
+pub fn create_spotlight(
+    db: State<Database>,
+    entry_id: String,
+    context_text: String,
+    highlighted_text: String,
+    start_offset: i32,
+    end_offset: i32,
+) -> Result<Spotlight, AppError> {
+    if start_offset < 0 || end_offset < 0 {
+        return Err(format!(
+            "Spotlight offsets must be non-negative (start: {}, end: {})",
+            start_offset, end_offset
+        )
+        .into());
+    }
+
+    if start_offset > end_offset {
+        return Err(format!(
+            "Spotlight start offset ({}) must not be greater than end offset ({})",
+            start_offset, end_offset
+        )
+        .into());
+    }
+
+    let conn = db.pool.get()?;
+    let id = uuid::Uuid::new_v4().to_string();
+
+    conn.execute(
+        "INSERT INTO spotlights (id, entry_id, context_text, highlighted_text, start_offset, end_offset) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![id, entry_id, context_text, highlighted_text, start_offset, end_offset],
+    )?;
+
+    Ok(Spotlight {
+        id,
+        entry_id,
+        context_text,
+        highlighted_text,
+        start_offset,
+        end_offset,
+    })
+}
+
+#[tauri::command]
+pub fn get_spotlights_for_entry(
+    db: State<Database>,
+    entry_id: String,
+) -> Result<Vec<Spotlight>, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, entry_id, context_text, highlighted_text, start_offset, end_offset 
+             FROM spotlights 
+             WHERE entry_id = ?1 
+             ORDER BY start_offset ASC",
+    )?;
+
+    let spotlights = stmt
+        .query_map(params![entry_id], |row| {
+            Ok(Spotlight {
+                id: row.get(0)?,
+                entry_id: row.get(1)?,
+                context_text: row.get(2)?,
+                highlighted_text: row.get(3)?,
+                start_offset: row.get(4)?,
+                end_offset: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(spotlights)
+}
+
+#[tauri::command]
+pub fn delete_spotlight(db: State<Database>, spotlight_id: String) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+
+    conn.execute(
+        "DELETE FROM spotlights WHERE id = ?1",
+        params![spotlight_id],
+    )?;
+
+    Ok(())
+}
+
+// ============================================================
+// LINK COMMANDS
+// ============================================================
+
+/// Links `source_entry_id` to `target_entry_id`. Both must be live entries, in any streams.
+/// Linking the same pair again keeps the original link.
+#[tauri::command]
+pub fn create_entry_link(
+    db: State<Database>,
+    source_entry_id: String,
+    target_entry_id: String,
+) -> Result<EntryLink, AppError> {
+    if source_entry_id == target_entry_id {
+        return Err("Cannot link an entry to itself".into());
+    }
+
+    let conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    for entry_id in [&source_entry_id, &target_entry_id] {
+        let exists: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM entries WHERE id = ?1 AND deleted_at IS NULL)",
+            params![entry_id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(AppError::new(
+                "NOT_FOUND",
+                &format!("Entry '{}' does not exist", entry_id),
+            ));
+        }
+    }
+
+    conn.execute(
+        "INSERT OR IGNORE INTO entry_links (source_entry_id, target_entry_id, created_at) 
+         VALUES (?1, ?2, ?3)",
+        params![source_entry_id, target_entry_id, now],
+    )?;
+
+    let created_at: i64 = conn.query_row(
+        "SELECT created_at FROM entry_links WHERE source_entry_id = ?1 AND target_entry_id = ?2",
+        params![source_entry_id, target_entry_id],
+        |row| row.get(0),
+    )?;
+
+    Ok(EntryLink {
+        source_entry_id,
+        target_entry_id,
+        created_at,
+    })
+}
+
+#[tauri::command]
+pub fn delete_entry_link(
+    db: State<Database>,
+    source_entry_id: String,
+    target_entry_id: String,
+) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+
+    conn.execute(
+        "DELETE FROM entry_links WHERE source_entry_id = ?1 AND target_entry_id = ?2",
+        params![source_entry_id, target_entry_id],
+    )?;
+
+    Ok(())
+}
+
+/// Entries linked from and to `entry_id`, oldest link first. Links to trashed entries
+/// are hidden until the entry is restored; purging it removes them.
+#[tauri::command]
+pub fn get_linked_entries(
+    db: State<Database>,
+    entry_id: String,
+) -> Result<LinkedEntries, AppError> {
+    let conn = db.pool.get()?;
+
+    // `near` is the column holding `entry_id`, `far` the one holding the linked entry
+    let linked = |near: &str, far: &str| -> Result<Vec<LinkedEntry>, AppError> {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {}, s.title, l.created_at 
+                 FROM entry_links l 
+                 JOIN entries e ON e.id = l.{} 
+                 JOIN streams s ON s.id = e.stream_id 
+                 WHERE l.{} = ?1 AND e.deleted_at IS NULL 
+                 ORDER BY l.created_at ASC, e.id ASC",
+            ENTRY_COLUMNS, far, near
+        ))?;
+
+        let entries = stmt
+            .query_map(params![entry_id], |row| {
+                Ok(LinkedEntry {
+                    entry: entry_from_row(row)?,
+                    stream_title: row.get(ENTRY_COLUMN_COUNT)?,
+                    linked_at: row.get(ENTRY_COLUMN_COUNT + 1)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(entries)
+    };
+
+    Ok(LinkedEntries {
+        outbound: linked("source_entry_id", "target_entry_id")?,
+        inbound: linked("target_entry_id", "source_entry_id")?,
+    })
+}
+
+// ============================================================
+// BRIDGE COMMANDS
+// ============================================================
+
+/// How many random keys to try before giving up on finding an unused one
+const MAX_BRIDGE_KEY_ATTEMPTS: usize = 64;
+
+#[tauri::command]
+pub fn generate_bridge_key(db: State<Database>, length: Option<usize>) -> Result<String, AppError> {
+    use rand::Rng;
+
+    let length = length.unwrap_or(4);
+    if length == 0 {
+        return Err("Bridge key length must be at least 1".into());
+    }
+
+    let conn = db.pool.get()?;
+    let mut rng = rand::thread_rng();
+    let chars: Vec<char> = "abcdefghijklmnopqrstuvwxyz0123456789".chars().collect();
+
+    for _ in 0..MAX_BRIDGE_KEY_ATTEMPTS {
+        let key: String = (0..length)
+            .map(|_| chars[rng.gen_range(0..chars.len())])
+            .collect();
+
+        // Keys are matched case-insensitively, so compare lowercased
+        let in_use: bool = conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM pending_blocks WHERE LOWER(bridge_key) = ?1)",
+            params![key],
+            |row| row.get(0),
+        )?;
+
+        if !in_use {
+            return Ok(key);
+        }
+    }
+
+    Err(format!(
+        "Could not find an unused bridge key of length {} after {} attempts",
+        length, MAX_BRIDGE_KEY_ATTEMPTS
+    )
+    .into())
+}
+
+/// `app_meta` key holding the chosen `BridgeMarkerStyle` as JSON
+const BRIDGE_MARKER_STYLE_KEY: &str = "bridge_marker_style";
+
+/// `style` if given, else the stored preference, else the HTML comment default
+fn marker_style_or_stored(
+    conn: &rusqlite::Connection,
+    style: Option<BridgeMarkerStyle>,
+) -> Result<BridgeMarkerStyle, AppError> {
+    if let Some(style) = style {
+        return Ok(style);
+    }
+
+    let stored = Database::get_meta(conn, BRIDGE_MARKER_STYLE_KEY)?;
+    Ok(stored
+        .and_then(|value| serde_json::from_str(&value).ok())
+        .unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn get_bridge_marker_style(db: State<Database>) -> Result<BridgeMarkerStyle, AppError> {
+    let conn = db.pool.get()?;
+
+    marker_style_or_stored(&conn, None)
+}
+
+#[tauri::command]
+pub fn set_bridge_marker_style(
+    db: State<Database>,
+    style: BridgeMarkerStyle,
+) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+
+    Database::set_meta(
+        &conn,
+        BRIDGE_MARKER_STYLE_KEY,
+        &serde_json::to_string(&style)?,
+    )?;
+
+    Ok(())
+}
+
+/// `style` defaults to the stored preference
+#[tauri::command]
+pub fn validate_bridge_key(
+    db: State<Database>,
+    input_text: String,
+    expected_key: String,
+    style: Option<BridgeMarkerStyle>,
+) -> Result<bool, AppError> {
+    let found_key = extract_bridge_key(db, input_text, style)?;
+
+    Ok(found_key.is_some_and(|found_key| found_key == expected_key.to_lowercase()))
+}
+
+/// Finds the first marker of `style` (default: the stored preference) and returns its
+/// key lowercased
+#[tauri::command]
+pub fn extract_bridge_key(
+    db: State<Database>,
+    input_text: String,
+    style: Option<BridgeMarkerStyle>,
+) -> Result<Option<String>, AppError> {
+    let style = marker_style_or_stored(&*db.pool.get()?, style)?;
+    let pattern = bridge::marker_regex(style)?;
+
+    Ok(pattern
+        .captures(&input_text)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_lowercase()))
+}
+
+/// Builds the text pasted into an AI tool: the directive's instructions, each staged
+/// entry as Markdown in the order given, and the bridge marker the reply must echo.
+/// `style` defaults to the stored preference.
+/// Context ids may carry a `:versionNumber` suffix; the entry's current content is used.
+#[tauri::command]
+pub fn assemble_bridge_payload(
+    db: State<Database>,
+    stream_id: String,
+    staged_context_ids: Vec<String>,
+    directive: String,
+    bridge_key: String,
+    style: Option<BridgeMarkerStyle>,
+) -> Result<String, AppError> {
+    let directive: Directive = directive.parse()?;
+
+    // The marker regex only matches alphanumeric keys
+    if bridge_key.is_empty() || !bridge_key.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(format!("Invalid bridge key '{}'", bridge_key).into());
+    }
+
+    let conn = db.pool.get()?;
+    let style = marker_style_or_stored(&conn, style)?;
+
+    let mut stmt = conn.prepare(
+        "SELECT role, sequence_id, content FROM entries 
+         WHERE id = ?1 AND stream_id = ?2 AND deleted_at IS NULL",
+    )?;
+
+    let mut blocks = Vec::with_capacity(staged_context_ids.len());
+    for context_id in &staged_context_ids {
+        let entry_id = context_entry_id(context_id);
+        let (role, sequence_id, content_str): (String, i32, String) = stmt
+            .query_row(params![entry_id, stream_id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .map_err(|e| {
+                AppError::from_lookup(e, || {
+                    format!("Entry {} not found in stream {}", entry_id, stream_id)
+                })
+            })?;
+        let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
+
+        blocks.push(bridge::ContextBlock {
+            entry_id: entry_id.to_string(),
+            role,
+            sequence_id,
+            body: content::to_markdown(&content),
+        });
+    }
+
+    Ok(bridge::assemble(directive, &blocks, &bridge_key, style))
+}
+
+/// Heuristic used for token estimates; close enough for English prose
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Context window sizes keyed by the frontend's `MODEL_CONFIGS` ids
+const MODEL_CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt4-turbo", 128_000),
+    ("claude-sonnet", 200_000),
+    ("gemini-pro", 128_000),
+    ("default", 4_000),
+];
+
+#[tauri::command]
+pub fn estimate_context_tokens(
+    db: State<Database>,
+    entry_ids: Vec<String>,
+    model: Option<String>,
+) -> Result<TokenEstimate, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt =
+        conn.prepare("SELECT content FROM entries WHERE id = ?1 AND deleted_at IS NULL")?;
+
+    let mut char_count = 0;
+    let mut per_entry = Vec::with_capacity(entry_ids.len());
+    for entry_id in entry_ids {
+        let content_str: String = stmt
+            .query_row(params![entry_id], |row| row.get(0))
+            .map_err(|e| AppError::from_lookup(e, || format!("Entry not found: {}", entry_id)))?;
+
+        let chars = content::extract_plain_text_from_str(&content_str)
+            .chars()
+            .count();
+        char_count += chars;
+        per_entry.push((entry_id, chars.div_ceil(CHARS_PER_TOKEN)));
+    }
+
+    let approx_tokens = char_count.div_ceil(CHARS_PER_TOKEN);
+    let context_window = model.and_then(|model| {
+        MODEL_CONTEXT_WINDOWS
+            .iter()
+            .find(|(id, _)| *id == model)
+            .map(|(_, window)| *window)
+    });
+
+    Ok(TokenEstimate {
+        char_count,
+        approx_tokens,
+        per_entry,
+        context_window,
+        exceeds_window: context_window.is_some_and(|window| approx_tokens > window),
+    })
+}
+
+#[tauri::command]
+pub fn create_pending_block(
+    db: State<Database>,
+    user_id: String,
+    stream_id: String,
+    bridge_key: String,
+    staged_context_ids: Vec<String>,
+    directive: String,
+) -> Result<PendingBlock, AppError> {
+    let directive: Directive = directive.parse()?;
+
+    let conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+    let id = uuid::Uuid::new_v4().to_string();
+    let context_ids_json = serde_json::to_string(&staged_context_ids)?;
+
+    conn.execute(
+        "INSERT INTO pending_blocks (id, user_id, stream_id, bridge_key, staged_context_ids, directive, created_at) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![id, user_id, stream_id, bridge_key, context_ids_json, directive, now],
+    )?;
+
+    Ok(PendingBlock {
+        id,
+        user_id,
+        stream_id,
+        bridge_key,
+        staged_context_ids,
+        directive,
+        created_at: now,
+    })
+}
+
+/// Staged context ids may carry the version that was staged, as `entryId:versionNumber`
+fn context_entry_id(context_id: &str) -> &str {
+    context_id
+        .split_once(':')
+        .map_or(context_id, |(entry_id, _)| entry_id)
+}
+
+/// Maps a `pending_blocks` row selected in table column order
+fn pending_block_from_row(row: &rusqlite::Row) -> rusqlite::Result<PendingBlock> {
+    let context_ids_str: String = row.get(4)?;
+    let staged_context_ids: Vec<String> =
+        serde_json::from_str(&context_ids_str).unwrap_or_default();
+
+    Ok(PendingBlock {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        stream_id: row.get(2)?,
+        bridge_key: row.get(3)?,
+        staged_context_ids,
+        directive: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+#[tauri::command]
+pub fn get_pending_block(
+    db: State<Database>,
+    stream_id: String,
+) -> Result<Option<PendingBlock>, AppError> {
+    let conn = db.pool.get()?;
+
+    let result = conn.query_row(
+        "SELECT id, user_id, stream_id, bridge_key, staged_context_ids, directive, created_at 
+         FROM pending_blocks 
+         WHERE stream_id = ?1 
+         ORDER BY created_at DESC 
+         LIMIT 1",
+        params![stream_id],
+        pending_block_from_row,
+    );
+
+    match result {
+        Ok(block) => Ok(Some(block)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[tauri::command]
+pub fn get_all_pending_blocks(
+    db: State<Database>,
+    stream_id: String,
+) -> Result<Vec<PendingBlock>, AppError> {
+    let conn = db.pool.get()?;
+
+    let mut stmt = conn.prepare(
+        "SELECT id, user_id, stream_id, bridge_key, staged_context_ids, directive, created_at 
+             FROM pending_blocks 
+             WHERE stream_id = ?1 
+             ORDER BY created_at DESC",
+    )?;
+
+    let blocks = stmt
+        .query_map(params![stream_id], pending_block_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(blocks)
+}
+
+/// Every bridge awaiting a response across all streams, newest first
+#[tauri::command]
+pub fn get_active_bridge_keys(db: State<Database>) -> Result<Vec<BridgeKeyInfo>, AppError> {
+    let conn = db.pool.get()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.bridge_key, p.stream_id, s.title, p.directive, p.created_at 
+             FROM pending_blocks p 
+             JOIN streams s ON s.id = p.stream_id 
+             ORDER BY p.created_at DESC, p.id ASC",
+    )?;
+
+    let keys = stmt
+        .query_map([], |row| {
+            let created_at: i64 = row.get(5)?;
+            Ok(BridgeKeyInfo {
+                pending_block_id: row.get(0)?,
+                bridge_key: row.get(1)?,
+                stream_id: row.get(2)?,
+                stream_title: row.get(3)?,
+                directive: row.get(4)?,
+                created_at,
+                age_ms: (now - created_at).max(0),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(keys)
+}
+
+#[tauri::command]
+pub fn delete_pending_block(db: State<Database>, pending_block_id: String) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+
+    conn.execute(
+        "DELETE FROM pending_blocks WHERE id = ?1",
+        params![pending_block_id],
+    )?;
+
+    Ok(())
+}
+
+/// Lands the AI response for a pending block: appends it as an AI entry whose context is
+/// the block's staged entries, unstages those entries, and removes the block, all at once
+#[tauri::command]
+pub fn resolve_pending_block(
+    db: State<Database>,
+    pending_block_id: String,
+    content: serde_json::Value,
+    ai_metadata: AiMetadata,
+) -> Result<Entry, AppError> {
+    content::validate_doc(&content)?;
+    warn_unknown_provider(&ai_metadata);
+
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    let block = tx
+        .query_row(
+            "SELECT id, user_id, stream_id, bridge_key, staged_context_ids, directive, created_at 
+             FROM pending_blocks 
+             WHERE id = ?1",
+            params![pending_block_id],
+            pending_block_from_row,
+        )
+        .map_err(|e| {
+            AppError::from_lookup(e, || {
+                format!("Pending block not found: {}", pending_block_id)
+            })
+        })?;
+
+    ensure_stream_unlocked(&tx, &block.stream_id)?;
+
+    let max_seq: i32 = tx.query_row(
+        "SELECT COALESCE(MAX(sequence_id), 0) FROM entries WHERE stream_id = ?1",
+        params![block.stream_id],
+        |row| row.get(0),
+    )?;
+
+    {
+        let mut unstage_stmt =
+            tx.prepare("UPDATE entries SET is_staged = 0 WHERE id = ?1 AND stream_id = ?2")?;
+        for context_id in &block.staged_context_ids {
+            unstage_stmt.execute(params![context_entry_id(context_id), block.stream_id])?;
+        }
+    }
+
+    let input = CreateEntryInput {
+        user_id: block.user_id,
+        stream_id: block.stream_id,
+        profile_id: None,
+        role: "ai".to_string(),
+        content,
+        ai_metadata: Some(ai_metadata),
+        parent_context_ids: Some(block.staged_context_ids),
+        insert_after_id: None,
+        insert_before_id: None,
+        created_at: None,
+        updated_at: None,
+    };
+    let entry = insert_entry(&tx, input, max_seq + 1)?;
+
+    tx.execute(
+        "DELETE FROM pending_blocks WHERE id = ?1",
+        params![block.id],
+    )?;
+
+    tx.commit()?;
+
+    Ok(entry)
+}
+
+#[tauri::command]
+pub fn cleanup_expired_pending_blocks(
+    db: State<Database>,
+    max_age_ms: i64,
+) -> Result<i32, AppError> {
+    if max_age_ms < 0 {
+        return Err("max_age_ms must not be negative".into());
+    }
+
+    let removed = db.cleanup_expired_pending_blocks(max_age_ms)?;
+
+    Ok(removed as i32)
+}
+
+/// Providers and model ids the bridge recognizes
+#[tauri::command]
+pub fn get_known_providers() -> Result<Vec<ProviderInfo>, AppError> {
+    Ok(providers::PROVIDERS
+        .iter()
+        .map(|provider| ProviderInfo {
+            id: provider.id.to_string(),
+            name: provider.name.to_string(),
+            models: provider.models.iter().map(|m| m.to_string()).collect(),
+        })
+        .collect())
+}
+
+// ============================================================
+// SEARCH COMMANDS
+// ============================================================
+
+/// Characters of context shown on each side of the first match in a search snippet
+const SNIPPET_RADIUS: usize = 40;
+
+/// Turns free-form user input into an FTS5 MATCH expression.
+/// Each word is quoted (so operators and punctuation are literal) and prefix-matched.
+fn fts_match_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[tauri::command]
+pub fn search_entries(
+    db: State<Database>,
+    query: String,
+    stream_id: Option<String>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+) -> Result<SearchPage, AppError> {
+    let conn = db.pool.get()?;
+    let match_query = fts_match_query(&query);
+    let terms: Vec<&str> = query
+        .split_whitespace()
+        .map(|t| t.trim_matches('"'))
+        .collect();
+    let limit = limit.unwrap_or(50);
+    let offset = offset.unwrap_or(0);
+
+    if match_query.is_empty() {
+        return Ok(SearchPage {
+            results: Vec::new(),
+            total_count: 0,
+            has_more: false,
+        });
+    }
+
+    // A NULL stream_id searches every stream
+    let total_count: i64 = conn
+        .prepare_cached(
+            "SELECT COUNT(*) 
+             FROM entries_fts 
+             JOIN entries e ON e.id = entries_fts.entry_id
+             WHERE entries_fts MATCH ?1 AND e.deleted_at IS NULL
+               AND (?2 IS NULL OR e.stream_id = ?2)",
+        )
+        .and_then(|mut stmt| stmt.query_row(params![match_query, stream_id], |row| row.get(0)))?;
+
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {}, s.title 
+                 FROM entries_fts 
+                 JOIN entries e ON e.id = entries_fts.entry_id
+                 JOIN streams s ON s.id = e.stream_id
+                 WHERE entries_fts MATCH ?1 AND e.deleted_at IS NULL
+                   AND (?4 IS NULL OR e.stream_id = ?4)
+                 ORDER BY bm25(entries_fts), e.id ASC
+                 LIMIT ?2 OFFSET ?3",
+        ENTRY_COLUMNS
+    ))?;
+
+    let results = stmt
+        .query_map(params![match_query, limit, offset, stream_id], |row| {
+            let entry = entry_from_row(row)?;
+            let text = content::extract_plain_text(&entry.content);
+
+            Ok(SearchResult {
+                stream_id: entry.stream_id.clone(),
+                stream_title: row.get(ENTRY_COLUMN_COUNT)?,
+                snippet: content::snippet(&text, &terms, SNIPPET_RADIUS),
+                highlighted_snippet: content::highlighted_snippet(&text, &terms, SNIPPET_RADIUS),
+                entry,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let has_more = offset + (results.len() as i64) < total_count;
+
+    Ok(SearchPage {
+        results,
+        total_count,
+        has_more,
+    })
+}
+
+/// Results returned by `search_entries_filtered`
+const FILTERED_RESULT_LIMIT: i64 = 50;
+
+/// Search narrowed by stream, role, profile and creation time, newest first. Values are
+/// always bound, never spliced into the SQL. An empty query matches every entry.
+#[tauri::command]
+pub fn search_entries_filtered(
+    db: State<Database>,
+    query: String,
+    filters: SearchFilters,
+) -> Result<Vec<SearchResult>, AppError> {
+    let conn = db.pool.get()?;
+    let match_query = fts_match_query(&query);
+    let terms: Vec<&str> = query
+        .split_whitespace()
+        .map(|t| t.trim_matches('"'))
+        .collect();
+
+    if let Some(role) = &filters.role {
+        if role != "user" && role != "ai" {
+            return Err(format!("Invalid role filter '{}': expected user or ai", role).into());
+        }
+    }
+
+    let mut conditions = vec!["e.deleted_at IS NULL".to_string()];
+    let mut values: Vec<rusqlite::types::Value> = Vec::new();
+    let mut bind = |condition: &str, value: rusqlite::types::Value| {
+        values.push(value);
+        conditions.push(condition.replace('?', &format!("?{}", values.len())));
+    };
+
+    if !match_query.is_empty() {
+        bind(
+            "e.id IN (SELECT entry_id FROM entries_fts WHERE entries_fts MATCH ?)",
+            match_query.into(),
+        );
+    }
+    if let Some(stream_id) = filters.stream_id {
+        bind("e.stream_id = ?", stream_id.into());
+    }
+    if let Some(role) = filters.role {
+        bind("e.role = ?", role.into());
+    }
+    if let Some(profile_id) = filters.profile_id {
+        bind("e.profile_id = ?", profile_id.into());
+    }
+    if let Some(after) = filters.created_after {
+        bind("e.created_at >= ?", after.into());
+    }
+    if let Some(before) = filters.created_before {
+        bind("e.created_at < ?", before.into());
+    }
+    values.push(FILTERED_RESULT_LIMIT.into());
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, s.title
+             FROM entries e
+             JOIN streams s ON s.id = e.stream_id
+             WHERE {}
+             ORDER BY e.created_at DESC, e.id ASC
+             LIMIT ?{}",
+        ENTRY_COLUMNS,
+        conditions.join(" AND "),
+        values.len()
+    ))?;
+
+    let results = stmt
+        .query_map(rusqlite::params_from_iter(&values), |row| {
+            let entry = entry_from_row(row)?;
+            let text = content::extract_plain_text(&entry.content);
+
+            Ok(SearchResult {
+                stream_id: entry.stream_id.clone(),
+                stream_title: row.get(ENTRY_COLUMN_COUNT)?,
+                snippet: content::snippet(&text, &terms, SNIPPET_RADIUS),
+                highlighted_snippet: content::highlighted_snippet(&text, &terms, SNIPPET_RADIUS),
+                entry,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(results)
+}
+
+/// Live entries whose content references `entry_id` through a mention node, most
+/// recently updated first. `reference_type` overrides the node type, which defaults to
+/// `mention`. Snippets show the start of each referencing entry.
+#[tauri::command]
+pub fn get_backlinks(
+    db: State<Database>,
+    entry_id: String,
+    reference_type: Option<String>,
+) -> Result<Vec<SearchResult>, AppError> {
+    let reference_type = reference_type.unwrap_or_else(|| content::MENTION_NODE.to_string());
+    let conn = db.pool.get()?;
+
+    // instr() narrows the candidates before the content is parsed
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, s.title 
+             FROM entries e 
+             JOIN streams s ON s.id = e.stream_id 
+             WHERE e.deleted_at IS NULL AND e.id != ?1 AND instr(e.content, ?1) > 0 
+             ORDER BY e.updated_at DESC, e.id ASC",
+        ENTRY_COLUMNS
+    ))?;
+
+    let candidates = stmt
+        .query_map(params![entry_id], |row| {
+            Ok((
+                entry_from_row(row)?,
+                row.get::<_, String>(ENTRY_COLUMN_COUNT)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let results = candidates
+        .into_iter()
+        .filter(|(entry, _)| {
+            content::referenced_entry_ids(&entry.content, &[reference_type.as_str()])
+                .contains(&entry_id)
+        })
+        .map(|(entry, stream_title)| {
+            let text = content::extract_plain_text(&entry.content);
+            SearchResult {
+                stream_id: entry.stream_id.clone(),
+                stream_title,
+                snippet: content::snippet(&text, &[], SNIPPET_RADIUS),
+                highlighted_snippet: content::highlighted_snippet(&text, &[], SNIPPET_RADIUS),
+                entry,
+            }
+        })
+        .collect();
+
+    Ok(results)
+}
+
+/// Results returned by `search_versions`
+const VERSION_SEARCH_LIMIT: usize = 50;
+
+/// Searches committed snapshots of live entries, newest commit first, for text that may
+/// since have been edited out. Every query word must appear, case-insensitively, in the
+/// snapshot's plain text. There's no index over snapshots, so each one is scanned.
+#[tauri::command]
+pub fn search_versions(
+    db: State<Database>,
+    query: String,
+) -> Result<Vec<VersionSearchResult>, AppError> {
+    let terms: Vec<&str> = query
+        .split_whitespace()
+        .map(|t| t.trim_matches('"'))
+        .filter(|t| !t.is_empty())
+        .collect();
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let lowered_terms: Vec<String> = terms.iter().map(|t| t.to_lowercase()).collect();
+
+    let conn = db.pool.get()?;
+
+    // Version columns in `VERSION_COLUMNS` order, then the stream
+    let mut stmt = conn.prepare(
+        "SELECT v.id, v.entry_id, v.version_number, v.content_snapshot, v.commit_message, 
+                v.committed_at, v.label, v.bookmarked, e.stream_id, s.title 
+             FROM entry_versions v 
+             JOIN entries e ON e.id = v.entry_id 
+             JOIN streams s ON s.id = e.stream_id 
+             WHERE e.deleted_at IS NULL 
+             ORDER BY v.committed_at DESC, v.id ASC",
+    )?;
+    let mut rows = stmt.query([])?;
+
+    let mut results = Vec::new();
+    while let Some(row) = rows.next()? {
+        let version = version_from_row(row)?;
+        let text = content::extract_plain_text(&version.content_snapshot);
+        let lowered = text.to_lowercase();
+        if !lowered_terms.iter().all(|term| lowered.contains(term)) {
+            continue;
+        }
+
+        results.push(VersionSearchResult {
+            entry_id: version.entry_id.clone(),
+            stream_id: row.get(8)?,
+            stream_title: row.get(9)?,
+            snippet: content::snippet(&text, &terms, SNIPPET_RADIUS),
+            highlighted_snippet: content::highlighted_snippet(&text, &terms, SNIPPET_RADIUS),
+            version,
+        });
+        if results.len() == VERSION_SEARCH_LIMIT {
+            break;
+        }
+    }
+
+    Ok(results)
+}
+
+/// Largest typo distance `search_entries_fuzzy` accepts per word
+const MAX_FUZZY_DISTANCE: usize = 3;
+
+/// Entries pulled from SQLite for fuzzy scoring, before ranking in Rust
+const FUZZY_CANDIDATE_LIMIT: i64 = 500;
+
+/// Fuzzy results returned, closest first
+const FUZZY_RESULT_LIMIT: usize = 50;
+
+/// Typo-tolerant search. Candidates are entries sharing a fragment with a query word (see
+/// `fuzzy::candidate_fragments`); each must then contain, for every query word, a word within
+/// `max_distance` edits (capped at 3). Results are ordered by total distance, then most
+/// recently updated.
+#[tauri::command]
+pub fn search_entries_fuzzy(
+    db: State<Database>,
+    query: String,
+    max_distance: usize,
+) -> Result<Vec<SearchResult>, AppError> {
+    let conn = db.pool.get()?;
+    let max_distance = max_distance.min(MAX_FUZZY_DISTANCE);
+    let terms: Vec<&str> = query.split_whitespace().collect();
+
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // A term short enough to match any word leaves nothing to narrow by, so candidates
+    // are then bounded only by the limit, to the most recently updated entries
+    let fragments = terms
+        .iter()
+        .map(|term| fuzzy::candidate_fragments(term, max_distance))
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default();
+    let mut patterns: Vec<String> = fragments
+        .into_iter()
+        .flatten()
+        .map(|fragment| {
+            let escaped = fragment
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            format!("%{}%", escaped)
+        })
+        .collect();
+    patterns.sort();
+    patterns.dedup();
+
+    // ?1 is the candidate limit; the LIKE patterns follow as ?2..?N
+    let conditions = if patterns.is_empty() {
+        "1".to_string()
+    } else {
+        (2..patterns.len() + 2)
+            .map(|i| format!("entries_fts.body LIKE ?{} ESCAPE '\\'", i))
+            .collect::<Vec<_>>()
+            .join(" OR ")
+    };
+    let values: Vec<rusqlite::types::Value> = std::iter::once(FUZZY_CANDIDATE_LIMIT.into())
+        .chain(patterns.into_iter().map(Into::into))
+        .collect();
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, s.title, entries_fts.body
+             FROM entries_fts
+             JOIN entries e ON e.id = entries_fts.entry_id
+             JOIN streams s ON s.id = e.stream_id
+             WHERE e.deleted_at IS NULL AND ({})
+             ORDER BY e.updated_at DESC, e.id ASC
+             LIMIT ?1",
+        ENTRY_COLUMNS, conditions
+    ))?;
+
+    let candidates = stmt
+        .query_map(rusqlite::params_from_iter(&values), |row| {
+            Ok((
+                entry_from_row(row)?,
+                row.get::<_, String>(ENTRY_COLUMN_COUNT)?,
+                row.get::<_, String>(ENTRY_COLUMN_COUNT + 1)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut scored: Vec<(usize, SearchResult)> = candidates
+        .into_iter()
+        .filter_map(|(entry, stream_title, text)| {
+            // Every query word needs a close match; the matched words anchor the snippet
+            let matches: Vec<(usize, &str)> = terms
+                .iter()
+                .map(|term| fuzzy::closest_word(term, &text, max_distance))
+                .collect::<Option<_>>()?;
+            let distance = matches.iter().map(|(d, _)| d).sum();
+            let matched: Vec<&str> = matches.iter().map(|(_, word)| *word).collect();
+
+            Some((
+                distance,
+                SearchResult {
+                    stream_id: entry.stream_id.clone(),
+                    stream_title,
+                    snippet: content::snippet(&text, &matched, SNIPPET_RADIUS),
+                    highlighted_snippet: content::highlighted_snippet(
+                        &text,
+                        &matched,
+                        SNIPPET_RADIUS,
+                    ),
+                    entry,
+                },
+            ))
+        })
+        .collect();
+
+    // Stable sort keeps the most-recently-updated order among equal distances
+    scored.sort_by_key(|(distance, _)| *distance);
+
+    Ok(scored
+        .into_iter()
+        .take(FUZZY_RESULT_LIMIT)
+        .map(|(_, result)| result)
+        .collect())
+}
+
+/// Most recently updated entries across every stream, for a "where you left off" view.
+/// The snippet is the start of each entry. Non-positive limits fall back to 20.
+#[tauri::command]
+pub fn get_recent_entries(db: State<Database>, limit: i32) -> Result<Vec<SearchResult>, AppError> {
+    let conn = db.pool.get()?;
+    let limit = if limit > 0 { limit } else { 20 };
+
+    let mut stmt = conn.prepare_cached(&format!(
+        "SELECT {}, s.title
+             FROM entries e
+             JOIN streams s ON s.id = e.stream_id
+             WHERE e.deleted_at IS NULL
+             ORDER BY e.updated_at DESC, e.id ASC
+             LIMIT ?1",
+        ENTRY_COLUMNS
+    ))?;
+
+    let results = stmt
+        .query_map(params![limit], |row| {
+            let entry = entry_from_row(row)?;
+            let text = content::extract_plain_text(&entry.content);
+
+            Ok(SearchResult {
+                stream_id: entry.stream_id.clone(),
+                stream_title: row.get(ENTRY_COLUMN_COUNT)?,
+                snippet: content::snippet(&text, &[], SNIPPET_RADIUS),
+                highlighted_snippet: content::highlighted_snippet(&text, &[], SNIPPET_RADIUS),
+                entry,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(results)
+}
+
+// ============================================================
+// EXPORT COMMANDS
+// ============================================================
+
+#[tauri::command]
+pub fn export_stream(db: State<Database>, stream_id: String) -> Result<String, AppError> {
+    let conn = db.pool.get()?;
+    let exported = read_exported_stream(&conn, &stream_id, false)?;
+
+    serde_json::to_string_pretty(&exported).map_err(AppError::from)
+}
+
+/// Reads a stream with its live entries, their versions, and spotlights.
+/// `include_deleted` adds entries in the trash too.
+fn read_exported_stream(
+    conn: &rusqlite::Connection,
+    stream_id: &str,
+    include_deleted: bool,
+) -> Result<ExportedStream, AppError> {
+    let stream = conn.query_row(
+        &format!("SELECT {} FROM streams WHERE id = ?1", STREAM_COLUMNS),
+        params![stream_id],
+        stream_from_row,
+    )?;
+
+    let mut entry_stmt = conn.prepare(&format!(
+        "SELECT {} FROM entries e 
+                 WHERE e.stream_id = ?1 AND (?2 OR e.deleted_at IS NULL)
+                 ORDER BY e.sequence_id ASC, e.id ASC",
+        ENTRY_COLUMNS
+    ))?;
+
+    let entries = entry_stmt
+        .query_map(params![stream_id, include_deleted], entry_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut version_stmt = conn.prepare(&format!(
+        "SELECT {} 
+             FROM entry_versions 
+             WHERE entry_id = ?1 
+             ORDER BY version_number ASC",
+        VERSION_COLUMNS
+    ))?;
+
+    let mut spotlight_stmt = conn.prepare(
+        "SELECT id, entry_id, context_text, highlighted_text, start_offset, end_offset 
+             FROM spotlights 
+             WHERE entry_id = ?1 
+             ORDER BY start_offset ASC",
+    )?;
+
+    let mut exported_entries = Vec::with_capacity(entries.len());
+
+    for entry in entries {
+        let versions = version_stmt
+            .query_map(params![entry.id], version_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let spotlights = spotlight_stmt
+            .query_map(params![entry.id], |row| {
+                Ok(Spotlight {
+                    id: row.get(0)?,
+                    entry_id: row.get(1)?,
+                    context_text: row.get(2)?,
+                    highlighted_text: row.get(3)?,
+                    start_offset: row.get(4)?,
+                    end_offset: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        exported_entries.push(ExportedEntry {
+            entry,
+            versions,
+            spotlights,
+        });
+    }
+
+    Ok(ExportedStream {
+        schema_version: EXPORT_SCHEMA_VERSION,
+        exported_at: chrono::Utc::now().timestamp_millis(),
+        stream,
+        entries: exported_entries,
+    })
+}
+
+#[tauri::command]
+pub fn import_stream(db: State<Database>, json: String) -> Result<Stream, AppError> {
+    let exported: ExportedStream =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid stream export: {}", e))?;
+
+    if exported.schema_version > EXPORT_SCHEMA_VERSION {
+        return Err(format!(
+            "Export schema version {} is newer than supported version {}",
+            exported.schema_version, EXPORT_SCHEMA_VERSION
+        )
+        .into());
+    }
+
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    let stream = insert_exported_stream(&tx, exported, false)?;
+
+    tx.commit()?;
+
+    Ok(stream)
+}
+
+/// Inserts an exported stream under fresh ids, remapping internal references.
+/// `keep_ids` reuses the exported ids instead, for putting back a deleted stream.
+/// Runs on the caller's transaction.
+fn insert_exported_stream(
+    tx: &rusqlite::Transaction,
+    exported: ExportedStream,
+    keep_ids: bool,
+) -> Result<Stream, AppError> {
+    // Fresh ids everywhere so importing never collides with existing rows
+    let new_id = |id: &str| {
+        if keep_ids {
+            id.to_string()
+        } else {
+            uuid::Uuid::new_v4().to_string()
+        }
+    };
+    // Profiles are not part of the export; only keep links to profiles that exist locally
+    let mut profile_exists_stmt = tx.prepare("SELECT 1 FROM profiles WHERE id = ?1")?;
+    let default_profile_id = match exported.stream.default_profile_id {
+        Some(profile_id) => profile_exists_stmt
+            .exists(params![profile_id])?
+            .then_some(profile_id),
+        None => None,
+    };
+    let stream = Stream {
+        id: new_id(&exported.stream.id),
+        default_profile_id,
+        ..exported.stream
+    };
+    let entry_id_map: HashMap<String, String> = exported
+        .entries
+        .iter()
+        .map(|e| (e.entry.id.clone(), new_id(&e.entry.id)))
+        .collect();
+
+    let tags_json = serde_json::to_string(&stream.tags)?;
+    tx.execute(
+        "INSERT INTO streams (id, user_id, title, description, tags, color, pinned, created_at, updated_at, archived, default_profile_id, word_goal, locked) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        params![
+            stream.id,
+            stream.user_id,
+            stream.title,
+            stream.description,
+            tags_json,
+            stream.color,
+            if stream.pinned { 1 } else { 0 },
+            stream.created_at,
+            stream.updated_at,
+            if stream.archived { 1 } else { 0 },
+            stream.default_profile_id,
+            stream.word_goal,
+            if stream.locked { 1 } else { 0 }
+        ],
+    )?;
+    Database::sync_stream_tags(tx, &stream.id, &stream.tags)?;
+
+    if stream.pinned {
+        Database::append_pin_order(tx, &stream.id)?;
+    }
+
+    {
+        let mut entry_stmt = tx
+            .prepare(
+                "INSERT INTO entries (id, user_id, stream_id, profile_id, role, content, sequence_id, version_head, is_staged, parent_context_ids, ai_metadata, created_at, updated_at, is_pinned, color, label) 
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            )?;
+        let mut version_stmt = tx
+            .prepare(
+                "INSERT INTO entry_versions (id, entry_id, version_number, content_snapshot, commit_message, committed_at, content_hash, label, bookmarked) 
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, content_hash(?4), ?7, ?8)",
+            )?;
+        let mut spotlight_stmt = tx
+            .prepare(
+                "INSERT INTO spotlights (id, entry_id, context_text, highlighted_text, start_offset, end_offset) 
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?;
+
+        for exported_entry in exported.entries {
+            let entry = exported_entry.entry;
+            let new_entry_id = &entry_id_map[&entry.id];
+
+            let profile_id = match entry.profile_id {
+                Some(profile_id) => {
+                    let exists = profile_exists_stmt.exists(params![profile_id])?;
+                    exists.then_some(profile_id)
+                }
+                None => None,
+            };
+
+            // Remap context references within the stream, keeping any `:version` suffix;
+            // references elsewhere are kept as-is
+            let parent_context_ids = entry.parent_context_ids.map(|ids| {
+                ids.into_iter()
+                    .map(|id| {
+                        let old_entry_id = context_entry_id(&id);
+                        match entry_id_map.get(old_entry_id) {
+                            Some(new_id) => format!("{new_id}{}", &id[old_entry_id.len()..]),
+                            None => id,
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            });
+
+            let content_str = serde_json::to_string(&entry.content)?;
+            let parent_context_ids_str = parent_context_ids
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?;
+            let ai_metadata_str = entry
+                .ai_metadata
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()?;
+
+            entry_stmt.execute(params![
+                new_entry_id,
+                entry.user_id,
+                stream.id,
+                profile_id,
+                entry.role,
+                content_str,
+                entry.sequence_id,
+                entry.version_head,
+                if entry.is_staged { 1 } else { 0 },
+                parent_context_ids_str,
+                ai_metadata_str,
+                entry.created_at,
+                entry.updated_at,
+                if entry.is_pinned { 1 } else { 0 },
+                entry.color,
+                entry.label
+            ])?;
+
+            for version in exported_entry.versions {
+                let snapshot_str = serde_json::to_string(&version.content_snapshot)?;
+
+                version_stmt.execute(params![
+                    new_id(&version.id),
+                    new_entry_id,
+                    version.version_number,
+                    snapshot_str,
+                    version.commit_message,
+                    version.committed_at,
+                    version.label,
+                    if version.bookmarked { 1 } else { 0 }
+                ])?;
+            }
+
+            for spotlight in exported_entry.spotlights {
+                spotlight_stmt.execute(params![
+                    new_id(&spotlight.id),
+                    new_entry_id,
+                    spotlight.context_text,
+                    spotlight.highlighted_text,
+                    spotlight.start_offset,
+                    spotlight.end_offset
+                ])?;
+            }
+        }
+    }
+
+    Ok(stream)
+}
+
+#[tauri::command]
+pub fn export_stream_markdown(db: State<Database>, stream_id: String) -> Result<String, AppError> {
+    let conn = db.pool.get()?;
+
+    let stream = conn.query_row(
+        &format!("SELECT {} FROM streams WHERE id = ?1", STREAM_COLUMNS),
+        params![stream_id],
+        stream_from_row,
+    )?;
+
+    let mut stmt = conn.prepare(
+        "SELECT content, ai_metadata 
+             FROM entries 
+             WHERE stream_id = ?1 AND deleted_at IS NULL
+             ORDER BY sequence_id ASC, id ASC",
+    )?;
+
+    let blocks = stmt
+        .query_map(params![stream_id], |row| {
+            let content_str: String = row.get(0)?;
+            let ai_metadata_str: Option<String> = row.get(1)?;
+            let ai_metadata: Option<AiMetadata> =
+                ai_metadata_str.and_then(|s| serde_json::from_str(&s).ok());
+
+            let content: serde_json::Value = serde_json::from_str(&content_str).unwrap_or_default();
+            let mut block = content::to_markdown(&content);
+
+            // Attribute AI-authored entries to the model that produced them
+            if let Some(metadata) = ai_metadata {
+                if !block.is_empty() {
+                    block.push_str("\n\n");
+                }
+                block.push_str(&format!("> _from {}_", metadata.model));
+            }
+
+            Ok(block)
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut markdown = format!("# {}\n", stream.title);
+    if let Some(description) = stream.description.filter(|d| !d.is_empty()) {
+        markdown.push_str(&format!("\n{}\n", description));
+    }
+
+    for block in blocks.iter().filter(|b| !b.is_empty()) {
+        markdown.push_str("\n---\n\n");
+        markdown.push_str(block);
+        markdown.push('\n');
+    }
+
+    Ok(markdown)
+}
+
+/// Files at the root of a workspace archive; streams live under `streams/`
+const ARCHIVE_MANIFEST_FILE: &str = "manifest.json";
+const ARCHIVE_PROFILES_FILE: &str = "profiles.json";
+const ARCHIVE_META_FILE: &str = "app_meta.json";
+
+/// Writes the whole workspace to a zip at `destination_path`: a manifest, every profile,
+/// the `app_meta` values, and one `streams/<id>.json` per stream in the `export_stream` shape.
+/// Unlike `backup_database` the result is plain JSON that can be inspected by hand.
+#[tauri::command]
+pub fn export_all(db: State<Database>, destination_path: String) -> Result<(), AppError> {
+    let target = resolve_backup_destination(&db, &destination_path)?;
+    let conn = db.pool.get()?;
+
+    let profiles = conn
+        .prepare(
+            "SELECT id, user_id, name, role, avatar_url, color, initials, bio, is_default, created_at, updated_at 
+             FROM profiles 
+             ORDER BY created_at ASC, id ASC",
+        )?
+        .query_map([], |row| {
+            Ok(Profile {
+                id: row.get(0)?,
+                user_id: row.get(1)?,
+                name: row.get(2)?,
+                role: row.get(3)?,
+                avatar_url: row.get(4)?,
+                color: row.get(5)?,
+                initials: row.get(6)?,
+                bio: row.get(7)?,
+                is_default: row.get::<_, i32>(8)? != 0,
+                created_at: row.get(9)?,
+                updated_at: row.get(10)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let meta = conn
+        .prepare("SELECT key, value FROM app_meta ORDER BY key")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<BTreeMap<String, String>, _>>()?;
+
+    let stream_ids = conn
+        .prepare("SELECT id FROM streams ORDER BY created_at ASC, id ASC")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let streams = stream_ids
+        .iter()
+        .map(|id| read_exported_stream(&conn, id, false))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let schema_version: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )?;
+
+    let manifest = WorkspaceManifest {
+        archive_version: WORKSPACE_ARCHIVE_VERSION,
+        schema_version,
+        exported_at: chrono::Utc::now().timestamp_millis(),
+        profile_count: profiles.len(),
+        stream_count: streams.len(),
+        entry_count: streams.iter().map(|s| s.entries.len()).sum(),
+        streams: stream_ids
+            .iter()
+            .map(|id| format!("streams/{}.json", id))
+            .collect(),
+    };
+
+    let mut archive = zip::ZipWriter::new(std::fs::File::create(&target)?);
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    write_archive_json(&mut archive, options, ARCHIVE_MANIFEST_FILE, &manifest)?;
+    write_archive_json(&mut archive, options, ARCHIVE_PROFILES_FILE, &profiles)?;
+    write_archive_json(&mut archive, options, ARCHIVE_META_FILE, &meta)?;
+    for (name, stream) in manifest.streams.iter().zip(&streams) {
+        write_archive_json(&mut archive, options, name, stream)?;
+    }
+
+    archive.finish()?;
+
+    Ok(())
+}
+
+/// Restores an archive written by `export_all` alongside the existing data.
+/// Profiles and streams get fresh ids, with entry attributions remapped to the new
+/// profiles; imported profiles never replace the local default, and `app_meta` keys
+/// already set locally are kept.
+#[tauri::command]
+pub fn import_all(db: State<Database>, zip_path: String) -> Result<Vec<Stream>, AppError> {
+    let file =
+        std::fs::File::open(&zip_path).map_err(|e| format!("Cannot open {}: {}", zip_path, e))?;
+    let mut archive = zip::ZipArchive::new(file)
+        .map_err(|e| format!("{} is not a workspace archive: {}", zip_path, e))?;
+
+    let manifest: WorkspaceManifest = read_archive_json(&mut archive, ARCHIVE_MANIFEST_FILE)?;
+    if manifest.archive_version > WORKSPACE_ARCHIVE_VERSION {
+        return Err(format!(
+            "Archive version {} is newer than supported version {}",
+            manifest.archive_version, WORKSPACE_ARCHIVE_VERSION
+        )
+        .into());
+    }
+
+    let profiles: Vec<Profile> = read_archive_json(&mut archive, ARCHIVE_PROFILES_FILE)?;
+    let meta: BTreeMap<String, String> = read_archive_json(&mut archive, ARCHIVE_META_FILE)?;
+
+    let mut streams = Vec::with_capacity(manifest.streams.len());
+    for name in &manifest.streams {
+        let exported: ExportedStream = read_archive_json(&mut archive, name)?;
+        if exported.schema_version > EXPORT_SCHEMA_VERSION {
+            return Err(format!(
+                "Export schema version {} in {} is newer than supported version {}",
+                exported.schema_version, name, EXPORT_SCHEMA_VERSION
+            )
+            .into());
+        }
+        streams.push(exported);
+    }
+
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    let mut profile_id_map = HashMap::new();
+    {
+        let mut profile_stmt = tx.prepare(
+            "INSERT INTO profiles (id, user_id, name, role, avatar_url, color, initials, bio, is_default, created_at, updated_at) 
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 0, ?9, ?10)",
+        )?;
+
+        for profile in profiles {
+            let new_id = uuid::Uuid::new_v4().to_string();
+            profile_stmt.execute(params![
+                new_id,
+                profile.user_id,
+                profile.name,
+                profile.role,
+                profile.avatar_url,
+                profile.color,
+                profile.initials,
+                profile.bio,
+                profile.created_at,
+                profile.updated_at
+            ])?;
+            profile_id_map.insert(profile.id, new_id);
+        }
+    }
+
+    let remap_profile =
+        |id: Option<String>| id.map(|id| profile_id_map.get(&id).cloned().unwrap_or(id));
+
+    let mut stream_id_map = HashMap::new();
+    let mut imported = Vec::with_capacity(streams.len());
+    for mut exported in streams {
+        exported.stream.default_profile_id = remap_profile(exported.stream.default_profile_id);
+        for exported_entry in &mut exported.entries {
+            let profile_id = exported_entry.entry.profile_id.take();
+            exported_entry.entry.profile_id = remap_profile(profile_id);
+        }
+
+        let old_id = exported.stream.id.clone();
+        let stream = insert_exported_stream(&tx, exported, false)?;
+        stream_id_map.insert(old_id, stream.id.clone());
+        imported.push(stream);
+    }
+
+    {
+        let mut meta_stmt =
+            tx.prepare("INSERT OR IGNORE INTO app_meta (key, value) VALUES (?1, ?2)")?;
+
+        for (key, value) in meta {
+            // The last opened stream only makes sense under its new id
+            let value = if key == LAST_OPENED_STREAM_KEY {
+                match stream_id_map.get(&value) {
+                    Some(new_id) => new_id.clone(),
+                    None => continue,
+                }
+            } else {
+                value
+            };
+            meta_stmt.execute(params![key, value])?;
+        }
+    }
+
+    tx.commit()?;
+
+    Ok(imported)
+}
+
+fn write_archive_json<T: serde::Serialize>(
+    archive: &mut zip::ZipWriter<std::fs::File>,
+    options: zip::write::SimpleFileOptions,
+    name: &str,
+    value: &T,
+) -> Result<(), AppError> {
+    archive.start_file(name, options)?;
+    serde_json::to_writer_pretty(archive, value)?;
+    Ok(())
+}
+
+fn read_archive_json<T: serde::de::DeserializeOwned>(
+    archive: &mut zip::ZipArchive<std::fs::File>,
+    name: &str,
+) -> Result<T, AppError> {
+    let file = archive
+        .by_name(name)
+        .map_err(|_| format!("Workspace archive is missing {}", name))?;
+    serde_json::from_reader(file)
+        .map_err(|e| format!("Invalid {} in workspace archive: {}", name, e).into())
+}
+
+// ============================================================
+// UNDO COMMANDS
+// ============================================================
+
+/// Undo snapshots kept; older ones are dropped as new ones arrive
+const UNDO_LOG_LIMIT: i64 = 50;
+
+/// Saves what a destructive command is about to remove. Runs on the command's own
+/// transaction so the snapshot and the deletion land together.
+fn record_undo(tx: &rusqlite::Transaction, snapshot: &UndoSnapshot) -> Result<(), AppError> {
+    tx.execute(
+        "INSERT INTO undo_log (operation, snapshot, created_at) VALUES (?1, ?2, ?3)",
+        params![
+            snapshot.operation(),
+            serde_json::to_string(snapshot)?,
+            chrono::Utc::now().timestamp_millis()
+        ],
+    )?;
+
+    tx.execute(
+        "DELETE FROM undo_log WHERE id NOT IN (SELECT id FROM undo_log ORDER BY id DESC LIMIT ?1)",
+        params![UNDO_LOG_LIMIT],
+    )?;
+
+    Ok(())
+}
+
+/// Puts back what the most recent delete_stream, delete_entry/delete_entries or
+/// clear_all_staging removed, then forgets that snapshot.
+#[tauri::command]
+pub fn undo_last_operation(db: State<Database>) -> Result<(), AppError> {
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+
+    let (log_id, snapshot): (i64, String) = tx
+        .query_row(
+            "SELECT id, snapshot FROM undo_log ORDER BY id DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| AppError::from_lookup(e, || "Nothing to undo".to_string()))?;
+
+    match serde_json::from_str(&snapshot)? {
+        UndoSnapshot::DeleteStream { stream, deleted_at } => {
+            insert_exported_stream(&tx, *stream, true)?;
+            for (entry_id, deleted_at) in deleted_at {
+                tx.execute(
+                    "UPDATE entries SET deleted_at = ?1 WHERE id = ?2",
+                    params![deleted_at, entry_id],
+                )?;
+            }
+        }
+        UndoSnapshot::DeleteEntries { entries } => {
+            for (entry_id, was_staged) in entries {
+                untrash_entry(&tx, &entry_id)?;
+                tx.execute(
+                    "UPDATE entries SET is_staged = ?1 WHERE id = ?2",
+                    params![if was_staged { 1 } else { 0 }, entry_id],
+                )?;
+            }
+        }
+        UndoSnapshot::ClearStaging { entry_ids } => {
+            for entry_id in entry_ids {
+                tx.execute(
+                    "UPDATE entries SET is_staged = 1 WHERE id = ?1",
+                    params![entry_id],
+                )?;
+            }
+        }
+    }
+
+    tx.execute("DELETE FROM undo_log WHERE id = ?1", params![log_id])?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+// ============================================================
+// SETTINGS COMMANDS
+// ============================================================
+
+/// Reads a persisted setting. Values are opaque strings, usually JSON written by the frontend.
+#[tauri::command]
+pub fn get_meta(db: State<Database>, key: String) -> Result<Option<String>, AppError> {
+    let conn = db.pool.get()?;
+
+    Database::get_meta(&conn, &key).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn set_meta(db: State<Database>, key: String, value: String) -> Result<(), AppError> {
+    let conn = db.pool.get()?;
+
+    Database::set_meta(&conn, &key, &value).map_err(AppError::from)
+}
+
+/// `app_meta` key holding the macOS traffic light offset as `[x, y]`
+#[cfg(target_os = "macos")]
+const TRAFFIC_LIGHT_POSITION_KEY: &str = "traffic_light_position";
+
+/// Offset saved by `set_traffic_light_position`, if any
+#[cfg(target_os = "macos")]
+pub fn stored_traffic_light_position(db: &Database) -> Option<(f64, f64)> {
+    let conn = db.pool.get().ok()?;
+    let value = Database::get_meta(&conn, TRAFFIC_LIGHT_POSITION_KEY).ok()??;
+    serde_json::from_str(&value).ok()
+}
+
+/// Moves the macOS close/minimize/zoom buttons to `(x, y)` from the window's top-left
+/// and remembers the offset for future launches. Does nothing on other platforms.
+#[tauri::command]
+pub fn set_traffic_light_position(
+    window: tauri::WebviewWindow,
+    db: State<Database>,
+    x: f64,
+    y: f64,
+) -> Result<(), AppError> {
+    #[cfg(target_os = "macos")]
+    {
+        if !x.is_finite() || !y.is_finite() {
+            return Err("Traffic light position must be finite".into());
+        }
+
+        let conn = db.pool.get()?;
+        let value = serde_json::to_string(&(x, y))?;
+        Database::set_meta(&conn, TRAFFIC_LIGHT_POSITION_KEY, &value)?;
+
+        let ns_window = window
+            .ns_window()
+            .map_err(|e| AppError::new("WINDOW", &e.to_string()))?;
+        // Synchronous commands run on the main thread, which AppKit requires
+        unsafe {
+            crate::reposition_traffic_lights(ns_window as cocoa::base::id, x, y);
+        }
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    let _ = (window, db, x, y);
+
+    Ok(())
+}
+
+// ============================================================
+// DATABASE COMMANDS
+// ============================================================
+
+/// Whether an encrypted database is still waiting for `set_database_key`.
+/// Every command that needs the database fails until then.
+#[tauri::command]
+pub fn is_database_locked<R: tauri::Runtime>(app: tauri::AppHandle<R>) -> bool {
+    app.try_state::<Database>().is_none()
+}
+
+/// Unlocks an encrypted database with `key`, or re-encrypts the open one under it.
+/// The key is then saved to the OS keychain, so later launches unlock on their own.
+#[tauri::command]
+pub fn set_database_key<R: tauri::Runtime>(
+    app: tauri::AppHandle<R>,
+    key: String,
+) -> Result<(), AppError> {
+    if key.is_empty() {
+        return Err("Database key cannot be empty".into());
+    }
+
+    #[cfg(not(feature = "encryption"))]
+    {
+        let _ = app;
+        Err(database::DatabaseError::EncryptionUnavailable.into())
+    }
+
+    #[cfg(feature = "encryption")]
+    {
+        match app.try_state::<Database>() {
+            Some(db) => db.change_key(&key)?,
+            None => {
+                let app_data_dir = app
+                    .path()
+                    .app_data_dir()
+                    .map_err(|e| AppError::new("IO", &e.to_string()))?;
+                let db = Database::new(app_data_dir, Some(key.clone())).map_err(|e| match e {
+                    database::DatabaseError::Sqlite(rusqlite::Error::SqliteFailure(failure, _))
+                        if failure.code == rusqlite::ErrorCode::NotADatabase =>
+                    {
+                        AppError::new("INVALID_KEY", "This key doesn't unlock the database")
+                    }
+                    e => e.into(),
+                })?;
+                db.run_startup_tasks()?;
+                app.manage(db);
+            }
+        }
+
+        crate::keychain::store_database_key(&key).map_err(|e| {
+            AppError::new(
+                "KEYCHAIN",
+                &format!("Database unlocked, but the key couldn't be saved: {}", e),
+            )
+        })
+    }
+}
+
+/// Reports where the database lives and whether it is healthy.
+/// `integrity_check` reads every page, so this is slow on large databases.
+#[tauri::command]
+pub fn get_database_info(db: State<Database>) -> Result<DbInfo, AppError> {
+    let conn = db.pool.get()?;
+
+    let pragma = |name: &str| -> Result<i64, AppError> {
+        conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+            .map_err(AppError::from)
+    };
+    let page_count = pragma("page_count")?;
+    let page_size = pragma("page_size")?;
+
+    let journal_mode: String = conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+
+    let user_version: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+        [],
+        |row| row.get(0),
+    )?;
+
+    // A healthy database yields a single "ok" row; otherwise each row describes a problem
+    let integrity: String = conn.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+
+    Ok(DbInfo {
+        path: db.path.to_string_lossy().into_owned(),
+        page_count,
+        page_size,
+        size_bytes: page_count * page_size,
+        journal_mode,
+        user_version,
+        integrity_ok: integrity == "ok",
+    })
+}
+
+/// Refreshes query planner statistics and rebuilds the file to reclaim space left by deletes.
+/// VACUUM rewrites the whole database and blocks writers from other pooled connections
+/// until it finishes, so call this when the app is idle.
+#[tauri::command]
+pub fn optimize_database(db: State<Database>) -> Result<OptimizeReport, AppError> {
+    let conn = db.pool.get()?;
+
+    // Fold the WAL back into the main file so both sizes measure the same thing
+    let checkpoint = || {
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .map_err(AppError::from)
+    };
+    let file_size = || {
+        std::fs::metadata(&db.path)
+            .map(|m| m.len())
+            .map_err(AppError::from)
+    };
+
+    checkpoint()?;
+    let bytes_before = file_size()?;
+
+    // VACUUM cannot run inside a transaction, so this uses plain autocommit statements
+    conn.execute_batch("PRAGMA optimize; VACUUM;")?;
+
+    checkpoint()?;
+    let bytes_after = file_size()?;
+
+    Ok(OptimizeReport {
+        bytes_before,
+        bytes_after,
+    })
+}
+
+/// Writes a consistent copy of the live database to `destination_path` using SQLite's
+/// online backup API, which is safe while other connections keep writing.
+#[tauri::command]
+pub fn backup_database(db: State<Database>, destination_path: String) -> Result<(), AppError> {
+    let target = resolve_backup_destination(&db, &destination_path)?;
+
+    let conn = db.pool.get()?;
+
+    conn.backup(rusqlite::DatabaseName::Main, &target, None)?;
+
+    Ok(())
+}
+
+/// Checks that a backup can be written to `destination_path` and resolves it to an
+/// absolute path, refusing the live database file itself
+fn resolve_backup_destination(
+    db: &Database,
+    destination_path: &str,
+) -> Result<std::path::PathBuf, AppError> {
+    let destination = std::path::Path::new(destination_path);
+
+    let parent = match destination.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p,
+        _ => std::path::Path::new("."),
+    };
+    let parent_metadata = std::fs::metadata(parent)
+        .map_err(|_| format!("Backup directory does not exist: {}", parent.display()))?;
+    if !parent_metadata.is_dir() {
+        return Err(format!("Not a directory: {}", parent.display()).into());
+    }
+    if parent_metadata.permissions().readonly() {
+        return Err(format!("Backup directory is not writable: {}", parent.display()).into());
+    }
+
+    // Compare resolved paths so "./x/../kolam_ikan.db" can't slip past
+    let live = std::fs::canonicalize(&db.path)?;
+    let target = std::fs::canonicalize(parent)?.join(
+        destination
+            .file_name()
+            .ok_or("Backup path has no file name")?,
+    );
+    if target == live {
+        return Err("Refusing to overwrite the live database".into());
+    }
+
+    Ok(target)
+}
+
+/// Tables a Kolam Ikan database must have before it can be restored from
+const REQUIRED_TABLES: &[&str] = &[
+    "streams",
+    "profiles",
+    "entries",
+    "entry_versions",
+    "spotlights",
+    "pending_blocks",
+];
+
+/// Replaces the live database with the contents of `source_path`.
+/// The current data is first copied next to the live file as
+/// `kolam_ikan.pre-restore-<timestamp>.db` so a bad restore can be undone.
+#[tauri::command]
+pub fn restore_database(db: State<Database>, source_path: String) -> Result<(), AppError> {
+    let source = rusqlite::Connection::open_with_flags(
+        &source_path,
+        rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY,
+    )
+    .map_err(|e| format!("Cannot open {}: {}", source_path, e))?;
+
+    for table in REQUIRED_TABLES {
+        let exists = source
+            .prepare("SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1")
+            .and_then(|mut stmt| stmt.exists(params![table]))
+            .map_err(|_| format!("{} is not a SQLite database", source_path))?;
+        if !exists {
+            return Err(format!("Not a Kolam Ikan database: missing table '{}'", table).into());
+        }
+    }
+
+    // Databases from before schema_version existed are treated as version 0 and migrated
+    let source_version: i64 = source
+        .query_row(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or(0);
+    if source_version > database::latest_schema_version() {
+        return Err(format!(
+            "Backup uses schema version {}, but this app only supports up to {}",
+            source_version,
+            database::latest_schema_version()
+        )
+        .into());
+    }
+    drop(source);
+
+    let mut conn = db.pool.get()?;
+
+    let snapshot = db.path.with_file_name(format!(
+        "kolam_ikan.pre-restore-{}.db",
+        chrono::Utc::now().timestamp_millis()
+    ));
+    conn.backup(rusqlite::DatabaseName::Main, &snapshot, None)
+        .map_err(|e| {
+            AppError::with_details(
+                "BACKUP_FAILED",
+                "Failed to save pre-restore backup",
+                &e.to_string(),
+            )
+        })?;
+
+    conn.restore(
+        rusqlite::DatabaseName::Main,
+        &source_path,
+        None::<fn(rusqlite::backup::Progress)>,
+    )?;
+
+    // An older backup may predate recent migrations or the FTS triggers
+    Database::initialize_schema(&conn)?;
+
+    Ok(())
+}
+
+/// Ids of entries whose stream is gone
+const MISSING_STREAM_SQL: &str = "SELECT e.id FROM entries e
+     WHERE NOT EXISTS (SELECT 1 FROM streams s WHERE s.id = e.stream_id)
+     ORDER BY e.created_at, e.sequence_id, e.id";
+
+/// Ids of entries whose profile is gone
+const MISSING_PROFILE_SQL: &str = "SELECT e.id FROM entries e
+     WHERE e.profile_id IS NOT NULL
+       AND NOT EXISTS (SELECT 1 FROM profiles p WHERE p.id = e.profile_id)
+     ORDER BY e.created_at, e.sequence_id, e.id";
+
+fn query_ids(conn: &rusqlite::Connection, sql: &str) -> rusqlite::Result<Vec<String>> {
+    conn.prepare(sql)?
+        .query_map([], |row| row.get(0))?
+        .collect()
+}
+
+/// Finds entries left pointing at deleted streams or profiles, which could happen
+/// while foreign keys were not enforced. Soft-deleted entries are included.
+#[tauri::command]
+pub fn find_orphaned_entries(db: State<Database>) -> Result<OrphanReport, AppError> {
+    let conn = db.pool.get()?;
+
+    Ok(OrphanReport {
+        missing_stream: query_ids(&conn, MISSING_STREAM_SQL)?,
+        missing_profile: query_ids(&conn, MISSING_PROFILE_SQL)?,
+    })
+}
+
+/// Repairs what `find_orphaned_entries` reports. With `"reassign"`, entries without a
+/// stream move to a new "Recovered" stream per user; with `"delete"`, they are removed
+/// along with their versions and spotlights. Either way, dangling profile references
+/// are cleared, since those entries still belong to a live stream.
+/// Returns the number of references repaired.
+#[tauri::command]
+pub fn repair_orphaned_entries(db: State<Database>, strategy: String) -> Result<usize, AppError> {
+    if strategy != "reassign" && strategy != "delete" {
+        return Err(format!(
+            "Unknown repair strategy '{}': expected reassign or delete",
+            strategy
+        )
+        .into());
+    }
+
+    let mut conn = db.pool.get()?;
+    let tx = conn.transaction()?;
+    let now = chrono::Utc::now().timestamp_millis();
+
+    let missing_stream = query_ids(&tx, MISSING_STREAM_SQL)?;
+    let mut changed = missing_stream.len();
+
+    if strategy == "delete" {
+        for entry_id in &missing_stream {
+            tx.execute("DELETE FROM entries WHERE id = ?1", params![entry_id])?;
+        }
+    } else {
+        // Streams are per user, so each user's orphans get their own Recovered stream
+        let mut recovered: HashMap<String, (String, i32)> = HashMap::new();
+        for entry_id in &missing_stream {
+            let user_id: String = tx.query_row(
+                "SELECT user_id FROM entries WHERE id = ?1",
+                params![entry_id],
+                |row| row.get(0),
+            )?;
+
+            let (stream_id, sequence_id) = match recovered.entry(user_id) {
+                std::collections::hash_map::Entry::Occupied(slot) => slot.into_mut(),
+                std::collections::hash_map::Entry::Vacant(slot) => {
+                    let stream_id = uuid::Uuid::new_v4().to_string();
+                    tx.execute(
+                        "INSERT INTO streams (id, user_id, title, description, created_at, updated_at)
+                         VALUES (?1, ?2, 'Recovered', ?3, ?4, ?4)",
+                        params![stream_id, slot.key(), "Entries whose stream was missing", now],
+                    )?;
+                    slot.insert((stream_id, 0))
+                }
+            };
+            *sequence_id += 1;
+
+            // Parent links pointed into the lost stream, so they can't be trusted
+            tx.execute(
+                "UPDATE entries SET stream_id = ?1, sequence_id = ?2, parent_context_ids = NULL, is_staged = 0
+                 WHERE id = ?3",
+                params![*stream_id, *sequence_id, entry_id],
+            )?;
+        }
+    }
+
+    changed += tx.execute(
+        "UPDATE entries SET profile_id = NULL
+             WHERE profile_id IS NOT NULL
+               AND NOT EXISTS (SELECT 1 FROM profiles p WHERE p.id = entries.profile_id)",
+        [],
+    )?;
+
+    tx.commit()?;
+    Ok(changed)
+}
+
+// ============================================================
+// TESTS
+// ============================================================
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tauri::test::{mock_app, MockRuntime};
+    use tauri::{App, Manager};
+
+    /// A mock app managing a fresh in-memory database
+    fn test_app() -> App<MockRuntime> {
+        let app = mock_app();
+        app.manage(Database::open_in_memory().expect("failed to open in-memory database"));
+        app
+    }
+
+    /// A one-paragraph ProseMirror document
+    fn doc(text: &str) -> serde_json::Value {
+        serde_json::json!({
+            "type": "doc",
+            "content": [{ "type": "paragraph", "content": [{ "type": "text", "text": text }] }]
+        })
+    }
+
+    fn entry_input(stream_id: &str, text: &str) -> CreateEntryInput {
+        CreateEntryInput {
+            user_id: "default-user".to_string(),
+            stream_id: stream_id.to_string(),
+            profile_id: None,
+            role: "user".to_string(),
+            content: doc(text),
+            ai_metadata: None,
+            parent_context_ids: None,
+            insert_after_id: None,
+            insert_before_id: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn new_stream(db: &State<Database>, title: &str) -> Stream {
+        create_stream(
+            db.clone(),
+            CreateStreamInput {
+                user_id: "default-user".to_string(),
+                title: title.to_string(),
+                description: None,
+                tags: None,
+                color: None,
+            },
+        )
+        .unwrap()
+    }
+
+    fn new_entry(db: &State<Database>, stream_id: &str, text: &str) -> Entry {
+        create_entry(db.clone(), entry_input(stream_id, text)).unwrap()
+    }
+
+    fn new_profile(db: &State<Database>, name: &str) -> Profile {
+        create_profile(
+            db.clone(),
+            CreateProfileInput {
+                user_id: "default-user".to_string(),
+                name: name.to_string(),
+                role: "self".to_string(),
+                color: None,
+                initials: None,
+                bio: None,
+            },
+        )
+        .unwrap()
+    }
+
+    /// Makes every matching statement abort, to force a failure partway through a command
+    fn fail_on(db: &State<Database>, trigger: &str) {
+        db.pool
+            .get()
+            .unwrap()
+            .execute_batch(&format!(
+                "CREATE TRIGGER forced_failure {} BEGIN SELECT RAISE(ABORT, 'forced failure'); END;",
+                trigger
+            ))
+            .unwrap();
+    }
+
+    fn count(db: &State<Database>, sql: &str, id: &str) -> i64 {
+        db.pool
+            .get()
+            .unwrap()
+            .query_row(sql, params![id], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn entry_ids(db: &State<Database>, stream_id: &str) -> Vec<String> {
+        get_stream_details(db.clone(), stream_id.to_string(), None, None)
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|e| e.id)
+            .collect()
+    }
+
+    fn ai_metadata(provider: &str, model: &str) -> AiMetadata {
+        AiMetadata {
+            model: model.to_string(),
+            provider: provider.to_string(),
+            directive: "CRITIQUE".to_string(),
+            bridge_key: "abc123".to_string(),
+            summary: None,
+        }
+    }
+
+    fn listed_ids(streams: Vec<StreamMetadata>) -> Vec<String> {
+        streams.into_iter().map(|s| s.id).collect()
+    }
+
+    /// Commits `count` versions of an entry, each with different content
+    fn commit_versions(db: &State<Database>, entry_id: &str, count: usize) {
+        for i in 1..=count {
+            update_entry_content(
+                db.clone(),
+                entry_id.to_string(),
+                doc(&format!("draft {}", i)),
+            )
+            .unwrap();
+            commit_entry_version(db.clone(), entry_id.to_string(), None).unwrap();
+        }
+    }
+
+    fn version_numbers(db: &State<Database>, entry_id: &str) -> Vec<i32> {
+        let mut numbers: Vec<_> = get_entry_versions(db.clone(), entry_id.to_string())
+            .unwrap()
+            .into_iter()
+            .map(|v| v.version_number)
+            .collect();
+        numbers.sort();
+        numbers
+    }
+
+    fn tagged_stream(db: &State<Database>, title: &str, tags: &[&str]) -> Stream {
+        create_stream(
+            db.clone(),
+            CreateStreamInput {
+                user_id: "default-user".to_string(),
+                title: title.to_string(),
+                description: None,
+                tags: Some(tags.iter().map(|tag| tag.to_string()).collect()),
+                color: None,
+            },
+        )
+        .unwrap()
+    }
+
+    /// Like `test_app`, but backed by a database file in a fresh temporary directory,
+    /// for commands that work on the file itself
+    fn file_test_app() -> (App<MockRuntime>, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("kolam-ikan-test-{}", uuid::Uuid::new_v4()));
+        let app = mock_app();
+        app.manage(Database::new(dir.clone(), None).expect("failed to open database"));
+        (app, dir)
+    }
+
+    #[test]
+    fn spotlights_round_trip_in_offset_order() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "the quick brown fox");
+
+        let later = create_spotlight(
+            db.clone(),
+            entry.id.clone(),
+            "the quick brown fox".to_string(),
+            "fox".to_string(),
+            16,
+            19,
+        )
+        .unwrap();
+        let earlier = create_spotlight(
+            db.clone(),
+            entry.id.clone(),
+            "the quick brown fox".to_string(),
+            "quick".to_string(),
+            4,
+            9,
+        )
+        .unwrap();
+
+        let ids: Vec<_> = get_spotlights_for_entry(db.clone(), entry.id.clone())
+            .unwrap()
+            .into_iter()
+            .map(|s| s.id)
+            .collect();
+        assert_eq!(ids, vec![earlier.id.clone(), later.id]);
+
+        delete_spotlight(db.clone(), earlier.id).unwrap();
+        assert_eq!(
+            get_spotlights_for_entry(db.clone(), entry.id)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn spotlight_offsets_are_validated() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "text");
+
+        for (start, end) in [(-1, 2), (0, -2), (3, 1)] {
+            let err = create_spotlight(
+                db.clone(),
+                entry.id.clone(),
+                "text".to_string(),
+                "ex".to_string(),
+                start,
+                end,
+            )
+            .unwrap_err();
+            assert_eq!(err.code, "INVALID_INPUT");
+        }
+
+        // An empty selection is allowed
+        create_spotlight(
+            db.clone(),
+            entry.id,
+            "text".to_string(),
+            String::new(),
+            2,
+            2,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn spotlights_are_removed_with_their_entry() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "text");
+        create_spotlight(
+            db.clone(),
+            entry.id.clone(),
+            "text".to_string(),
+            "ex".to_string(),
+            1,
+            3,
+        )
+        .unwrap();
+
+        delete_entry(db.clone(), entry.id.clone()).unwrap();
+        purge_deleted_entries(db.clone(), stream.id).unwrap();
+
+        let remaining: i64 = db
+            .pool
+            .get()
+            .unwrap()
+            .query_row(
+                "SELECT COUNT(*) FROM spotlights WHERE entry_id = ?1",
+                params![entry.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn create_entry_rolls_back_when_stream_bump_fails() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        fail_on(&db, "BEFORE UPDATE ON streams");
+
+        assert!(create_entry(db.clone(), entry_input(&stream.id, "lost")).is_err());
+
+        let entries = count(
+            &db,
+            "SELECT COUNT(*) FROM entries WHERE stream_id = ?1",
+            &stream.id,
+        );
+        assert_eq!(entries, 0);
+    }
+
+    #[test]
+    fn commit_rolls_back_when_head_bump_fails() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "draft");
+        fail_on(&db, "BEFORE UPDATE OF version_head ON entries");
+
+        assert!(commit_entry_version(db.clone(), entry.id.clone(), None).is_err());
+
+        let versions = count(
+            &db,
+            "SELECT COUNT(*) FROM entry_versions WHERE entry_id = ?1",
+            &entry.id,
+        );
+        assert_eq!(versions, 0);
+    }
+
+    #[test]
+    fn default_switch_rolls_back_when_setting_new_default_fails() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let first = new_profile(&db, "First");
+        let second = new_profile(&db, "Second");
+        set_default_profile(db.clone(), first.id.clone()).unwrap();
+        fail_on(
+            &db,
+            "BEFORE UPDATE OF is_default ON profiles WHEN NEW.is_default = 1",
+        );
+
+        assert!(set_default_profile(db.clone(), second.id).is_err());
+
+        let default = get_default_profile(db.clone()).unwrap().unwrap();
+        assert_eq!(default.id, first.id);
+    }
+
+    #[test]
+    fn deleting_a_stream_cascades_to_its_entries() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "first");
+        new_entry(&db, &stream.id, "second");
+        commit_entry_version(db.clone(), entry.id.clone(), None).unwrap();
+
+        delete_stream(db.clone(), stream.id.clone()).unwrap();
+
+        let entries = count(
+            &db,
+            "SELECT COUNT(*) FROM entries WHERE stream_id = ?1",
+            &stream.id,
+        );
+        let versions = count(
+            &db,
+            "SELECT COUNT(*) FROM entry_versions WHERE entry_id = ?1",
+            &entry.id,
+        );
+        assert_eq!((entries, versions), (0, 0));
+    }
+
+    #[test]
+    fn reorder_entries_reverses_a_stream() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let ids: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|text| new_entry(&db, &stream.id, text).id)
+            .collect();
+        let reversed: Vec<_> = ids.iter().rev().cloned().collect();
+
+        reorder_entries(db.clone(), stream.id.clone(), reversed.clone()).unwrap();
+
+        assert_eq!(entry_ids(&db, &stream.id), reversed);
+    }
+
+    #[test]
+    fn reorder_entries_rejects_missing_or_extra_ids() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let a = new_entry(&db, &stream.id, "a").id;
+        let b = new_entry(&db, &stream.id, "b").id;
+
+        assert!(reorder_entries(db.clone(), stream.id.clone(), vec![a.clone()]).is_err());
+        assert!(reorder_entries(
+            db.clone(),
+            stream.id.clone(),
+            vec![a.clone(), b.clone(), "stranger".to_string()]
+        )
+        .is_err());
+        assert_eq!(entry_ids(&db, &stream.id), vec![a, b]);
+    }
+
+    #[test]
+    fn move_entry_appends_to_target_and_keeps_versions() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let source = new_stream(&db, "Source");
+        let target = new_stream(&db, "Target");
+        let existing = new_entry(&db, &target.id, "already here");
+        let moved = new_entry(&db, &source.id, "travelling");
+        commit_entry_version(db.clone(), moved.id.clone(), Some("v1".to_string())).unwrap();
+
+        move_entry(db.clone(), moved.id.clone(), target.id.clone()).unwrap();
+
+        assert!(entry_ids(&db, &source.id).is_empty());
+        assert_eq!(
+            entry_ids(&db, &target.id),
+            vec![existing.id, moved.id.clone()]
+        );
+        let versions = get_entry_versions(db.clone(), moved.id).unwrap();
+        assert_eq!(versions.len(), 1);
+        assert_eq!(versions[0].commit_message.as_deref(), Some("v1"));
+    }
+
+    #[test]
+    fn move_entry_rejects_missing_target() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let source = new_stream(&db, "Source");
+        let entry = new_entry(&db, &source.id, "staying");
+
+        assert!(move_entry(db.clone(), entry.id.clone(), "nowhere".to_string()).is_err());
+        assert_eq!(entry_ids(&db, &source.id), vec![entry.id]);
+    }
+
+    #[test]
+    fn export_then_import_round_trips_with_fresh_ids() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let original = new_stream(&db, "Original");
+        let parent = new_entry(&db, &original.id, "question");
+        let child = create_entry(
+            db.clone(),
+            CreateEntryInput {
+                parent_context_ids: Some(vec![parent.id.clone(), format!("{}:1", parent.id)]),
+                ..entry_input(&original.id, "answer")
+            },
+        )
+        .unwrap();
+        commit_entry_version(db.clone(), child.id.clone(), None).unwrap();
+
+        let json = export_stream(db.clone(), original.id.clone()).unwrap();
+        let imported = import_stream(db.clone(), json).unwrap();
+
+        assert_ne!(imported.id, original.id);
+        assert_eq!(imported.title, original.title);
+
+        let entries = get_stream_details(db.clone(), imported.id, None, None)
+            .unwrap()
+            .entries;
+        let contents: Vec<_> = entries.iter().map(|e| e.content.clone()).collect();
+        assert_eq!(contents, vec![doc("question"), doc("answer")]);
+        assert!(entries
+            .iter()
+            .all(|e| e.id != parent.id && e.id != child.id));
+        assert_eq!(
+            entries[1].parent_context_ids,
+            Some(vec![entries[0].id.clone(), format!("{}:1", entries[0].id)])
+        );
+        assert_eq!(
+            get_entry_versions(db.clone(), entries[1].id.clone())
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn import_rejects_newer_schema_versions() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        new_entry(&db, &stream.id, "text");
+
+        let mut exported: serde_json::Value =
+            serde_json::from_str(&export_stream(db.clone(), stream.id).unwrap()).unwrap();
+        exported["schemaVersion"] = serde_json::json!(EXPORT_SCHEMA_VERSION + 1);
+
+        assert!(import_stream(db.clone(), exported.to_string()).is_err());
+    }
+
+    #[test]
+    fn markdown_export_attributes_ai_entries() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        new_entry(&db, &stream.id, "question");
+        create_entry(
+            db.clone(),
+            CreateEntryInput {
+                role: "ai".to_string(),
+                ai_metadata: Some(ai_metadata("openai", "gpt-4o")),
+                ..entry_input(&stream.id, "answer")
+            },
+        )
+        .unwrap();
+
+        let markdown = export_stream_markdown(db.clone(), stream.id).unwrap();
+
+        assert_eq!(
+            markdown,
+            "# Notes\n\n---\n\nquestion\n\n---\n\nanswer\n\n> _from gpt-4o_\n"
+        );
+    }
+
+    #[test]
+    fn restoring_after_a_reorder_does_not_duplicate_sequence_ids() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let a = new_entry(&db, &stream.id, "a").id;
+        let b = new_entry(&db, &stream.id, "b").id;
+        let c = new_entry(&db, &stream.id, "c").id;
+
+        delete_entry(db.clone(), a.clone()).unwrap();
+        reorder_entries(db.clone(), stream.id.clone(), vec![c.clone(), b.clone()]).unwrap();
+        restore_entry(db.clone(), a.clone()).unwrap();
+
+        let entries = get_stream_details(db.clone(), stream.id.clone(), None, None)
+            .unwrap()
+            .entries;
+        let order: Vec<_> = entries
+            .iter()
+            .map(|e| (e.id.clone(), e.sequence_id))
+            .collect();
+        assert_eq!(order, vec![(c, 1), (b, 2), (a, 3)]);
+    }
+
+    #[test]
+    fn restoring_keeps_a_free_position() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let a = new_entry(&db, &stream.id, "a").id;
+        let b = new_entry(&db, &stream.id, "b").id;
+
+        delete_entry(db.clone(), a.clone()).unwrap();
+        restore_entry(db.clone(), a.clone()).unwrap();
+
+        assert_eq!(entry_ids(&db, &stream.id), vec![a, b]);
+    }
+
+    #[test]
+    fn archived_streams_are_hidden_unless_requested() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let kept = new_stream(&db, "Kept");
+        let archived = new_stream(&db, "Archived");
+
+        archive_stream(db.clone(), archived.id.clone()).unwrap();
+
+        let user = "default-user".to_string();
+        let default = listed_ids(get_all_streams(db.clone(), user.clone(), None).unwrap());
+        assert_eq!(default, vec![kept.id.clone()]);
+        let all = listed_ids(get_all_streams(db.clone(), user.clone(), Some(true)).unwrap());
+        assert!(all.contains(&archived.id) && all.contains(&kept.id));
+
+        // Still openable directly
+        let details = get_stream_details(db.clone(), archived.id.clone(), None, None).unwrap();
+        assert!(details.stream.archived);
+
+        unarchive_stream(db.clone(), archived.id.clone()).unwrap();
+        let default = listed_ids(get_all_streams(db.clone(), user, None).unwrap());
+        assert!(default.contains(&archived.id));
+    }
+
+    #[test]
+    fn diff_entry_versions_compares_snapshots() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "first");
+        commit_entry_version(db.clone(), entry.id.clone(), None).unwrap();
+        update_entry_content(db.clone(), entry.id.clone(), doc("second")).unwrap();
+        commit_entry_version(db.clone(), entry.id.clone(), None).unwrap();
+
+        let diff = diff_entry_versions(db.clone(), entry.id.clone(), 1, 2).unwrap();
+        let kinds: Vec<_> = diff
+            .iter()
+            .map(|op| (op.kind.as_str(), op.text.as_str()))
+            .collect();
+        assert_eq!(kinds, vec![("remove", "first"), ("add", "second")]);
+
+        let err = diff_entry_versions(db.clone(), entry.id, 1, 3).unwrap_err();
+        assert_eq!(err.code, "NOT_FOUND");
+    }
+
+    #[test]
+    fn pruning_keeps_the_newest_versions() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "draft");
+        commit_versions(&db, &entry.id, 10);
+
+        let removed = prune_entry_versions(db.clone(), entry.id.clone(), 3).unwrap();
+
+        assert_eq!(removed, 7);
+        assert_eq!(version_numbers(&db, &entry.id), vec![8, 9, 10]);
+    }
+
+    #[test]
+    fn pruning_never_removes_the_head_version() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "draft");
+        let other = new_entry(&db, &stream.id, "other");
+        commit_versions(&db, &entry.id, 10);
+        commit_versions(&db, &other.id, 2);
+        db.pool
+            .get()
+            .unwrap()
+            .execute(
+                "UPDATE entries SET version_head = 2 WHERE id = ?1",
+                params![entry.id],
+            )
+            .unwrap();
+
+        let removed = prune_all_versions(db.clone(), 3).unwrap();
+
+        assert_eq!(removed, 6);
+        assert_eq!(version_numbers(&db, &entry.id), vec![2, 8, 9, 10]);
+        assert_eq!(version_numbers(&db, &other.id), vec![1, 2]);
+        assert!(prune_entry_versions(db.clone(), entry.id, -1).is_err());
+    }
+
+    #[test]
+    fn listing_commands_return_identical_entry_shapes() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let profile = new_profile(&db, "Critic");
+        let parent = new_entry(&db, &stream.id, "context");
+        let entry = create_entry(
+            db.clone(),
+            CreateEntryInput {
+                role: "ai".to_string(),
+                profile_id: Some(profile.id),
+                ai_metadata: Some(ai_metadata("openai", "gpt-4o")),
+                parent_context_ids: Some(vec![parent.id]),
+                ..entry_input(&stream.id, "distinctive reply")
+            },
+        )
+        .unwrap();
+        toggle_entry_staging(db.clone(), entry.id.clone(), true).unwrap();
+        commit_entry_version(db.clone(), entry.id.clone(), None).unwrap();
+
+        let mut from_details = get_stream_details(db.clone(), stream.id.clone(), None, None)
+            .unwrap()
+            .entries
+            .into_iter()
+            .find(|e| e.id == entry.id)
+            .unwrap();
+        // Only the stream view joins the author's profile
+        assert_eq!(from_details.profile.take().unwrap().name, "Critic");
+        let from_staged = get_staged_entries(db.clone(), stream.id.clone())
+            .unwrap()
+            .remove(0);
+        let from_search = search_entries(db.clone(), "distinctive".to_string(), None, None, None)
+            .unwrap()
+            .results
+            .remove(0)
+            .entry;
+
+        let details = serde_json::to_value(&from_details).unwrap();
+        assert_eq!(details["isStaged"], true);
+        assert_eq!(details["versionHead"], 1);
+        assert_eq!(serde_json::to_value(&from_staged).unwrap(), details);
+        assert_eq!(serde_json::to_value(&from_search).unwrap(), details);
+    }
+
+    #[test]
+    fn streams_by_tag_match_case_insensitively() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let lower = tagged_stream(&db, "Lower", &["work"]);
+        let upper = tagged_stream(&db, "Upper", &["Work"]);
+        tagged_stream(&db, "Home", &["home"]);
+
+        let mut found = listed_ids(get_streams_by_tag(db.clone(), "work".to_string()).unwrap());
+        found.sort();
+        let mut expected = vec![lower.id, upper.id];
+        expected.sort();
+
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn stream_stats_skip_ai_and_staged_entries_by_default() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        new_entry(&db, &stream.id, "one two three");
+        let staged = new_entry(&db, &stream.id, "staged words");
+        toggle_entry_staging(db.clone(), staged.id, true).unwrap();
+        create_entry(
+            db.clone(),
+            CreateEntryInput {
+                role: "ai".to_string(),
+                ai_metadata: Some(ai_metadata("openai", "gpt-4o")),
+                ..entry_input(&stream.id, "four five")
+            },
+        )
+        .unwrap();
+
+        let stats = get_stream_stats(db.clone(), stream.id.clone(), None).unwrap();
+        assert_eq!((stats.entry_count, stats.word_count), (3, 3));
+        assert_eq!(stats.character_count, "one two three".len() as i64);
+
+        let with_ai = get_stream_stats(db.clone(), stream.id, Some(true)).unwrap();
+        assert_eq!(with_ai.word_count, 5);
+    }
+
+    #[test]
+    fn committing_unchanged_content_reuses_the_head_version() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "draft");
+
+        let first = commit_entry_version(db.clone(), entry.id.clone(), None).unwrap();
+        let again = commit_entry_version(db.clone(), entry.id.clone(), None).unwrap();
+        assert_eq!(again.id, first.id);
+        assert_eq!(version_numbers(&db, &entry.id), vec![1]);
+
+        update_entry_content(db.clone(), entry.id.clone(), doc("revised")).unwrap();
+        let changed = commit_entry_version(db.clone(), entry.id.clone(), None).unwrap();
+        assert_eq!(changed.version_number, 2);
+    }
+
+    #[test]
+    fn repeated_stream_reads_stay_consistent() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let mut expected = Vec::new();
+
+        for round in 0..200 {
+            if round % 20 == 0 {
+                expected.push(new_entry(&db, &stream.id, &format!("entry {}", round)).id);
+            }
+            assert_eq!(entry_ids(&db, &stream.id), expected);
+        }
+
+        let page = get_stream_details(db.clone(), stream.id, Some(3), Some(2)).unwrap();
+        let page_ids: Vec<_> = page.entries.into_iter().map(|e| e.id).collect();
+        assert_eq!(page_ids, expected[2..5]);
+    }
+
+    #[test]
+    fn optimize_shrinks_the_file_after_mass_deletes() {
+        let (app, dir) = file_test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Bulk");
+        let filler = "x".repeat(2000);
+        let ids: Vec<_> = (0..300)
+            .map(|i| new_entry(&db, &stream.id, &format!("{} {}", i, filler)).id)
+            .collect();
+        delete_entries(db.clone(), ids).unwrap();
+        purge_deleted_entries(db.clone(), stream.id).unwrap();
+
+        let report = optimize_database(db.clone()).unwrap();
+
+        assert!(
+            report.bytes_after < report.bytes_before,
+            "{} -> {}",
+            report.bytes_before,
+            report.bytes_after
+        );
+        assert!(get_database_info(db.clone()).unwrap().integrity_ok);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn database_is_locked_until_one_is_managed() {
+        let app = mock_app();
+        assert!(is_database_locked(app.handle().clone()));
+
+        app.manage(Database::open_in_memory().unwrap());
+
+        assert!(!is_database_locked(app.handle().clone()));
+        let err = set_database_key(app.handle().clone(), String::new()).unwrap_err();
+        assert_eq!(err.code, "INVALID_INPUT");
+    }
+
+    #[test]
+    fn staging_and_pinning_are_independent() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let both = new_entry(&db, &stream.id, "staged and pinned");
+        let pinned = new_entry(&db, &stream.id, "pinned only");
+
+        toggle_entry_staging(db.clone(), both.id.clone(), true).unwrap();
+        toggle_entry_pin(db.clone(), both.id.clone(), true).unwrap();
+        toggle_entry_pin(db.clone(), pinned.id.clone(), true).unwrap();
+
+        let staged = get_staged_entries(db.clone(), stream.id.clone()).unwrap();
+        assert_eq!(staged.len(), 1);
+        assert!(staged[0].is_pinned);
+
+        // Unpinning leaves the entry staged
+        toggle_entry_pin(db.clone(), both.id.clone(), false).unwrap();
+        assert_eq!(
+            get_staged_entries(db.clone(), stream.id.clone())
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // Clearing staging leaves pins alone
+        toggle_entry_pin(db.clone(), both.id.clone(), true).unwrap();
+        clear_all_staging(db.clone(), stream.id.clone()).unwrap();
+        assert!(get_staged_entries(db.clone(), stream.id.clone())
+            .unwrap()
+            .is_empty());
+        let mut pinned_ids: Vec<_> = get_pinned_entries(db.clone(), stream.id.clone())
+            .unwrap()
+            .into_iter()
+            .map(|e| e.id)
+            .collect();
+        pinned_ids.sort();
+        let mut expected = vec![both.id, pinned.id];
+        expected.sort();
+        assert_eq!(pinned_ids, expected);
+    }
+
+    #[test]
+    fn merge_appends_source_entries_in_order_and_unions_tags() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let target = tagged_stream(&db, "Target", &["work", "ideas"]);
+        let source = tagged_stream(&db, "Source", &["ideas", "draft"]);
+        let mut expected: Vec<_> = ["t1", "t2"]
+            .iter()
+            .map(|text| new_entry(&db, &target.id, text).id)
+            .collect();
+        expected.extend(
+            ["s1", "s2", "s3"]
+                .iter()
+                .map(|text| new_entry(&db, &source.id, text).id),
+        );
+
+        merge_streams(db.clone(), source.id.clone(), target.id.clone()).unwrap();
+
+        let merged = get_stream_details(db.clone(), target.id.clone(), None, None).unwrap();
+        assert_eq!(merged.stream.tags, ["work", "ideas", "draft"]);
+        let ids: Vec<_> = merged.entries.iter().map(|e| e.id.clone()).collect();
+        assert_eq!(ids, expected);
+        let sequence_ids: Vec<_> = merged.entries.iter().map(|e| e.sequence_id).collect();
+        assert_eq!(sequence_ids, [1, 2, 3, 4, 5]);
+        assert_eq!(
+            count(
+                &db,
+                "SELECT COUNT(*) FROM streams WHERE id = ?1",
+                &source.id
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn malformed_content_is_rejected_on_create_and_update() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "kept");
+        let bad = serde_json::json!({ "type": "paragraph", "content": [] });
+
+        let mut input = entry_input(&stream.id, "");
+        input.content = bad.clone();
+        assert_eq!(
+            create_entry(db.clone(), input).unwrap_err().code,
+            "INVALID_INPUT"
+        );
+        let err = update_entry_content(db.clone(), entry.id.clone(), bad).unwrap_err();
+        assert_eq!(err.code, "INVALID_INPUT");
+
+        assert_eq!(entry_ids(&db, &stream.id).len(), 1);
+        let stored = get_entry(db.clone(), entry.id).unwrap().unwrap();
+        assert_eq!(stored.content, doc("kept"));
+    }
+
+    #[test]
+    fn context_graph_follows_a_chain_and_skips_outside_parents() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let other = new_stream(&db, "Elsewhere");
+        let outside = new_entry(&db, &other.id, "other stream");
+        let deleted = new_entry(&db, &stream.id, "deleted");
+        let child_of = |text: &str, parents: Vec<String>| {
+            let mut input = entry_input(&stream.id, text);
+            input.parent_context_ids = Some(parents);
+            create_entry(db.clone(), input).unwrap().id
+        };
+        let a = new_entry(&db, &stream.id, "a").id;
+        let b = child_of("b", vec![a.clone(), outside.id.clone()]);
+        let c = child_of("c", vec![b.clone(), deleted.id.clone()]);
+        delete_entry(db.clone(), deleted.id).unwrap();
+
+        let graph = get_context_graph(db.clone(), stream.id).unwrap();
+
+        assert_eq!(graph.nodes, [a.clone(), b.clone(), c.clone()]);
+        assert_eq!(graph.edges, [(a, b.clone()), (b, c)]);
+    }
+
+    #[test]
+    fn meta_values_round_trip_and_overwrite() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let key = "ui.sidebar".to_string();
+
+        assert_eq!(get_meta(db.clone(), key.clone()).unwrap(), None);
+
+        set_meta(db.clone(), key.clone(), r#"{"width":240}"#.to_string()).unwrap();
+        assert_eq!(
+            get_meta(db.clone(), key.clone()).unwrap().as_deref(),
+            Some(r#"{"width":240}"#)
+        );
+
+        set_meta(db.clone(), key.clone(), r#"{"width":320}"#.to_string()).unwrap();
+        assert_eq!(
+            get_meta(db.clone(), key.clone()).unwrap().as_deref(),
+            Some(r#"{"width":320}"#)
+        );
+        assert_eq!(
+            count(&db, "SELECT COUNT(*) FROM app_meta WHERE key = ?1", &key),
+            1
+        );
+    }
+
+    #[test]
+    fn last_opened_stream_is_only_set_explicitly_and_forgotten_once_deleted() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let opened = new_stream(&db, "Opened");
+        let peeked = new_stream(&db, "Peeked");
+
+        set_last_opened_stream(db.clone(), opened.id.clone()).unwrap();
+        // Loading a stream (for a preview or refresh) doesn't count as opening it
+        get_stream_details(db.clone(), peeked.id.clone(), None, None).unwrap();
+
+        let last = get_last_opened_stream(db.clone()).unwrap().unwrap();
+        assert_eq!(last.stream.id, opened.id);
+
+        let err = set_last_opened_stream(db.clone(), "missing".to_string()).unwrap_err();
+        assert_eq!(err.code, "NOT_FOUND");
+
+        delete_stream(db.clone(), opened.id).unwrap();
+        assert!(get_last_opened_stream(db.clone()).unwrap().is_none());
+        assert_eq!(
+            get_meta(db.clone(), LAST_OPENED_STREAM_KEY.to_string()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn empty_stream_color_clears_it_like_update_stream() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let color = |db: &State<Database>| {
+            get_stream_details(db.clone(), stream.id.clone(), Some(0), None)
+                .unwrap()
+                .stream
+                .color
+        };
+
+        update_stream_color(db.clone(), stream.id.clone(), Some("#A1b2C3".into())).unwrap();
+        assert_eq!(color(&db).as_deref(), Some("#A1b2C3"));
+
+        update_stream_color(db.clone(), stream.id.clone(), Some(String::new())).unwrap();
+        assert_eq!(color(&db), None);
+
+        update_stream_color(db.clone(), stream.id.clone(), Some("#abc".into())).unwrap();
+        update_stream_color(db.clone(), stream.id.clone(), None).unwrap();
+        assert_eq!(color(&db), None);
+
+        let err = update_stream_color(db.clone(), stream.id.clone(), Some("red".into()));
+        assert_eq!(err.unwrap_err().code, "INVALID_INPUT");
+    }
+
+    #[test]
+    fn fuzzy_search_finds_short_words_with_typos() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let the = new_entry(&db, &stream.id, "read the manual");
+        let organise = new_entry(&db, &stream.id, "organise the shelf");
+        new_entry(&db, &stream.id, "unrelated words only");
+
+        let found = |query: &str, max_distance: usize| {
+            let mut ids: Vec<_> = search_entries_fuzzy(db.clone(), query.to_string(), max_distance)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.entry.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+        let mut both = vec![the.id.clone(), organise.id.clone()];
+        both.sort();
+
+        assert_eq!(found("teh", 2), both);
+        assert_eq!(found("organize", 1), [organise.id]);
+        assert_eq!(found("mnual", 1), [the.id]);
+    }
+
+    /// Deletes rows behind the foreign keys' back, the way older builds could
+    fn orphan_rows(db: &State<Database>, sql: &str) {
+        db.pool
+            .get()
+            .unwrap()
+            .execute_batch(&format!(
+                "PRAGMA foreign_keys = OFF; {}; PRAGMA foreign_keys = ON;",
+                sql
+            ))
+            .unwrap();
+    }
+
+    /// A stream with two entries whose stream row is gone, and a live entry whose
+    /// profile is gone. Returns (lost entry ids, live entry id).
+    fn orphaned_fixture(db: &State<Database>) -> (Vec<String>, String) {
+        let lost = new_stream(db, "Lost");
+        let live = new_stream(db, "Live");
+        let profile = new_profile(db, "Gone");
+        let lost_ids = vec![
+            new_entry(db, &lost.id, "first").id,
+            new_entry(db, &lost.id, "second").id,
+        ];
+        let mut input = entry_input(&live.id, "attributed");
+        input.profile_id = Some(profile.id.clone());
+        let live_entry = create_entry(db.clone(), input).unwrap();
+
+        orphan_rows(
+            db,
+            &format!(
+                "DELETE FROM streams WHERE id = '{}'; DELETE FROM profiles WHERE id = '{}'",
+                lost.id, profile.id
+            ),
+        );
+        (lost_ids, live_entry.id)
+    }
+
+    #[test]
+    fn orphans_are_reported_and_reassigned_to_a_recovered_stream() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let (mut lost_ids, live_id) = orphaned_fixture(&db);
+
+        let mut report = find_orphaned_entries(db.clone()).unwrap();
+        report.missing_stream.sort();
+        lost_ids.sort();
+        assert_eq!(report.missing_stream, lost_ids);
+        assert_eq!(report.missing_profile, std::slice::from_ref(&live_id));
+
+        assert_eq!(
+            repair_orphaned_entries(db.clone(), "reassign".into()).unwrap(),
+            3
+        );
+
+        let recovered = get_all_streams(db.clone(), "default-user".into(), None)
+            .unwrap()
+            .into_iter()
+            .find(|s| s.title == "Recovered")
+            .unwrap();
+        let mut recovered_ids = entry_ids(&db, &recovered.id);
+        recovered_ids.sort();
+        assert_eq!(recovered_ids, lost_ids);
+        assert!(get_entry(db.clone(), live_id)
+            .unwrap()
+            .unwrap()
+            .profile_id
+            .is_none());
+        let report = find_orphaned_entries(db.clone()).unwrap();
+        assert!(report.missing_stream.is_empty() && report.missing_profile.is_empty());
+    }
+
+    #[test]
+    fn orphans_can_be_deleted_instead() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let (lost_ids, _) = orphaned_fixture(&db);
+
+        let err = repair_orphaned_entries(db.clone(), "ignore".into()).unwrap_err();
+        assert_eq!(err.code, "INVALID_INPUT");
+
+        repair_orphaned_entries(db.clone(), "delete".into()).unwrap();
+
+        for id in lost_ids {
+            assert_eq!(
+                count(&db, "SELECT COUNT(*) FROM entries WHERE id = ?1", &id),
+                0
+            );
+        }
+    }
+
+    #[test]
+    fn deleting_a_profile_with_reassign_leaves_no_entry_orphaned() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let from = new_profile(&db, "Old persona");
+        let to = new_profile(&db, "Merged persona");
+        for text in ["one", "two", "three"] {
+            let mut input = entry_input(&stream.id, text);
+            input.profile_id = Some(from.id.clone());
+            create_entry(db.clone(), input).unwrap();
+        }
+
+        // Without a target, or with one that doesn't exist, nothing changes
+        assert!(delete_profile(db.clone(), from.id.clone(), None).is_err());
+        let err = delete_profile(db.clone(), from.id.clone(), Some("missing".into())).unwrap_err();
+        assert_eq!(err.code, "NOT_FOUND");
+        assert_eq!(
+            get_profile_entry_count(db.clone(), from.id.clone()).unwrap(),
+            3
+        );
+
+        delete_profile(db.clone(), from.id.clone(), Some(to.id.clone())).unwrap();
+
+        assert!(get_profile(db.clone(), from.id).unwrap().is_none());
+        assert_eq!(get_profile_entry_count(db.clone(), to.id).unwrap(), 3);
+        assert_eq!(
+            count(
+                &db,
+                "SELECT COUNT(*) FROM entries WHERE stream_id = ?1 AND profile_id IS NULL",
+                &stream.id
+            ),
+            0
+        );
+    }
+
+    #[test]
+    fn setting_a_new_default_profile_unsets_the_old_one() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let a = new_profile(&db, "A");
+        let b = new_profile(&db, "B");
+
+        set_default_profile(db.clone(), a.id.clone()).unwrap();
+        assert_eq!(get_default_profile(db.clone()).unwrap().unwrap().id, a.id);
+
+        set_default_profile(db.clone(), b.id.clone()).unwrap();
+
+        assert_eq!(get_default_profile(db.clone()).unwrap().unwrap().id, b.id);
+        assert!(!get_profile(db.clone(), a.id).unwrap().unwrap().is_default);
+        assert_eq!(
+            count(
+                &db,
+                "SELECT COUNT(*) FROM profiles WHERE is_default = ?1",
+                "1"
+            ),
+            1
+        );
+    }
+
+    #[test]
+    fn every_write_to_a_locked_stream_is_rejected_while_reads_work() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let locked = new_stream(&db, "Published");
+        let open = new_stream(&db, "Drafts");
+        let entry = new_entry(&db, &locked.id, "final text");
+        let twin = new_entry(&db, &locked.id, "final text");
+        let trashed = new_entry(&db, &locked.id, "trashed");
+        let elsewhere = new_entry(&db, &open.id, "draft");
+        commit_versions(&db, &entry.id, 1);
+        delete_entry(db.clone(), trashed.id.clone()).unwrap();
+        let block = create_pending_block(
+            db.clone(),
+            "default-user".into(),
+            locked.id.clone(),
+            "abc123".into(),
+            vec![entry.id.clone()],
+            "CRITIQUE".into(),
+        )
+        .unwrap();
+        set_stream_locked(db.clone(), locked.id.clone(), true).unwrap();
+        let before = get_stream_details(db.clone(), locked.id.clone(), None, None).unwrap();
+
+        let attempts: Vec<(&str, Result<(), AppError>)> = vec![
+            (
+                "create_entry",
+                create_entry(db.clone(), entry_input(&locked.id, "new")).map(|_| ()),
+            ),
+            (
+                "create_entry_at",
+                create_entry_at(db.clone(), entry_input(&locked.id, "new"), 0).map(|_| ()),
+            ),
+            (
+                "bulk_create_entries",
+                bulk_create_entries(
+                    db.clone(),
+                    locked.id.clone(),
+                    vec![entry_input(&locked.id, "new")],
+                )
+                .map(|_| ()),
+            ),
+            (
+                "update_entry_content",
+                update_entry_content(db.clone(), entry.id.clone(), doc("edited")),
+            ),
+            (
+                "append_paragraph_to_entry",
+                append_paragraph_to_entry(db.clone(), entry.id.clone(), "more".into()).map(|_| ()),
+            ),
+            (
+                "reclassify_entry",
+                reclassify_entry(db.clone(), entry.id.clone(), "ai".into(), None, None).map(|_| ()),
+            ),
+            ("delete_entry", delete_entry(db.clone(), entry.id.clone())),
+            (
+                "reorder_entries",
+                reorder_entries(
+                    db.clone(),
+                    locked.id.clone(),
+                    vec![twin.id.clone(), entry.id.clone()],
+                ),
+            ),
+            (
+                "move_entry out",
+                move_entry(db.clone(), entry.id.clone(), open.id.clone()),
+            ),
+            (
+                "move_entry in",
+                move_entry(db.clone(), elsewhere.id.clone(), locked.id.clone()),
+            ),
+            (
+                "revert_to_version",
+                revert_to_version(db.clone(), entry.id.clone(), 1),
+            ),
+            (
+                "merge_streams from",
+                merge_streams(db.clone(), locked.id.clone(), open.id.clone()),
+            ),
+            (
+                "merge_streams into",
+                merge_streams(db.clone(), open.id.clone(), locked.id.clone()),
+            ),
+            (
+                "split_stream",
+                split_stream(db.clone(), locked.id.clone(), 2, "Half".into()).map(|_| ()),
+            ),
+            (
+                "resolve_pending_block",
+                resolve_pending_block(
+                    db.clone(),
+                    block.id.clone(),
+                    doc("reply"),
+                    ai_metadata("anthropic", "claude"),
+                )
+                .map(|_| ()),
+            ),
+            (
+                "merge_duplicate_entries",
+                merge_duplicate_entries(db.clone(), entry.id.clone(), vec![twin.id.clone()])
+                    .map(|_| ()),
+            ),
+            (
+                "restore_entry",
+                restore_entry(db.clone(), trashed.id.clone()),
+            ),
+            (
+                "purge_deleted_entries",
+                purge_deleted_entries(db.clone(), locked.id.clone()).map(|_| ()),
+            ),
+        ];
+        for (name, result) in attempts {
+            match result {
+                Err(e) => assert_eq!(e.code, "LOCKED", "{} failed with {}", name, e.message),
+                Ok(()) => panic!("{} wrote to a locked stream", name),
+            }
+        }
+
+        // Nothing changed, and everything can still be read
+        let after = get_stream_details(db.clone(), locked.id.clone(), None, None).unwrap();
+        let contents = |details: &StreamWithEntries| -> Vec<_> {
+            details
+                .entries
+                .iter()
+                .map(|e| (e.id.clone(), e.sequence_id, e.content.clone()))
+                .collect()
+        };
+        assert_eq!(contents(&after), contents(&before));
+        assert_eq!(
+            get_deleted_entries(db.clone(), locked.id.clone())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(version_numbers(&db, &entry.id), [1]);
+        let pending = get_pending_block(db.clone(), locked.id.clone()).unwrap();
+        assert_eq!(pending.map(|b| b.id), Some(block.id));
+        assert_eq!(entry_ids(&db, &open.id), [elsewhere.id]);
+
+        set_stream_locked(db.clone(), locked.id, false).unwrap();
+        update_entry_content(db.clone(), entry.id, doc("edited")).unwrap();
+    }
+
+    #[test]
+    fn compact_closes_gaps_and_puts_trashed_entries_last() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let other = new_stream(&db, "Other");
+        let ids: Vec<_> = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|text| new_entry(&db, &stream.id, text).id)
+            .collect();
+        delete_entry(db.clone(), ids[1].clone()).unwrap();
+        move_entry(db.clone(), ids[3].clone(), other.id.clone()).unwrap();
+
+        compact_sequence_ids(db.clone(), stream.id.clone()).unwrap();
+
+        let live: Vec<_> = get_stream_details(db.clone(), stream.id.clone(), None, None)
+            .unwrap()
+            .entries
+            .into_iter()
+            .map(|e| (e.id, e.sequence_id))
+            .collect();
+        assert_eq!(
+            live,
+            [
+                (ids[0].clone(), 1),
+                (ids[2].clone(), 2),
+                (ids[4].clone(), 3)
+            ]
+        );
+        let trashed = get_deleted_entries(db.clone(), stream.id.clone()).unwrap();
+        assert_eq!(trashed[0].sequence_id, 4);
+
+        restore_entry(db.clone(), ids[1].clone()).unwrap();
+        assert_eq!(
+            entry_ids(&db, &stream.id),
+            [
+                ids[0].clone(),
+                ids[2].clone(),
+                ids[4].clone(),
+                ids[1].clone()
+            ]
+        );
+    }
+
+    #[test]
+    fn revert_to_bookmark_uses_the_newest_bookmark_until_it_is_removed() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "start");
+        commit_versions(&db, &entry.id, 3);
+        let version = |number: i32| {
+            get_entry_versions(db.clone(), entry.id.clone())
+                .unwrap()
+                .into_iter()
+                .find(|v| v.version_number == number)
+                .unwrap()
+        };
+        let content = || {
+            get_entry(db.clone(), entry.id.clone())
+                .unwrap()
+                .unwrap()
+                .content
+        };
+
+        let err = revert_to_bookmark(db.clone(), entry.id.clone()).unwrap_err();
+        assert_eq!(err.code, "NOT_FOUND");
+
+        toggle_version_bookmark(db.clone(), version(1).id, true).unwrap();
+        toggle_version_bookmark(db.clone(), version(2).id, true).unwrap();
+        update_entry_content(db.clone(), entry.id.clone(), doc("unsaved")).unwrap();
+
+        let reverted = revert_to_bookmark(db.clone(), entry.id.clone()).unwrap();
+        assert_eq!(reverted.version_number, 2);
+        assert_eq!(content(), doc("draft 2"));
+
+        toggle_version_bookmark(db.clone(), version(2).id, false).unwrap();
+        let reverted = revert_to_bookmark(db.clone(), entry.id.clone()).unwrap();
+        assert_eq!(reverted.version_number, 1);
+        assert_eq!(content(), doc("draft 1"));
+        assert!(!version(2).bookmarked);
+    }
+
+    #[test]
+    fn backlinks_list_live_entries_that_mention_the_target() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let target = new_entry(&db, &stream.id, "the target");
+        let referencing = |text: &str, node_type: &str| {
+            let mut input = entry_input(&stream.id, text);
+            input.content = serde_json::json!({
+                "type": "doc",
+                "content": [{ "type": "paragraph", "content": [
+                    { "type": "text", "text": text },
+                    { "type": node_type, "attrs": { "id": target.id } }
+                ]}]
+            });
+            create_entry(db.clone(), input).unwrap()
+        };
+        let linking = referencing("links here", "mention");
+        let trashed = referencing("deleted link", "mention");
+        let other_type = referencing("other node", "entryLink");
+        // Mentions the id in plain text only, which isn't a reference
+        new_entry(&db, &stream.id, &format!("just text {}", target.id));
+        delete_entry(db.clone(), trashed.id).unwrap();
+
+        let ids = |reference_type: Option<&str>| -> Vec<String> {
+            get_backlinks(
+                db.clone(),
+                target.id.clone(),
+                reference_type.map(String::from),
+            )
+            .unwrap()
+            .into_iter()
+            .map(|r| r.entry.id)
+            .collect()
+        };
+
+        assert_eq!(ids(None), [linking.id]);
+        assert_eq!(ids(Some("entryLink")), [other_type.id]);
+    }
+
+    fn empty_entry(db: &State<Database>, stream_id: &str) -> String {
+        let mut input = entry_input(stream_id, "");
+        input.content = serde_json::json!({ "type": "doc", "content": [content::paragraph("")] });
+        create_entry(db.clone(), input).unwrap().id
+    }
+
+    #[test]
+    fn empty_entries_are_found_and_trashed() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let blank = empty_entry(&db, &stream.id);
+        let kept = new_entry(&db, &stream.id, "has words").id;
+        let spaces = new_entry(&db, &stream.id, "   ").id;
+
+        assert_eq!(
+            find_empty_entries(db.clone(), stream.id.clone()).unwrap(),
+            [blank.clone(), spaces.clone()]
+        );
+
+        assert_eq!(
+            delete_empty_entries(db.clone(), stream.id.clone()).unwrap(),
+            2
+        );
+        assert_eq!(entry_ids(&db, &stream.id), [kept]);
+        assert_eq!(get_deleted_entries(db.clone(), stream.id).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn deleting_empties_keeps_one_when_every_entry_is_empty() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Blank");
+        let first = empty_entry(&db, &stream.id);
+        empty_entry(&db, &stream.id);
+
+        assert_eq!(
+            delete_empty_entries(db.clone(), stream.id.clone()).unwrap(),
+            1
+        );
+        assert_eq!(entry_ids(&db, &stream.id), [first]);
+
+        // A lone empty entry is left alone
+        assert_eq!(
+            delete_empty_entries(db.clone(), stream.id.clone()).unwrap(),
+            0
+        );
+    }
+
+    fn top_level_nodes(db: &State<Database>, entry_id: &str) -> Vec<serde_json::Value> {
+        get_entry(db.clone(), entry_id.to_string())
+            .unwrap()
+            .unwrap()
+            .content["content"]
+            .as_array()
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn append_paragraph_adds_one_node_at_the_end() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let entry = new_entry(&db, &stream.id, "first");
+
+        append_paragraph_to_entry(db.clone(), entry.id.clone(), "second".into()).unwrap();
+
+        let nodes = top_level_nodes(&db, &entry.id);
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1], content::paragraph("second"));
+
+        // Empty text still adds a (valid, empty) paragraph
+        append_paragraph_to_entry(db.clone(), entry.id.clone(), String::new()).unwrap();
+        assert_eq!(top_level_nodes(&db, &entry.id)[2], content::paragraph(""));
+
+        delete_entry(db.clone(), entry.id.clone()).unwrap();
+        let err = append_paragraph_to_entry(db.clone(), entry.id, "late".into()).unwrap_err();
+        assert_eq!(err.code, "NOT_FOUND");
+    }
+
+    #[test]
+    fn streamed_text_extends_the_last_paragraph_or_starts_one() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let mut input = entry_input(&stream.id, "");
+        input.content = serde_json::json!({ "type": "doc", "content": [] });
+        let empty = create_entry(db.clone(), input).unwrap();
+        let written = new_entry(&db, &stream.id, "Hello");
+
+        append_text_to_last_node(db.clone(), empty.id.clone(), "First chunk".into()).unwrap();
+        assert_eq!(
+            top_level_nodes(&db, &empty.id),
+            [content::paragraph("First chunk")]
+        );
+
+        append_text_to_last_node(db.clone(), written.id.clone(), ", world".into()).unwrap();
+        append_text_to_last_node(db.clone(), written.id.clone(), "!".into()).unwrap();
+        let stored = get_entry(db.clone(), written.id).unwrap().unwrap();
+        assert_eq!(top_level_nodes(&db, &stored.id).len(), 1);
+        assert_eq!(
+            content::extract_plain_text(&stored.content),
+            "Hello, world!"
+        );
+    }
+
+    #[test]
+    fn reclassify_turns_a_user_entry_into_an_ai_entry_and_back() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let critic = new_profile(&db, "Critic");
+        let entry = new_entry(&db, &stream.id, "pasted response");
+        assert_eq!(entry.role, "user");
+
+        let reclassified = reclassify_entry(
+            db.clone(),
+            entry.id.clone(),
+            "ai".into(),
+            Some(critic.id.clone()),
+            Some(ai_metadata("anthropic", "claude-sonnet")),
+        )
+        .unwrap();
+
+        assert_eq!(reclassified.role, "ai");
+        assert_eq!(reclassified.profile_id.as_deref(), Some(critic.id.as_str()));
+        let metadata = reclassified.ai_metadata.unwrap();
+        assert_eq!(
+            (metadata.provider.as_str(), metadata.model.as_str()),
+            ("anthropic", "claude-sonnet")
+        );
+        let stored = get_entry(db.clone(), entry.id.clone()).unwrap().unwrap();
+        assert_eq!(stored.role, "ai");
+        assert!(stored.ai_metadata.is_some());
+
+        let back =
+            reclassify_entry(db.clone(), entry.id.clone(), "user".into(), None, None).unwrap();
+        assert_eq!(back.role, "user");
+        assert!(back.profile_id.is_none() && back.ai_metadata.is_none());
+
+        let err = reclassify_entry(db.clone(), entry.id, "bot".into(), None, None);
+        assert_eq!(err.unwrap_err().code, "INVALID_INPUT");
+    }
+
+    #[test]
+    fn children_of_entry_match_versioned_parents_and_skip_malformed_rows() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let parent = new_entry(&db, &stream.id, "parent");
+        let child_of = |text: &str, parents: Vec<String>| {
+            let mut input = entry_input(&stream.id, text);
+            input.parent_context_ids = Some(parents);
+            create_entry(db.clone(), input).unwrap().id
+        };
+        let bare = child_of("bare", vec![parent.id.clone()]);
+        let versioned = child_of("versioned", vec![format!("{}:2", parent.id)]);
+        let malformed = new_entry(&db, &stream.id, "malformed");
+        db.pool
+            .get()
+            .unwrap()
+            .execute(
+                "UPDATE entries SET parent_context_ids = ?1 WHERE id = ?2",
+                params![format!("[\"{}\"", parent.id), malformed.id],
+            )
+            .unwrap();
+
+        let children = get_children_of_entry(db.clone(), parent.id).unwrap();
+
+        let ids: Vec<_> = children.into_iter().map(|entry| entry.id).collect();
+        assert_eq!(ids, [bare, versioned]);
+    }
+
+    #[test]
+    fn context_graph_reduces_versioned_parents_to_entry_ids() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let a = new_entry(&db, &stream.id, "a").id;
+        let mut input = entry_input(&stream.id, "b");
+        input.parent_context_ids = Some(vec![format!("{a}:3"), a.clone()]);
+        let b = create_entry(db.clone(), input).unwrap().id;
+
+        let graph = get_context_graph(db.clone(), stream.id).unwrap();
+
+        assert_eq!(graph.nodes, [a.clone(), b.clone()]);
+        assert_eq!(graph.edges, [(a, b)]);
+    }
+
+    #[test]
+    fn duplicate_stream_remaps_versioned_parent_ids_into_the_copy() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let original = new_stream(&db, "Original");
+        let parent = new_entry(&db, &original.id, "prompt");
+        let mut input = entry_input(&original.id, "generated");
+        input.parent_context_ids = Some(vec![format!("{}:2", parent.id)]);
+        create_entry(db.clone(), input).unwrap();
+
+        let copy = duplicate_stream(db.clone(), original.id, None, None).unwrap();
+
+        let entries = get_stream_details(db.clone(), copy.id, None, None)
+            .unwrap()
+            .entries;
+        assert_ne!(entries[0].id, parent.id);
+        assert_eq!(
+            entries[1].parent_context_ids,
+            Some(vec![format!("{}:2", entries[0].id)])
+        );
+    }
+
+    #[test]
+    fn split_stream_drops_versioned_parents_across_the_split() {
+        let app = test_app();
+        let db = app.state::<Database>();
+        let stream = new_stream(&db, "Notes");
+        let child_of = |text: &str, parents: Vec<String>| {
+            let mut input = entry_input(&stream.id, text);
+            input.parent_context_ids = Some(parents);
+            create_entry(db.clone(), input).unwrap()
+        };
+        let a = new_entry(&db, &stream.id, "a");
+        let b = child_of("b", vec![format!("{}:1", a.id)]);
+        let c = child_of("c", vec![format!("{}:1", b.id), format!("{}:2", a.id)]);
+
+        let half = split_stream(db.clone(), stream.id, b.sequence_id, "Half".into()).unwrap();
+
+        let moved = get_stream_details(db.clone(), half.id, None, None)
+            .unwrap()
+            .entries;
+        assert_eq!(moved[0].id, b.id);
+        assert_eq!(moved[0].parent_context_ids, None);
+        assert_eq!(moved[1].id, c.id);
+        assert_eq!(
+            moved[1].parent_context_ids,
+            Some(vec![format!("{}:1", b.id)])
+        );
+    }
+}
@@ -0,0 +1,686 @@
+use serde_json::Value;
+
+// ============================================================
+// PROSEMIRROR CONTENT HELPERS
+// ============================================================
+
+/// Node types that end with a line break when flattened to plain text
+const BLOCK_NODES: &[&str] = &[
+    "paragraph",
+    "heading",
+    "blockquote",
+    "codeBlock",
+    "bulletList",
+    "orderedList",
+    "listItem",
+    "taskList",
+    "taskItem",
+    "horizontalRule",
+];
+
+fn node_type(node: &Value) -> Option<&str> {
+    node.get("type").and_then(Value::as_str)
+}
+
+/// Flattens a ProseMirror document into plain text.
+/// Text nodes are concatenated and block nodes are separated by newlines.
+pub fn extract_plain_text(value: &Value) -> String {
+    let mut out = String::new();
+    collect_text(value, &mut out);
+    out.trim_end_matches('\n').to_string()
+}
+
+fn collect_text(node: &Value, out: &mut String) {
+    if let Some(text) = node.get("text").and_then(Value::as_str) {
+        out.push_str(text);
+    }
+
+    if node_type(node) == Some("hardBreak") {
+        out.push('\n');
+    }
+
+    if let Some(children) = node.get("content").and_then(Value::as_array) {
+        for child in children {
+            collect_text(child, out);
+        }
+    }
+
+    let is_block = node_type(node).is_some_and(|t| BLOCK_NODES.contains(&t));
+    if is_block && !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Same as `extract_plain_text`, but takes the serialized JSON stored in the database.
+/// Unparseable content yields an empty string.
+pub fn extract_plain_text_from_str(content: &str) -> String {
+    serde_json::from_str::<Value>(content)
+        .map(|value| extract_plain_text(&value))
+        .unwrap_or_default()
+}
+
+/// Counts whitespace-separated words in plain text
+pub fn word_count(text: &str) -> usize {
+    text.split_whitespace().count()
+}
+
+/// Counts characters in plain text, ignoring line breaks between blocks
+pub fn character_count(text: &str) -> usize {
+    text.chars().filter(|c| *c != '\n').count()
+}
+
+/// Hex-encoded BLAKE3 hash of serialized content, used to spot identical snapshots
+pub fn content_hash(content: &str) -> String {
+    blake3::hash(content.as_bytes()).to_hex().to_string()
+}
+
+/// Checks that `value` is a ProseMirror document: a `doc` node whose `content` is an array.
+/// Only the top level is checked; unknown child nodes are tolerated by the extractor and renderer.
+pub fn validate_doc(value: &Value) -> Result<(), String> {
+    match node_type(value) {
+        Some("doc") => {}
+        Some(other) => return Err(format!("Expected a \"doc\" node, got \"{}\"", other)),
+        None => return Err("Content is missing a \"type\" of \"doc\"".to_string()),
+    }
+
+    match value.get("content") {
+        Some(Value::Array(_)) => Ok(()),
+        Some(_) => Err("Document \"content\" must be an array".to_string()),
+        None => Err("Document is missing \"content\"".to_string()),
+    }
+}
+
+/// A paragraph node holding `text`. An empty `text` yields an empty paragraph,
+/// since ProseMirror rejects empty text nodes.
+pub fn paragraph(text: &str) -> Value {
+    if text.is_empty() {
+        return serde_json::json!({ "type": "paragraph", "content": [] });
+    }
+    serde_json::json!({
+        "type": "paragraph",
+        "content": [{ "type": "text", "text": text }]
+    })
+}
+
+/// Nodes whose children are inline text, so streamed text can land in them directly
+const TEXTBLOCK_NODES: &[&str] = &["paragraph", "heading", "codeBlock"];
+
+/// Appends `text` to the last text node of the document's last textblock, descending
+/// into lists and quotes. Marked text (bold, links) isn't extended; a plain text node
+/// follows it instead. A new paragraph is added when the document doesn't end in a
+/// textblock. Empty `text` is ignored, since ProseMirror rejects empty text nodes.
+pub fn append_text(doc: &mut Value, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+
+    let Some(nodes) = doc.get_mut("content").and_then(Value::as_array_mut) else {
+        return;
+    };
+    let appended = nodes
+        .last_mut()
+        .is_some_and(|last| append_text_to_block(last, text));
+    if !appended {
+        nodes.push(paragraph(text));
+    }
+}
+
+fn append_text_to_block(node: &mut Value, text: &str) -> bool {
+    let is_textblock = node_type(node).is_some_and(|t| TEXTBLOCK_NODES.contains(&t));
+    if !is_textblock {
+        return node
+            .get_mut("content")
+            .and_then(Value::as_array_mut)
+            .and_then(|children| children.last_mut())
+            .is_some_and(|last| append_text_to_block(last, text));
+    }
+
+    let Some(node) = node.as_object_mut() else {
+        return false;
+    };
+    let children = node
+        .entry("content")
+        .or_insert_with(|| Value::Array(Vec::new()));
+    if !children.is_array() {
+        *children = Value::Array(Vec::new());
+    }
+    let Some(children) = children.as_array_mut() else {
+        return false;
+    };
+
+    let extendable = children.last_mut().filter(|last| {
+        node_type(last) == Some("text")
+            && last
+                .get("marks")
+                .and_then(Value::as_array)
+                .map_or(0, Vec::len)
+                == 0
+    });
+    match extendable.and_then(|last| last.get_mut("text")) {
+        Some(Value::String(existing)) => existing.push_str(text),
+        _ => children.push(serde_json::json!({ "type": "text", "text": text })),
+    }
+    true
+}
+
+// ============================================================
+// ENTRY REFERENCES
+// ============================================================
+
+/// Node type of TipTap's mention extension, used for inline entry references
+pub const MENTION_NODE: &str = "mention";
+
+/// Ids referenced by nodes of any of `node_types`, read from their `attrs.id`,
+/// in document order without repeats
+pub fn referenced_entry_ids(value: &Value, node_types: &[&str]) -> Vec<String> {
+    let mut ids = Vec::new();
+    collect_references(value, node_types, &mut ids);
+    ids
+}
+
+fn collect_references(node: &Value, node_types: &[&str], ids: &mut Vec<String>) {
+    if node_type(node).is_some_and(|t| node_types.contains(&t)) {
+        if let Some(id) = attr(node, "id").and_then(Value::as_str) {
+            if !ids.iter().any(|existing| existing == id) {
+                ids.push(id.to_string());
+            }
+        }
+    }
+
+    for child in children(node) {
+        collect_references(child, node_types, ids);
+    }
+}
+
+// ============================================================
+// MARKDOWN RENDERING
+// ============================================================
+
+fn children(node: &Value) -> &[Value] {
+    node.get("content")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
+
+fn attr<'a>(node: &'a Value, key: &str) -> Option<&'a Value> {
+    node.get("attrs").and_then(|attrs| attrs.get(key))
+}
+
+/// Renders a ProseMirror document as Markdown.
+/// Unknown node types fall back to their plain text.
+pub fn to_markdown(value: &Value) -> String {
+    render_block(value)
+}
+
+fn render_blocks(nodes: &[Value], separator: &str) -> String {
+    nodes
+        .iter()
+        .map(render_block)
+        .filter(|block| !block.is_empty())
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+fn render_block(node: &Value) -> String {
+    match node_type(node) {
+        Some("doc") => render_blocks(children(node), "\n\n"),
+        Some("paragraph") => render_inline(children(node)),
+        Some("heading") => {
+            let level = attr(node, "level")
+                .and_then(Value::as_u64)
+                .unwrap_or(1)
+                .clamp(1, 6) as usize;
+            format!("{} {}", "#".repeat(level), render_inline(children(node)))
+        }
+        Some("blockquote") => prefix_lines(&render_blocks(children(node), "\n\n"), "> "),
+        Some("codeBlock") => {
+            let language = attr(node, "language").and_then(Value::as_str).unwrap_or("");
+            format!("```{}\n{}\n```", language, extract_plain_text(node))
+        }
+        Some("bulletList") => render_list(children(node), |_, _| "- ".to_string()),
+        Some("orderedList") => {
+            let start = attr(node, "start").and_then(Value::as_u64).unwrap_or(1);
+            render_list(children(node), |index, _| {
+                format!("{}. ", start + index as u64)
+            })
+        }
+        Some("taskList") => render_list(children(node), |_, item| {
+            let checked = attr(item, "checked")
+                .and_then(Value::as_bool)
+                .unwrap_or(false);
+            if checked { "- [x] " } else { "- [ ] " }.to_string()
+        }),
+        Some("horizontalRule") => "---".to_string(),
+        _ => extract_plain_text(node),
+    }
+}
+
+fn render_list(items: &[Value], marker: impl Fn(usize, &Value) -> String) -> String {
+    items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let marker = marker(index, item);
+            let indent = " ".repeat(marker.chars().count());
+            let body = render_blocks(children(item), "\n");
+
+            let mut lines = body.lines();
+            let first = lines.next().unwrap_or("");
+            let mut rendered = format!("{}{}", marker, first);
+            for line in lines {
+                rendered.push('\n');
+                if !line.is_empty() {
+                    rendered.push_str(&indent);
+                    rendered.push_str(line);
+                }
+            }
+            rendered
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn prefix_lines(text: &str, prefix: &str) -> String {
+    text.lines()
+        .map(|line| format!("{}{}", prefix, line).trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn render_inline(nodes: &[Value]) -> String {
+    nodes
+        .iter()
+        .map(|node| match node_type(node) {
+            Some("text") => render_text(node),
+            Some("hardBreak") => "  \n".to_string(),
+            _ => extract_plain_text(node),
+        })
+        .collect()
+}
+
+fn render_text(node: &Value) -> String {
+    let mut text = node
+        .get("text")
+        .and_then(Value::as_str)
+        .unwrap_or("")
+        .to_string();
+
+    let marks = node
+        .get("marks")
+        .and_then(Value::as_array)
+        .map(Vec::as_slice)
+        .unwrap_or(&[]);
+
+    for mark in marks {
+        text = match node_type(mark) {
+            Some("bold") => format!("**{}**", text),
+            Some("italic") => format!("*{}*", text),
+            Some("strike") => format!("~~{}~~", text),
+            Some("code") => format!("`{}`", text),
+            Some("link") => match attr(mark, "href").and_then(Value::as_str) {
+                Some(href) => format!("[{}]({})", text, href),
+                None => text,
+            },
+            _ => text,
+        };
+    }
+
+    text
+}
+
+// ============================================================
+// SEARCH SNIPPETS
+// ============================================================
+
+/// Returns up to `radius` characters on each side of the first case-insensitive
+/// occurrence of any of `terms`, with line breaks collapsed to spaces.
+/// Falls back to the start of the text when nothing matches.
+pub fn snippet(text: &str, terms: &[&str], radius: usize) -> String {
+    let window = SnippetWindow::new(text, terms, radius);
+    window.wrap(window.chars[window.start..window.end].iter().collect())
+}
+
+/// Like `snippet`, but wraps every occurrence of the terms inside the window in `**`
+/// so the frontend can emphasise them. Where terms overlap, the longest one wins.
+pub fn highlighted_snippet(text: &str, terms: &[&str], radius: usize) -> String {
+    let window = SnippetWindow::new(text, terms, radius);
+
+    let mut out = String::new();
+    let mut i = window.start;
+    while i < window.end {
+        let matched = window
+            .terms
+            .iter()
+            .filter(|term| {
+                i + term.len() <= window.end && window.lowered[i..i + term.len()] == term[..]
+            })
+            .map(|term| term.len())
+            .max();
+
+        match matched {
+            Some(len) => {
+                out.push_str("**");
+                out.extend(&window.chars[i..i + len]);
+                out.push_str("**");
+                i += len;
+            }
+            None => {
+                out.push(window.chars[i]);
+                i += 1;
+            }
+        }
+    }
+
+    window.wrap(out)
+}
+
+/// The span of text a search snippet shows, in characters
+struct SnippetWindow {
+    chars: Vec<char>,
+    lowered: Vec<char>,
+    terms: Vec<Vec<char>>,
+    start: usize,
+    end: usize,
+}
+
+impl SnippetWindow {
+    fn new(text: &str, terms: &[&str], radius: usize) -> Self {
+        let chars: Vec<char> = text
+            .chars()
+            .map(|c| if c == '\n' { ' ' } else { c })
+            .collect();
+        let lowered: Vec<char> = chars.iter().map(|c| lower_char(*c)).collect();
+        let terms: Vec<Vec<char>> = terms
+            .iter()
+            .map(|term| term.chars().map(lower_char).collect::<Vec<_>>())
+            .filter(|term| !term.is_empty())
+            .collect();
+
+        let first_match = terms
+            .iter()
+            .filter_map(|term| {
+                lowered
+                    .windows(term.len())
+                    .position(|window| window == term.as_slice())
+                    .map(|start| (start, term.len()))
+            })
+            .min();
+
+        let (start, end) = match first_match {
+            Some((position, len)) => (
+                position.saturating_sub(radius),
+                (position + len + radius).min(chars.len()),
+            ),
+            None => (0, (radius * 2).min(chars.len())),
+        };
+
+        Self {
+            chars,
+            lowered,
+            terms,
+            start,
+            end,
+        }
+    }
+
+    /// Adds ellipses on whichever sides the window cuts the text
+    fn wrap(&self, mut out: String) -> String {
+        if self.start > 0 {
+            out.insert(0, '…');
+        }
+        if self.end < self.chars.len() {
+            out.push('…');
+        }
+        out
+    }
+}
+
+/// Lowercases a single character, keeping a one-to-one mapping so match
+/// positions line up with the original text
+fn lower_char(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn text(text: &str) -> Value {
+        json!({ "type": "text", "text": text })
+    }
+
+    fn marked(text: &str, mark: &str) -> Value {
+        json!({ "type": "text", "text": text, "marks": [{ "type": mark }] })
+    }
+
+    fn node(node_type: &str, content: Vec<Value>) -> Value {
+        json!({ "type": node_type, "content": content })
+    }
+
+    fn doc(content: Vec<Value>) -> Value {
+        node("doc", content)
+    }
+
+    #[test]
+    fn plain_text_separates_blocks_and_walks_nested_nodes() {
+        let value = doc(vec![
+            json!({ "type": "heading", "attrs": { "level": 1 }, "content": [text("Title")] }),
+            node(
+                "bulletList",
+                vec![
+                    node(
+                        "listItem",
+                        vec![node("paragraph", vec![text("a"), text("b")])],
+                    ),
+                    json!({ "type": "listItem" }),
+                ],
+            ),
+            node("blockquote", vec![node("paragraph", vec![text("quoted")])]),
+            node("paragraph", vec![]),
+        ]);
+
+        assert_eq!(extract_plain_text(&value), "Title\nab\nquoted");
+    }
+
+    #[test]
+    fn plain_text_handles_missing_content_and_bad_json() {
+        assert_eq!(extract_plain_text(&json!({ "type": "doc" })), "");
+        assert_eq!(extract_plain_text_from_str("not json"), "");
+        assert_eq!(
+            extract_plain_text_from_str(
+                &doc(vec![node("paragraph", vec![text("hi")])]).to_string()
+            ),
+            "hi"
+        );
+    }
+
+    #[test]
+    fn markdown_renders_headings_marks_and_quotes() {
+        let value = doc(vec![
+            json!({ "type": "heading", "attrs": { "level": 2 }, "content": [text("Plan")] }),
+            node(
+                "paragraph",
+                vec![
+                    marked("bold", "bold"),
+                    text(" and "),
+                    marked("soft", "italic"),
+                ],
+            ),
+            node("blockquote", vec![node("paragraph", vec![text("quoted")])]),
+            json!({ "type": "codeBlock", "attrs": { "language": "rust" }, "content": [text("let x = 1;")] }),
+        ]);
+
+        assert_eq!(
+            to_markdown(&value),
+            "## Plan\n\n**bold** and *soft*\n\n> quoted\n\n```rust\nlet x = 1;\n```"
+        );
+    }
+
+    #[test]
+    fn markdown_renders_nested_lists() {
+        let item = |label: &str, nested: Option<Value>| {
+            let mut content = vec![node("paragraph", vec![text(label)])];
+            content.extend(nested);
+            node("listItem", content)
+        };
+        let value = doc(vec![
+            node(
+                "bulletList",
+                vec![
+                    item("one", Some(node("bulletList", vec![item("inner", None)]))),
+                    item("two", None),
+                ],
+            ),
+            json!({ "type": "orderedList", "attrs": { "start": 3 }, "content": [item("three", None), item("four", None)] }),
+        ]);
+
+        assert_eq!(
+            to_markdown(&value),
+            "- one\n  - inner\n- two\n\n3. three\n4. four"
+        );
+    }
+
+    #[test]
+    fn markdown_falls_back_to_plain_text_for_unknown_nodes() {
+        let value = doc(vec![node(
+            "callout",
+            vec![node("paragraph", vec![text("careful")])],
+        )]);
+
+        assert_eq!(to_markdown(&value), "careful");
+    }
+
+    #[test]
+    fn snippet_centres_on_the_first_match() {
+        let text = "The first line\nmentions nothing, but later the Organize step appears.";
+
+        assert_eq!(snippet(text, &["organize"], 6), "…r the Organize step …");
+        assert_eq!(
+            snippet("short organize", &["organize"], 20),
+            "short organize"
+        );
+    }
+
+    #[test]
+    fn snippet_falls_back_to_the_start_without_a_match() {
+        assert_eq!(snippet("line one\nline two", &["absent"], 4), "line one…");
+        assert_eq!(snippet("", &["absent"], 4), "");
+    }
+
+    #[test]
+    fn counts_words_and_characters_of_extracted_text() {
+        let value = doc(vec![
+            json!({ "type": "heading", "attrs": { "level": 1 }, "content": [text("Morning pages")] }),
+            node(
+                "paragraph",
+                vec![text("Wrote "), marked("three", "bold"), text(" words.")],
+            ),
+        ]);
+        let plain = extract_plain_text(&value);
+
+        assert_eq!(word_count(&plain), 5);
+        // Line breaks between blocks don't count as characters
+        assert_eq!(
+            character_count(&plain),
+            "Morning pages".len() + "Wrote three words.".len()
+        );
+        assert_eq!(word_count(&extract_plain_text(&doc(vec![]))), 0);
+    }
+
+    #[test]
+    fn validate_doc_accepts_a_doc_with_array_content() {
+        assert_eq!(validate_doc(&doc(vec![node("paragraph", vec![])])), Ok(()));
+        assert_eq!(validate_doc(&doc(vec![])), Ok(()));
+    }
+
+    #[test]
+    fn validate_doc_rejects_missing_or_wrong_type() {
+        let missing = json!({ "content": [] });
+        assert!(validate_doc(&missing).unwrap_err().contains("missing"));
+
+        let paragraph = node("paragraph", vec![text("hi")]);
+        assert!(validate_doc(&paragraph)
+            .unwrap_err()
+            .contains("\"paragraph\""));
+    }
+
+    #[test]
+    fn validate_doc_rejects_non_array_or_missing_content() {
+        let object_content = json!({ "type": "doc", "content": { "type": "paragraph" } });
+        assert!(validate_doc(&object_content)
+            .unwrap_err()
+            .contains("must be an array"));
+
+        let no_content = json!({ "type": "doc" });
+        assert!(validate_doc(&no_content).unwrap_err().contains("missing"));
+    }
+
+    #[test]
+    fn highlighted_snippet_marks_every_occurrence_in_original_case() {
+        assert_eq!(
+            highlighted_snippet("Cat sat; the cat and CAT.", &["cat"], 50),
+            "**Cat** sat; the **cat** and **CAT**."
+        );
+        // Matches past the window edge stay unmarked, along with the text around them
+        assert_eq!(
+            highlighted_snippet("aa cat bb cat cc", &["cat"], 3),
+            "aa **cat** bb…"
+        );
+    }
+
+    #[test]
+    fn highlighted_snippet_prefers_the_longest_overlapping_term() {
+        assert_eq!(
+            highlighted_snippet("Organize now", &["org", "organize"], 20),
+            "**Organize** now"
+        );
+    }
+
+    fn mention(node_type: &str, id: &str) -> Value {
+        json!({ "type": node_type, "attrs": { "id": id, "label": "ref" } })
+    }
+
+    #[test]
+    fn referenced_entry_ids_are_collected_in_order_without_repeats() {
+        let value = doc(vec![
+            node(
+                "paragraph",
+                vec![
+                    text("see "),
+                    mention("mention", "b"),
+                    mention("mention", "a"),
+                ],
+            ),
+            node(
+                "bulletList",
+                vec![node(
+                    "listItem",
+                    vec![node("paragraph", vec![mention("mention", "b")])],
+                )],
+            ),
+        ]);
+
+        assert_eq!(referenced_entry_ids(&value, &[MENTION_NODE]), ["b", "a"]);
+    }
+
+    #[test]
+    fn referenced_entry_ids_only_read_the_requested_node_types() {
+        let value = doc(vec![node(
+            "paragraph",
+            vec![
+                mention("mention", "a"),
+                mention("entryLink", "b"),
+                json!({ "type": "mention", "attrs": { "label": "no id" } }),
+            ],
+        )]);
+
+        assert_eq!(referenced_entry_ids(&value, &["entryLink"]), ["b"]);
+        assert_eq!(
+            referenced_entry_ids(&value, &["mention", "entryLink"]),
+            ["a", "b"]
+        );
+        assert!(referenced_entry_ids(&value, &[]).is_empty());
+    }
+}
@@ -1,27 +1,452 @@
-use rusqlite::{params, Connection, Result};
+use crate::content;
+use crate::models::Stream;
+use crate::templates::{self, StreamTemplate};
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::functions::FunctionFlags;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use std::collections::HashMap;
 use std::path::PathBuf;
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
+
+// ============================================================
+// MIGRATIONS
+// ============================================================
+
+/// A single schema change. Applied once, in order, and recorded in `schema_version`.
+struct Migration {
+    version: i64,
+    description: &'static str,
+    up: fn(&Connection) -> Result<()>,
+}
+
+/// Append new migrations to the end; never reorder or edit applied ones.
+/// Databases created before `schema_version` existed start at version 0, so the
+/// early migrations check for their columns before altering anything.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "add user_id to core tables",
+        up: |conn| {
+            for table in ["streams", "profiles", "entries", "pending_blocks"] {
+                if !has_column(conn, table, "user_id")? {
+                    // Add user_id column with a dummy default for now
+                    conn.execute(
+                        &format!(
+                            "ALTER TABLE {} ADD COLUMN user_id TEXT NOT NULL DEFAULT 'default-user'",
+                            table
+                        ),
+                        [],
+                    )?;
+                }
+            }
+            Ok(())
+        },
+    },
+    Migration {
+        version: 2,
+        description: "add profile_id to entries",
+        up: |conn| {
+            if !has_column(conn, "entries", "profile_id")? {
+                conn.execute(
+                    "ALTER TABLE entries ADD COLUMN profile_id TEXT REFERENCES profiles(id) ON DELETE SET NULL",
+                    [],
+                )?;
+            }
+            Ok(())
+        },
+    },
+    Migration {
+        version: 3,
+        description: "create entries_fts full-text index",
+        up: |conn| {
+            let has_entries_fts: bool = conn
+                .prepare(
+                    "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'entries_fts'",
+                )?
+                .exists([])?;
+
+            if !has_entries_fts {
+                // Create the FTS5 table and backfill it from existing entries
+                conn.execute_batch(
+                    r#"
+                    CREATE VIRTUAL TABLE entries_fts USING fts5(entry_id UNINDEXED, body);
+
+                    INSERT INTO entries_fts (entry_id, body)
+                    SELECT id, plain_text(content) FROM entries;
+                    "#,
+                )?;
+            }
+            Ok(())
+        },
+    },
+    Migration {
+        version: 4,
+        description: "add deleted_at to entries for soft delete",
+        up: |conn| {
+            conn.execute_batch(
+                "ALTER TABLE entries ADD COLUMN deleted_at INTEGER;
+                 CREATE INDEX IF NOT EXISTS idx_entries_deleted_at ON entries(stream_id, deleted_at);",
+            )
+        },
+    },
+    Migration {
+        version: 5,
+        description: "add archived flag to streams",
+        up: |conn| {
+            conn.execute(
+                "ALTER TABLE streams ADD COLUMN archived INTEGER DEFAULT 0",
+                [],
+            )?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 6,
+        description: "add content_hash to entry_versions",
+        up: |conn| {
+            conn.execute_batch(
+                "ALTER TABLE entry_versions ADD COLUMN content_hash TEXT;
+                 UPDATE entry_versions SET content_hash = content_hash(content_snapshot);",
+            )
+        },
+    },
+    Migration {
+        version: 7,
+        description: "add label to entry_versions",
+        up: |conn| conn.execute_batch("ALTER TABLE entry_versions ADD COLUMN label TEXT;"),
+    },
+    Migration {
+        version: 8,
+        description: "add is_pinned to entries",
+        up: |conn| {
+            conn.execute_batch("ALTER TABLE entries ADD COLUMN is_pinned INTEGER DEFAULT 0;")
+        },
+    },
+    Migration {
+        version: 9,
+        description: "add color and label to entries",
+        up: |conn| {
+            conn.execute_batch(
+                "ALTER TABLE entries ADD COLUMN color TEXT;
+                 ALTER TABLE entries ADD COLUMN label TEXT;",
+            )
+        },
+    },
+    Migration {
+        version: 10,
+        description: "create app_meta key-value table",
+        up: |conn| {
+            // Existing databases already went through first run, so they count as having the tutorial
+            conn.execute_batch(
+                "CREATE TABLE app_meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+                 INSERT INTO app_meta (key, value)
+                 SELECT 'tutorial_created', '1' WHERE EXISTS (SELECT 1 FROM streams);",
+            )
+        },
+    },
+    Migration {
+        version: 11,
+        description: "add pin_order to streams",
+        up: |conn| {
+            // Seed the order pinned streams were already shown in: most recently updated first
+            conn.execute_batch(
+                "ALTER TABLE streams ADD COLUMN pin_order INTEGER;
+                 UPDATE streams SET pin_order = (
+                     SELECT COUNT(*) FROM streams p
+                     WHERE p.user_id = streams.user_id AND p.pinned = 1
+                       AND (p.updated_at > streams.updated_at
+                            OR (p.updated_at = streams.updated_at AND p.id < streams.id))
+                 )
+                 WHERE pinned = 1;",
+            )
+        },
+    },
+    Migration {
+        version: 12,
+        description: "create undo_log table",
+        up: |conn| {
+            conn.execute_batch(
+                "CREATE TABLE undo_log (
+                     id INTEGER PRIMARY KEY AUTOINCREMENT,
+                     operation TEXT NOT NULL,
+                     snapshot TEXT NOT NULL,
+                     created_at INTEGER NOT NULL
+                 );",
+            )
+        },
+    },
+    Migration {
+        version: 13,
+        description: "add default_profile_id to streams",
+        up: |conn| {
+            conn.execute_batch(
+                "ALTER TABLE streams ADD COLUMN default_profile_id TEXT REFERENCES profiles(id) ON DELETE SET NULL;",
+            )
+        },
+    },
+    Migration {
+        version: 14,
+        description: "allow only one default profile",
+        up: |conn| {
+            // Keep the oldest default if earlier bugs left more than one
+            conn.execute_batch(
+                "UPDATE profiles SET is_default = 0
+                 WHERE is_default = 1 AND id != (
+                     SELECT id FROM profiles WHERE is_default = 1
+                     ORDER BY created_at ASC, id ASC LIMIT 1
+                 );
+                 CREATE UNIQUE INDEX idx_profiles_single_default ON profiles(is_default) WHERE is_default = 1;",
+            )
+        },
+    },
+    Migration {
+        version: 15,
+        description: "add word_goal to streams",
+        up: |conn| conn.execute_batch("ALTER TABLE streams ADD COLUMN word_goal INTEGER;"),
+    },
+    Migration {
+        version: 16,
+        description: "add locked to streams",
+        up: |conn| {
+            conn.execute_batch("ALTER TABLE streams ADD COLUMN locked INTEGER NOT NULL DEFAULT 0;")
+        },
+    },
+    Migration {
+        version: 17,
+        description: "normalize stream tags into stream_tags",
+        up: |conn| {
+            conn.execute_batch(
+                "CREATE TABLE stream_tags (
+                     stream_id TEXT NOT NULL,
+                     tag TEXT NOT NULL,
+                     PRIMARY KEY (stream_id, tag),
+                     FOREIGN KEY (stream_id) REFERENCES streams(id) ON DELETE CASCADE
+                 );
+                 CREATE INDEX idx_stream_tags_tag ON stream_tags(tag);
+                 INSERT OR IGNORE INTO stream_tags (stream_id, tag)
+                     SELECT s.id, t.value FROM streams s, json_each(s.tags) t
+                     WHERE json_valid(s.tags) AND json_type(s.tags) = 'array'
+                       AND t.type = 'text';",
+            )
+        },
+    },
+    Migration {
+        version: 18,
+        description: "add bookmarked to entry_versions",
+        up: |conn| {
+            conn.execute_batch(
+                "ALTER TABLE entry_versions ADD COLUMN bookmarked INTEGER NOT NULL DEFAULT 0;",
+            )
+        },
+    },
+    Migration {
+        version: 19,
+        description: "add entry_links",
+        up: |conn| {
+            conn.execute_batch(
+                "CREATE TABLE entry_links (
+                     source_entry_id TEXT NOT NULL,
+                     target_entry_id TEXT NOT NULL,
+                     created_at INTEGER NOT NULL,
+                     PRIMARY KEY (source_entry_id, target_entry_id),
+                     FOREIGN KEY (source_entry_id) REFERENCES entries(id) ON DELETE CASCADE,
+                     FOREIGN KEY (target_entry_id) REFERENCES entries(id) ON DELETE CASCADE
+                 );
+                 CREATE INDEX idx_entry_links_target ON entry_links(target_entry_id);",
+            )
+        },
+    },
+];
+
+/// `app_meta` key set once the tutorial stream has been created
+const TUTORIAL_CREATED_KEY: &str = "tutorial_created";
+
+/// Newest schema version this build knows how to migrate to
+pub fn latest_schema_version() -> i64 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool> {
+    conn.prepare(&format!(
+        "SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1",
+        table
+    ))?
+    .exists(params![column])
+}
+
+/// Errors from opening or using the database outside of a command
+#[derive(Debug, thiserror::Error)]
+pub enum DatabaseError {
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Pool(#[from] r2d2::Error),
+    #[error("database encryption requires building with the `encryption` feature")]
+    EncryptionUnavailable,
+    #[error("a plaintext database can't be keyed in place")]
+    NotEncrypted,
+}
+
+/// Pending blocks older than this are dropped on startup (24 hours)
+const PENDING_BLOCK_MAX_AGE_MS: i64 = 24 * 60 * 60 * 1000;
 
 pub struct Database {
-    pub conn: Mutex<Connection>,
+    pub pool: Pool<SqliteConnectionManager>,
+    /// Location of the database file on disk
+    pub path: PathBuf,
+    /// Entries edited since their last autosave version: entry id to last edit time (ms).
+    /// Kept in memory only; `flush_autosave_versions` turns idle ones into versions.
+    pub pending_autosaves: Mutex<HashMap<String, i64>>,
+    /// SQLCipher key new pooled connections are unlocked with; `None` for a plaintext database
+    key: Arc<Mutex<Option<String>>>,
 }
 
 impl Database {
-    pub fn new(app_data_dir: PathBuf) -> Result<Self> {
+    /// Opens (or creates) the database under `app_data_dir`. With a `key`, every connection
+    /// is unlocked via SQLCipher before anything else runs, including schema init.
+    ///
+    /// An existing plaintext database can't be keyed in place. To move to an encrypted one,
+    /// export each stream with `export_stream`, unlock an empty data directory with
+    /// `set_database_key`, then bring the streams back with `import_stream`.
+    pub fn new(
+        app_data_dir: PathBuf,
+        key: Option<String>,
+    ) -> std::result::Result<Self, DatabaseError> {
+        // Plain SQLite silently ignores PRAGMA key, which would leave the data unencrypted
+        if key.is_some() && !cfg!(feature = "encryption") {
+            return Err(DatabaseError::EncryptionUnavailable);
+        }
+
         std::fs::create_dir_all(&app_data_dir).expect("Failed to create app data directory");
 
         let db_path = app_data_dir.join("kolam_ikan.db");
-        let conn = Connection::open(&db_path)?;
+
+        // Check the key up front: the pool would keep retrying a wrong one until it timed out
+        if let Some(key) = &key {
+            Self::set_database_key(&Connection::open(&db_path)?, key)?;
+        }
+
+        // Every pooled connection gets the same pragmas and SQL functions
+        let key = Arc::new(Mutex::new(key));
+        let init_key = Arc::clone(&key);
+        let manager = SqliteConnectionManager::file(&db_path).with_init(move |conn| {
+            let key = init_key.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            if let Some(key) = &key {
+                Self::set_database_key(conn, key)?;
+            }
+            Self::configure_connection(conn)
+        });
+        let pool = Pool::builder().build(manager)?;
 
         // Initialize schema
-        Self::initialize_schema(&conn)?;
+        Self::initialize_schema(&*pool.get()?)?;
+
+        Ok(Self {
+            pool,
+            path: db_path,
+            pending_autosaves: Mutex::new(HashMap::new()),
+            key,
+        })
+    }
+
+    /// Opens a private in-memory database for tests. Every `:memory:` connection is its
+    /// own database, so the pool holds exactly one connection and never recycles it.
+    /// Checking out a second connection while one is held fails fast instead of waiting.
+    #[cfg(test)]
+    pub fn open_in_memory() -> std::result::Result<Self, DatabaseError> {
+        let manager = SqliteConnectionManager::memory().with_init(Self::configure_connection);
+        let pool = Pool::builder()
+            .max_size(1)
+            .connection_timeout(std::time::Duration::from_secs(1))
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .build(manager)?;
+
+        Self::initialize_schema(&*pool.get()?)?;
 
         Ok(Self {
-            conn: Mutex::new(conn),
+            pool,
+            path: PathBuf::from(":memory:"),
+            pending_autosaves: Mutex::new(HashMap::new()),
+            key: Arc::new(Mutex::new(None)),
         })
     }
 
-    fn initialize_schema(conn: &Connection) -> Result<()> {
+    /// Unlocks an SQLCipher database. Must be the first statement on the connection.
+    fn set_database_key(conn: &Connection, key: &str) -> Result<()> {
+        conn.pragma_update(None, "key", key)?;
+
+        // SQLCipher only checks the key on first read, so fail here rather than mid-query
+        conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+    }
+
+    /// Re-encrypts the database under `new_key`. A rekey leaves every other open connection
+    /// unable to read, so this first checks out the whole pool (waiting for running commands
+    /// to hand theirs back), rekeys through one and re-applies the key on the rest.
+    pub fn change_key(&self, new_key: &str) -> std::result::Result<(), DatabaseError> {
+        if self.key.lock().unwrap_or_else(|e| e.into_inner()).is_none() {
+            return Err(DatabaseError::NotEncrypted);
+        }
+
+        // Not holding the key lock here: opening a connection takes it in the pool's init
+        let connections = (0..self.pool.max_size())
+            .map(|_| self.pool.get())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        if let Some((first, rest)) = connections.split_first() {
+            first.pragma_update(None, "rekey", new_key)?;
+            for conn in rest {
+                Self::set_database_key(conn, new_key)?;
+            }
+        }
+
+        // With the whole pool checked out, no connection opens before this lands
+        *self.key.lock().unwrap_or_else(|e| e.into_inner()) = Some(new_key.to_string());
+        Ok(())
+    }
+
+    fn configure_connection(conn: &mut Connection) -> Result<()> {
+        // WAL lets reads proceed alongside writes, foreign_keys makes ON DELETE CASCADE
+        // actually fire, and busy_timeout waits on a locked database instead of failing
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             PRAGMA foreign_keys = ON;
+             PRAGMA busy_timeout = 5000;",
+        )?;
+
+        // Register custom SQL functions (used by the FTS triggers)
+        Self::register_functions(conn)
+    }
+
+    fn register_functions(conn: &Connection) -> Result<()> {
+        // plain_text(content) - flattens a ProseMirror JSON document to searchable text
+        conn.create_scalar_function(
+            "plain_text",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let content: Option<String> = ctx.get(0)?;
+                Ok(content
+                    .map(|c| content::extract_plain_text_from_str(&c))
+                    .unwrap_or_default())
+            },
+        )?;
+
+        // content_hash(content) - hash used to skip committing unchanged versions
+        conn.create_scalar_function(
+            "content_hash",
+            1,
+            FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+            |ctx| {
+                let content: Option<String> = ctx.get(0)?;
+                Ok(content.map(|c| content::content_hash(&c)))
+            },
+        )
+    }
+
+    /// Creates missing tables and brings the schema up to date. Also run after a restore.
+    pub fn initialize_schema(conn: &Connection) -> Result<()> {
         conn.execute_batch(
             r#"
             -- STREAMS
@@ -120,41 +545,61 @@ impl Database {
     }
 
     fn run_migrations(conn: &Connection) -> Result<()> {
-        let tables = ["streams", "profiles", "entries", "pending_blocks"];
-
-        for table in tables {
-            let has_user_id: bool = conn
-                .prepare(&format!(
-                    "SELECT 1 FROM pragma_table_info('{}') WHERE name = 'user_id'",
-                    table
-                ))?
-                .exists([])?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS schema_version (
+                version INTEGER PRIMARY KEY,
+                description TEXT NOT NULL,
+                applied_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
 
-            if !has_user_id {
-                // Migration: Add user_id column with a dummy default for now
-                conn.execute(
-                    &format!(
-                        "ALTER TABLE {} ADD COLUMN user_id TEXT NOT NULL DEFAULT 'default-user'",
-                        table
-                    ),
-                    [],
-                )
-                .ok();
-            }
+        let current_version: i64 = conn.query_row(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+            [],
+            |row| row.get(0),
+        )?;
+
+        for migration in MIGRATIONS.iter().filter(|m| m.version > current_version) {
+            // Each migration and its version bump commit together
+            let tx = conn.unchecked_transaction()?;
+            (migration.up)(&tx)?;
+            tx.execute(
+                "INSERT INTO schema_version (version, description, applied_at) VALUES (?1, ?2, ?3)",
+                params![
+                    migration.version,
+                    migration.description,
+                    chrono::Utc::now().timestamp_millis()
+                ],
+            )?;
+            tx.commit()?;
         }
 
-        // Check if profile_id column exists in entries
-        let has_profile_id: bool = conn
-            .prepare("SELECT 1 FROM pragma_table_info('entries') WHERE name = 'profile_id'")?
-            .exists([])?;
+        // Keep the FTS table in sync with entries.
+        // Triggers are recreated on every start so they always match the current projection.
+        conn.execute_batch(
+            r#"
+            DROP TRIGGER IF EXISTS entries_fts_insert;
+            CREATE TRIGGER entries_fts_insert AFTER INSERT ON entries BEGIN
+                INSERT INTO entries_fts (entry_id, body) VALUES (NEW.id, plain_text(NEW.content));
+            END;
+
+            DROP TRIGGER IF EXISTS entries_fts_update;
+            CREATE TRIGGER entries_fts_update AFTER UPDATE OF content ON entries BEGIN
+                DELETE FROM entries_fts WHERE entry_id = OLD.id;
+                INSERT INTO entries_fts (entry_id, body) VALUES (NEW.id, plain_text(NEW.content));
+            END;
 
-        if !has_profile_id {
-            // Migration: Add profile_id column to existing entries table
-            conn.execute(
-                "ALTER TABLE entries ADD COLUMN profile_id TEXT REFERENCES profiles(id) ON DELETE SET NULL",
-                [],
-            ).ok(); // Ignore errors if column already exists
-        }
+            CREATE TRIGGER IF NOT EXISTS entries_fts_delete AFTER DELETE ON entries BEGIN
+                DELETE FROM entries_fts WHERE entry_id = OLD.id;
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS streams_fts_delete AFTER DELETE ON streams BEGIN
+                DELETE FROM entries_fts
+                WHERE entry_id IN (SELECT id FROM entries WHERE stream_id = OLD.id);
+            END;
+            "#,
+        )?;
 
         // Now create the indexes
         conn.execute(
@@ -181,138 +626,288 @@ impl Database {
         Ok(())
     }
 
-    pub fn create_tutorial_stream(&self) -> Result<()> {
-        let conn = self.conn.lock().unwrap();
+    /// First-run setup and housekeeping, run each time the database is opened
+    pub fn run_startup_tasks(&self) -> std::result::Result<(), DatabaseError> {
+        self.create_default_profile()?;
+        self.create_tutorial_stream()?;
 
-        // Check if any streams exist
-        let count: i64 = conn.query_row("SELECT COUNT(*) FROM streams", [], |row| row.get(0))?;
+        // Drop bridge attempts that were never completed
+        if let Err(e) = self.cleanup_expired_pending_blocks(PENDING_BLOCK_MAX_AGE_MS) {
+            log::warn!("Failed to clean up expired pending blocks: {}", e);
+        }
 
-        if count == 0 {
-            let now = chrono::Utc::now().timestamp_millis();
-            let stream_id = uuid::Uuid::new_v4().to_string();
+        Ok(())
+    }
 
-            // Create welcome stream
-            conn.execute(
-                "INSERT INTO streams (id, user_id, title, description, tags, pinned, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-                params![
-                    stream_id,
-                    "default-user",
-                    "Welcome to Kolam Ikan",
-                    "Your first stream - feel free to experiment here!",
-                    "[\"tutorial\"]",
-                    1,
-                    now,
-                    now
-                ],
-            )?;
+    /// Creates the tutorial stream on first run. Tracked in `app_meta` rather than by
+    /// stream count, so deleting every stream doesn't bring it back.
+    pub fn create_tutorial_stream(&self) -> std::result::Result<(), DatabaseError> {
+        let created = Self::get_meta(&*self.pool.get()?, TUTORIAL_CREATED_KEY)?.is_some();
 
-            // Create first entry
-            let entry1_id = uuid::Uuid::new_v4().to_string();
-            let entry1_content = serde_json::json!({
-                "type": "doc",
-                "content": [
-                    {
-                        "type": "heading",
-                        "attrs": { "level": 1 },
-                        "content": [
-                            { "type": "text", "text": "Welcome! 👋" }
-                        ]
-                    },
-                    {
-                        "type": "paragraph",
-                        "content": [
-                            { "type": "text", "text": "Kolam Ikan is your personal thinking space. Here's how it works:" }
-                        ]
-                    },
-                    {
-                        "type": "orderedList",
-                        "content": [
-                            {
-                                "type": "listItem",
-                                "content": [{
-                                    "type": "paragraph",
-                                    "content": [
-                                        { "type": "text", "marks": [{ "type": "bold" }], "text": "Write freely" },
-                                        { "type": "text", "text": " - Just start typing your thoughts." }
-                                    ]
-                                }]
-                            },
-                            {
-                                "type": "listItem",
-                                "content": [{
-                                    "type": "paragraph",
-                                    "content": [
-                                        { "type": "text", "marks": [{ "type": "bold" }], "text": "Stage context" },
-                                        { "type": "text", "text": " - Check the boxes next to entries you want to send to AI." }
-                                    ]
-                                }]
-                            },
-                            {
-                                "type": "listItem",
-                                "content": [{
-                                    "type": "paragraph",
-                                    "content": [
-                                        { "type": "text", "marks": [{ "type": "bold" }], "text": "Choose a directive" },
-                                        { "type": "text", "text": " - DUMP (refactor), CRITIQUE (find gaps), or GENERATE (expand)." }
-                                    ]
-                                }]
-                            },
-                            {
-                                "type": "listItem",
-                                "content": [{
-                                    "type": "paragraph",
-                                    "content": [
-                                        { "type": "text", "marks": [{ "type": "bold" }], "text": "Copy & paste" },
-                                        { "type": "text", "text": " - Use the bridge buttons to connect with ChatGPT, Claude, or Gemini." }
-                                    ]
-                                }]
-                            }
-                        ]
-                    }
-                ]
-            });
+        if !created {
+            self.reset_tutorial()?;
+        }
 
-            conn.execute(
-                "INSERT INTO entries (id, user_id, stream_id, role, content, sequence_id, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-                params![
-                    entry1_id,
-                    "default-user",
-                    stream_id,
-                    "user",
-                    entry1_content.to_string(),
-                    1,
-                    now,
-                    now
-                ],
-            )?;
+        Ok(())
+    }
 
-            // Create second empty entry
-            let entry2_id = uuid::Uuid::new_v4().to_string();
-            let entry2_content = serde_json::json!({
-                "type": "doc",
-                "content": [
-                    {
-                        "type": "paragraph",
-                        "content": []
-                    }
-                ]
-            });
+    /// Creates the starter "You" profile as the default when there are no profiles yet
+    pub fn create_default_profile(&self) -> std::result::Result<(), DatabaseError> {
+        let now = chrono::Utc::now().timestamp_millis();
 
-            conn.execute(
+        self.pool.get()?.execute(
+            "INSERT INTO profiles (id, user_id, name, role, color, initials, is_default, created_at, updated_at)
+             SELECT ?1, 'default-user', 'You', 'self', '#3B82F6', 'ME', 1, ?2, ?2
+             WHERE NOT EXISTS (SELECT 1 FROM profiles)",
+            params![uuid::Uuid::new_v4().to_string(), now],
+        )?;
+
+        Ok(())
+    }
+
+    /// Creates a fresh tutorial stream, even if one was created before
+    pub fn reset_tutorial(&self) -> std::result::Result<Stream, DatabaseError> {
+        let stream =
+            self.create_stream_from_template(&templates::TUTORIAL, "default-user", None)?;
+        Self::set_meta(&*self.pool.get()?, TUTORIAL_CREATED_KEY, "1")?;
+
+        Ok(stream)
+    }
+
+    /// Reads a value from the `app_meta` key-value store
+    pub fn get_meta(conn: &Connection, key: &str) -> Result<Option<String>> {
+        conn.query_row(
+            "SELECT value FROM app_meta WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()
+    }
+
+    pub fn delete_meta(conn: &Connection, key: &str) -> Result<()> {
+        conn.execute("DELETE FROM app_meta WHERE key = ?1", params![key])?;
+        Ok(())
+    }
+
+    /// Inserts or overwrites a value in the `app_meta` key-value store
+    pub fn set_meta(conn: &Connection, key: &str, value: &str) -> Result<()> {
+        conn.execute(
+            "INSERT INTO app_meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// Moves a just-pinned stream to the end of its user's pinned streams
+    pub fn append_pin_order(conn: &Connection, stream_id: &str) -> Result<()> {
+        conn.execute(
+            "UPDATE streams SET pin_order = (
+                 SELECT COALESCE(MAX(p.pin_order) + 1, 0) FROM streams p
+                 WHERE p.user_id = streams.user_id AND p.pinned = 1 AND p.id != streams.id
+             )
+             WHERE id = ?1",
+            params![stream_id],
+        )?;
+        Ok(())
+    }
+
+    /// Mirrors a stream's tag array into `stream_tags`. `streams.tags` keeps the
+    /// display order; tag listing, filtering and renaming query the table.
+    pub fn sync_stream_tags(conn: &Connection, stream_id: &str, tags: &[String]) -> Result<()> {
+        conn.execute(
+            "DELETE FROM stream_tags WHERE stream_id = ?1",
+            params![stream_id],
+        )?;
+        let mut stmt = conn
+            .prepare_cached("INSERT OR IGNORE INTO stream_tags (stream_id, tag) VALUES (?1, ?2)")?;
+        for tag in tags {
+            stmt.execute(params![stream_id, tag])?;
+        }
+        Ok(())
+    }
+
+    /// Creates a stream seeded with a template's starter entries.
+    /// `title` defaults to the template name.
+    pub fn create_stream_from_template(
+        &self,
+        template: &StreamTemplate,
+        user_id: &str,
+        title: Option<&str>,
+    ) -> std::result::Result<Stream, DatabaseError> {
+        let mut conn = self.pool.get()?;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let stream = Stream {
+            id: uuid::Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            title: title.unwrap_or(template.name).to_string(),
+            description: Some(template.description.to_string()),
+            tags: template.tags.iter().map(|tag| tag.to_string()).collect(),
+            color: None,
+            pinned: template.pinned,
+            created_at: now,
+            updated_at: now,
+            archived: false,
+            default_profile_id: None,
+            word_goal: None,
+            locked: false,
+        };
+        let tags_json = serde_json::to_string(&stream.tags).unwrap_or_else(|_| "[]".to_string());
+
+        let tx = conn.transaction()?;
+
+        tx.execute(
+            "INSERT INTO streams (id, user_id, title, description, tags, pinned, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                stream.id,
+                stream.user_id,
+                stream.title,
+                stream.description,
+                tags_json,
+                if stream.pinned { 1 } else { 0 },
+                now,
+                now
+            ],
+        )?;
+
+        Self::sync_stream_tags(&tx, &stream.id, &stream.tags)?;
+
+        if stream.pinned {
+            Self::append_pin_order(&tx, &stream.id)?;
+        }
+
+        // Offset timestamps so the entries keep their order when sorted by time
+        for (i, content) in (template.entries)().into_iter().enumerate() {
+            let created_at = now + i as i64;
+            tx.execute(
                 "INSERT INTO entries (id, user_id, stream_id, role, content, sequence_id, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
                 params![
-                    entry2_id,
-                    "default-user",
-                    stream_id,
+                    uuid::Uuid::new_v4().to_string(),
+                    stream.user_id,
+                    stream.id,
                     "user",
-                    entry2_content.to_string(),
-                    2,
-                    now + 1,
-                    now + 1
+                    content.to_string(),
+                    i as i32 + 1,
+                    created_at,
+                    created_at
                 ],
             )?;
         }
 
-        Ok(())
+        tx.commit()?;
+
+        Ok(stream)
+    }
+
+    /// Deletes pending blocks created more than `max_age_ms` ago.
+    /// Returns how many were removed.
+    pub fn cleanup_expired_pending_blocks(
+        &self,
+        max_age_ms: i64,
+    ) -> std::result::Result<usize, DatabaseError> {
+        let conn = self.pool.get()?;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let removed = conn.execute(
+            "DELETE FROM pending_blocks WHERE created_at + ?1 < ?2",
+            params![max_age_ms, now],
+        )?;
+
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Opens a file-backed database in a fresh temporary directory
+    fn open_temp() -> (Database, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("kolam-ikan-test-{}", uuid::Uuid::new_v4()));
+        let db = Database::new(dir.clone(), None).expect("failed to open database");
+        (db, dir)
+    }
+
+    #[test]
+    fn connections_enable_wal_foreign_keys_and_busy_timeout() {
+        let (db, dir) = open_temp();
+        let conn = db.pool.get().unwrap();
+
+        let journal_mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        let foreign_keys: i64 = conn
+            .query_row("PRAGMA foreign_keys", [], |row| row.get(0))
+            .unwrap();
+        let busy_timeout: i64 = conn
+            .query_row("PRAGMA busy_timeout", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal_mode, "wal");
+        assert_eq!((foreign_keys, busy_timeout), (1, 5000));
+
+        drop(conn);
+        drop(db);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn change_key_keeps_pooled_connections_readable_and_retires_the_old_key() {
+        let dir = std::env::temp_dir().join(format!("kolam-ikan-test-{}", uuid::Uuid::new_v4()));
+        let db = Database::new(dir.clone(), Some("old".into())).unwrap();
+        db.create_default_profile().unwrap();
+
+        db.change_key("new").unwrap();
+
+        // Every connection the pool already opened must still read after the rekey
+        let connections: Vec<_> = (0..db.pool.max_size())
+            .map(|_| db.pool.get().unwrap())
+            .collect();
+        for conn in &connections {
+            let profiles: i64 = conn
+                .query_row("SELECT COUNT(*) FROM profiles", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(profiles, 1);
+        }
+        drop(connections);
+        drop(db);
+
+        assert!(Database::new(dir.clone(), Some("old".into())).is_err());
+        assert!(Database::new(dir.clone(), Some("new".into())).is_ok());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn stream_tags_migration_backfills_valid_tag_arrays() {
+        let db = Database::open_in_memory().unwrap();
+        let conn = db.pool.get().unwrap();
+        conn.execute_batch(
+            "DROP TABLE stream_tags;
+             INSERT INTO streams (id, user_id, title, tags, created_at, updated_at) VALUES
+                 ('a', 'u', 'A', '[\"work\", \"ideas\", \"work\", 7]', 1, 1),
+                 ('b', 'u', 'B', 'not json', 1, 1),
+                 ('c', 'u', 'C', '{\"tag\": \"x\"}', 1, 1),
+                 ('d', 'u', 'D', NULL, 1, 1);",
+        )
+        .unwrap();
+
+        let migration = MIGRATIONS.iter().find(|m| m.version == 17).unwrap();
+        (migration.up)(&conn).unwrap();
+
+        let rows: Vec<(String, String)> = conn
+            .prepare("SELECT stream_id, tag FROM stream_tags ORDER BY stream_id, tag")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                ("a".to_string(), "ideas".to_string()),
+                ("a".to_string(), "work".to_string())
+            ]
+        );
     }
 }
@@ -0,0 +1,107 @@
+use crate::models::DiffOp;
+
+// ============================================================
+// LINE DIFF
+// ============================================================
+
+/// Produces a line-level diff between two texts using a longest common subsequence table.
+/// Removals are emitted before additions when both occur at the same position.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffOp> {
+    let old_lines: Vec<&str> = old.lines().collect();
+    let new_lines: Vec<&str> = new.lines().collect();
+    let (n, m) = (old_lines.len(), new_lines.len());
+
+    // lcs[i][j] holds the LCS length of old_lines[i..] and new_lines[j..]
+    let mut lcs = vec![vec![0usize; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if old_lines[i] == new_lines[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut ops = Vec::with_capacity(n.max(m));
+    let (mut i, mut j) = (0, 0);
+    while i < n && j < m {
+        if old_lines[i] == new_lines[j] {
+            ops.push(op("unchanged", old_lines[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            ops.push(op("remove", old_lines[i]));
+            i += 1;
+        } else {
+            ops.push(op("add", new_lines[j]));
+            j += 1;
+        }
+    }
+    ops.extend(old_lines[i..].iter().map(|line| op("remove", line)));
+    ops.extend(new_lines[j..].iter().map(|line| op("add", line)));
+
+    ops
+}
+
+fn op(kind: &str, text: &str) -> DiffOp {
+    DiffOp {
+        kind: kind.to_string(),
+        text: text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ops(diff: &[DiffOp]) -> Vec<(&str, &str)> {
+        diff.iter()
+            .map(|op| (op.kind.as_str(), op.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn identical_texts_are_all_unchanged() {
+        let diff = diff_lines("a\nb", "a\nb");
+        assert_eq!(ops(&diff), vec![("unchanged", "a"), ("unchanged", "b")]);
+        assert!(diff_lines("", "").is_empty());
+    }
+
+    #[test]
+    fn insertions_are_added_in_place() {
+        let diff = diff_lines("a\nc", "a\nb\nc\nd");
+        assert_eq!(
+            ops(&diff),
+            vec![
+                ("unchanged", "a"),
+                ("add", "b"),
+                ("unchanged", "c"),
+                ("add", "d")
+            ]
+        );
+    }
+
+    #[test]
+    fn deletions_are_removed_in_place() {
+        let diff = diff_lines("a\nb\nc", "c");
+        assert_eq!(
+            ops(&diff),
+            vec![("remove", "a"), ("remove", "b"), ("unchanged", "c")]
+        );
+    }
+
+    #[test]
+    fn replacements_remove_before_adding() {
+        let diff = diff_lines("a\nold\nz", "a\nnew\nz");
+        assert_eq!(
+            ops(&diff),
+            vec![
+                ("unchanged", "a"),
+                ("remove", "old"),
+                ("add", "new"),
+                ("unchanged", "z")
+            ]
+        );
+    }
+}
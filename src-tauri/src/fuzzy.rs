@@ -0,0 +1,97 @@
+// ============================================================
+// FUZZY MATCHING
+// ============================================================
+
+/// Edit distance between two strings, counted in characters
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // Two rolling rows of the classic dynamic-programming table
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(ca != cb);
+            curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+
+    prev[b.len()]
+}
+
+/// Finds the word in `text` closest to `term`, ignoring case.
+/// Returns the distance and the word, or `None` if nothing is within `max_distance`.
+pub fn closest_word<'a>(
+    term: &str,
+    text: &'a str,
+    max_distance: usize,
+) -> Option<(usize, &'a str)> {
+    let term = term.to_lowercase();
+    let term_len = term.chars().count();
+
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        // Words whose length alone puts them out of range can be skipped
+        .filter(|word| word.chars().count().abs_diff(term_len) <= max_distance)
+        .map(|word| (levenshtein(&term, &word.to_lowercase()), word))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+}
+
+/// Substrings used to fetch fuzzy-search candidates with LIKE. Each edit breaks at most
+/// `size` of the term's `size`-character windows, so a word within `max_distance` edits
+/// still contains one of them while `size <= len / (max_distance + 1)`. The largest such
+/// size up to 3 is used. `None` means the term is so short any word could match it.
+pub fn candidate_fragments(term: &str, max_distance: usize) -> Option<Vec<String>> {
+    let chars: Vec<char> = term.to_lowercase().chars().collect();
+    let size = (chars.len() / (max_distance + 1)).min(3);
+    if size == 0 {
+        return None;
+    }
+
+    let mut fragments: Vec<String> = chars.windows(size).map(|w| w.iter().collect()).collect();
+    fragments.sort();
+    fragments.dedup();
+    Some(fragments)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein_counts_character_edits() {
+        assert_eq!(levenshtein("organize", "organise"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("teh", "the"), 2);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("café", "cafe"), 1);
+    }
+
+    #[test]
+    fn closest_word_ignores_case_and_respects_max_distance() {
+        let text = "Then the Organise step, later";
+
+        assert_eq!(closest_word("organize", text, 1), Some((1, "Organise")));
+        assert_eq!(closest_word("THE", text, 0), Some((0, "the")));
+        assert_eq!(closest_word("lattice", text, 1), None);
+    }
+
+    #[test]
+    fn candidate_fragments_shrink_so_a_typo_still_shares_one() {
+        let shared = |term: &str, word: &str, max_distance: usize| {
+            candidate_fragments(term, max_distance)
+                .map(|fragments| fragments.iter().any(|f| word.contains(f.as_str())))
+        };
+
+        assert_eq!(shared("organize", "organise", 1), Some(true));
+        assert_eq!(shared("teh", "the", 2), Some(true));
+        assert_eq!(shared("cta", "cat", 2), Some(true));
+        assert_eq!(shared("form", "from", 2), Some(true));
+        // Three edits can turn a three-letter term into any three-letter word
+        assert_eq!(candidate_fragments("abc", 3), None);
+    }
+}
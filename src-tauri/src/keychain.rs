@@ -0,0 +1,25 @@
+// ============================================================
+// OS KEYCHAIN
+// ============================================================
+
+/// Keychain service name; matches the bundle identifier in tauri.conf.json
+const SERVICE: &str = "com.merapilabs.kolamikan";
+const DATABASE_KEY_ACCOUNT: &str = "database-key";
+
+fn database_key_entry() -> keyring::Result<keyring::Entry> {
+    keyring::Entry::new(SERVICE, DATABASE_KEY_ACCOUNT)
+}
+
+/// The key saved by `store_database_key`, or `None` if there isn't one yet
+pub fn load_database_key() -> keyring::Result<Option<String>> {
+    match database_key_entry()?.get_password() {
+        Ok(key) => Ok(Some(key)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Saves the database key so later launches unlock without asking
+pub fn store_database_key(key: &str) -> keyring::Result<()> {
+    database_key_entry()?.set_password(key)
+}
@@ -2,9 +2,17 @@
 // Suppress unexpected_cfgs from objc macro (uses legacy cfg conditions)
 #![allow(deprecated, unexpected_cfgs)]
 
+mod bridge;
 mod commands;
+mod content;
 mod database;
+mod diff;
+mod fuzzy;
+#[cfg(feature = "encryption")]
+mod keychain;
 mod models;
+mod providers;
+mod templates;
 
 use database::Database;
 use tauri::Manager;
@@ -23,11 +31,16 @@ use cocoa::foundation::NSRect;
 #[cfg(target_os = "macos")]
 use objc::{msg_send, sel, sel_impl};
 
+/// Traffic light offset used until one is saved with `set_traffic_light_position`.
+/// Similar to Obsidian's trafficLightPosition.
+#[cfg(target_os = "macos")]
+const DEFAULT_TRAFFIC_LIGHT_POSITION: (f64, f64) = (20.0, 20.0);
+
 /// Repositions macOS traffic light buttons (close, minimize, zoom)
 /// to the specified x, y coordinates from the top-left of the window
 #[cfg(target_os = "macos")]
 #[allow(deprecated)]
-unsafe fn reposition_traffic_lights(ns_window: id, x: f64, y: f64) {
+pub(crate) unsafe fn reposition_traffic_lights(ns_window: id, x: f64, y: f64) {
     // Get the content view to calculate proper positioning
     let content_view: id = msg_send![ns_window, contentView];
     let _content_frame: NSRect = msg_send![content_view, frame];
@@ -64,14 +77,40 @@ unsafe fn reposition_traffic_lights(ns_window: id, x: f64, y: f64) {
     }
 }
 
-/// Sets up a window delegate to handle resize events and reposition traffic lights
+/// Repositions the traffic lights now and after every resize, since AppKit lays out the
+/// title bar again and the buttons drift back. Listening to Tauri's window events avoids
+/// a custom NSWindowDelegate, which would conflict with Tauri's existing delegate.
 #[cfg(target_os = "macos")]
-#[allow(deprecated)]
-unsafe fn setup_traffic_light_observer(ns_window: id, x: f64, y: f64) {
-    // For now, we'll just reposition on initial setup
-    // A full implementation would require creating a proper NSWindowDelegate
-    // which is complex and can cause issues with Tauri's existing delegate
-    reposition_traffic_lights(ns_window, x, y);
+fn setup_traffic_light_observer(window: &tauri::WebviewWindow) {
+    reposition_traffic_lights_from_settings(window);
+
+    let handle = window.clone();
+    window.on_window_event(move |event| {
+        if let tauri::WindowEvent::Resized(_) = event {
+            // Nothing to lay out while the window sits in the Dock
+            if handle.is_minimized().unwrap_or(false) {
+                return;
+            }
+            reposition_traffic_lights_from_settings(&handle);
+        }
+    });
+}
+
+/// Applies the offset saved with `set_traffic_light_position`, or the default
+/// (also used while an encrypted database is still locked)
+#[cfg(target_os = "macos")]
+fn reposition_traffic_lights_from_settings(window: &tauri::WebviewWindow) {
+    let (x, y) = window
+        .try_state::<Database>()
+        .and_then(|db| commands::stored_traffic_light_position(&db))
+        .unwrap_or(DEFAULT_TRAFFIC_LIGHT_POSITION);
+
+    // Use raw window handle to get NSWindow
+    if let Ok(ns_window) = window.ns_window() {
+        unsafe {
+            reposition_traffic_lights(ns_window as id, x, y);
+        }
+    }
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -96,30 +135,34 @@ pub fn run() {
                 .app_data_dir()
                 .expect("Failed to get app data directory");
 
-            // Initialize database
-            let db = Database::new(app_data_dir).expect("Failed to initialize database");
-
-            // Create tutorial stream on first run
-            db.create_tutorial_stream()
-                .expect("Failed to create tutorial stream");
+            // Encrypted builds unlock with the key saved in the OS keychain. Without one, or
+            // if it no longer opens the database, the app starts locked until the frontend
+            // supplies a key through `set_database_key`.
+            #[cfg(feature = "encryption")]
+            let db = match keychain::load_database_key() {
+                Ok(Some(key)) => Database::new(app_data_dir, Some(key))
+                    .map_err(|e| log::warn!("Failed to unlock database: {}", e))
+                    .ok(),
+                Ok(None) => None,
+                Err(e) => {
+                    log::warn!("Failed to read database key from keychain: {}", e);
+                    None
+                }
+            };
+            #[cfg(not(feature = "encryption"))]
+            let db =
+                Some(Database::new(app_data_dir, None).expect("Failed to initialize database"));
 
-            // Manage database state
-            app.manage(db);
+            // Create the default profile and tutorial stream on first run, then manage state
+            if let Some(db) = db {
+                db.run_startup_tasks().expect("Failed to prepare database");
+                app.manage(db);
+            }
 
-            // Reposition macOS traffic light buttons
+            // Reposition macOS traffic light buttons, and keep them in place on resize
             #[cfg(target_os = "macos")]
-            {
-                let window = app.get_webview_window("main");
-                if let Some(window) = window {
-                    // Use raw window handle to get NSWindow
-                    if let Ok(ns_window) = window.ns_window() {
-                        unsafe {
-                            // Position traffic lights at (20, 20) from top-left
-                            // Similar to Obsidian's trafficLightPosition
-                            setup_traffic_light_observer(ns_window as id, 20.0, 20.0);
-                        }
-                    }
-                }
+            if let Some(window) = app.get_webview_window("main") {
+                setup_traffic_light_observer(&window);
             }
 
             Ok(())
@@ -132,38 +175,144 @@ pub fn run() {
             commands::update_profile,
             commands::delete_profile,
             commands::get_default_profile,
+            commands::set_default_profile,
             commands::get_profile_entry_count,
+            commands::reassign_profile_entries,
             // Stream commands
             commands::create_stream,
             commands::get_all_streams,
+            commands::get_streams_by_tag,
             commands::get_stream_details,
+            commands::get_last_opened_stream,
+            commands::set_last_opened_stream,
             commands::delete_stream,
             commands::update_stream,
+            commands::update_stream_color,
+            commands::reorder_pinned_streams,
+            commands::set_stream_default_profile,
+            commands::set_stream_goal,
+            commands::set_stream_locked,
+            commands::archive_stream,
+            commands::unarchive_stream,
+            commands::duplicate_stream,
+            commands::create_stream_from_template,
+            commands::get_available_templates,
+            commands::reset_tutorial,
+            commands::merge_streams,
+            commands::split_stream,
+            commands::get_stream_stats,
+            commands::get_session_word_delta,
+            commands::get_stream_role_breakdown,
+            commands::get_workspace_stats,
+            // Tag commands
+            commands::get_all_tags,
+            commands::rename_tag,
+            commands::delete_tag,
             // Entry commands
             commands::create_entry,
+            commands::create_entry_at,
+            commands::bulk_create_entries,
+            commands::get_entry,
+            commands::get_entries_by_ids,
+            commands::get_adjacent_entries,
             commands::update_entry_content,
+            commands::append_paragraph_to_entry,
+            commands::append_text_to_last_node,
             commands::update_entry_profile,
             commands::bulk_update_entry_profile,
+            commands::reclassify_entry,
             commands::toggle_entry_staging,
+            commands::toggle_entry_pin,
+            commands::update_entry_appearance,
             commands::delete_entry,
             commands::bulk_delete_entries,
+            commands::delete_entries,
+            commands::find_empty_entries,
+            commands::delete_empty_entries,
+            commands::restore_entry,
+            commands::purge_deleted_entries,
+            commands::get_deleted_entries,
             commands::get_staged_entries,
+            commands::get_pinned_entries,
+            commands::get_children_of_entry,
+            commands::get_context_graph,
+            commands::find_duplicate_entries,
+            commands::merge_duplicate_entries,
             commands::clear_all_staging,
+            commands::get_entry_preview,
+            commands::reorder_entries,
+            commands::compact_sequence_ids,
+            commands::move_entry,
             // Version commands
             commands::commit_entry_version,
+            commands::commit_if_changed,
+            commands::flush_autosave_versions,
             commands::get_entry_versions,
             commands::get_latest_version,
             commands::get_version_by_number,
             commands::revert_to_version,
+            commands::toggle_version_bookmark,
+            commands::revert_to_bookmark,
+            commands::get_working_snapshot,
+            commands::label_version,
+            commands::get_labeled_versions,
+            commands::diff_entry_versions,
+            commands::get_uncommitted_diff,
+            commands::prune_entry_versions,
+            commands::prune_all_versions,
+            // Spotlight commands
+            commands::create_spotlight,
+            commands::get_spotlights_for_entry,
+            commands::delete_spotlight,
+            // Link commands
+            commands::create_entry_link,
+            commands::delete_entry_link,
+            commands::get_linked_entries,
             // Bridge commands
             commands::generate_bridge_key,
             commands::validate_bridge_key,
             commands::extract_bridge_key,
+            commands::assemble_bridge_payload,
+            commands::get_bridge_marker_style,
+            commands::set_bridge_marker_style,
+            commands::estimate_context_tokens,
             commands::create_pending_block,
             commands::get_pending_block,
+            commands::get_all_pending_blocks,
+            commands::get_active_bridge_keys,
             commands::delete_pending_block,
+            commands::resolve_pending_block,
+            commands::cleanup_expired_pending_blocks,
+            commands::get_known_providers,
             // Search commands
             commands::search_entries,
+            commands::search_entries_fuzzy,
+            commands::search_entries_filtered,
+            commands::search_versions,
+            commands::get_backlinks,
+            commands::get_recent_entries,
+            // Export/import commands
+            commands::export_stream,
+            commands::import_stream,
+            commands::export_stream_markdown,
+            commands::export_all,
+            commands::import_all,
+            // Undo commands
+            commands::undo_last_operation,
+            // Settings commands
+            commands::get_meta,
+            commands::set_meta,
+            // Window commands
+            commands::set_traffic_light_position,
+            // Database commands
+            commands::is_database_locked,
+            commands::set_database_key,
+            commands::get_database_info,
+            commands::optimize_database,
+            commands::backup_database,
+            commands::restore_database,
+            commands::find_orphaned_entries,
+            commands::repair_orphaned_entries,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
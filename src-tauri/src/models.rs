@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 // ============================================================
 // PROFILE TYPES
@@ -40,6 +41,8 @@ pub struct UpdateProfileInput {
     pub initials: Option<String>,
     pub bio: Option<String>,
     pub avatar_url: Option<String>,
+    /// Only `Some(true)` is acted on; unsetting happens implicitly when another profile becomes default
+    pub is_default: Option<bool>,
 }
 
 // ============================================================
@@ -58,6 +61,17 @@ pub struct Stream {
     pub pinned: bool,
     pub created_at: i64,
     pub updated_at: i64,
+    #[serde(default)]
+    pub archived: bool,
+    /// Profile given to new entries that don't name one
+    #[serde(default)]
+    pub default_profile_id: Option<String>,
+    /// Target word count, shown as progress in the stream stats
+    #[serde(default)]
+    pub word_goal: Option<i64>,
+    /// Locked streams reject entry edits until unlocked
+    #[serde(default)]
+    pub locked: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -71,6 +85,73 @@ pub struct StreamMetadata {
     pub pinned: bool,
     pub color: Option<String>,
     pub tags: Vec<String>,
+    pub archived: bool,
+}
+
+/// A stream template as offered in the picker
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateInfo {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+}
+
+/// An AI provider and its common model ids, for the bridge dropdown
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProviderInfo {
+    pub id: String,
+    pub name: String,
+    pub models: Vec<String>,
+}
+
+/// Writing statistics for a stream. Word and character counts cover
+/// non-staged entries, and AI entries only when requested.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct StreamStats {
+    pub entry_count: i64,
+    pub word_count: i64,
+    pub character_count: i64,
+    pub version_count: i64,
+    pub first_entry_at: Option<i64>,
+    pub last_updated_at: Option<i64>,
+    /// The stream's word goal, if one is set
+    pub goal: Option<i64>,
+    /// `word_count / goal`, capped at 1.0
+    pub progress_ratio: Option<f64>,
+}
+
+/// Totals across every stream. `words_by_day` pairs the UTC day start
+/// (ms) with words written in non-AI entries created that day, oldest first.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceStats {
+    pub total_streams: i64,
+    pub total_entries: i64,
+    pub total_words: i64,
+    pub total_ai_entries: i64,
+    pub total_commits: i64,
+    pub words_by_day: Vec<(i64, i64)>,
+}
+
+/// Who wrote a stream's entries. `by_profile` holds `(profile_id, name, count)`, largest first;
+/// entries without a profile are grouped under a `None` id named "Unassigned".
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RoleBreakdown {
+    pub user_count: i64,
+    pub ai_count: i64,
+    pub by_profile: Vec<(Option<String>, String, i64)>,
+}
+
+/// A distinct stream tag and how many streams use it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -99,6 +180,15 @@ pub struct Entry {
     pub ai_metadata: Option<AiMetadata>,
     pub created_at: i64,
     pub updated_at: i64,
+    /// Durable bookmark; unlike staging it survives `clear_all_staging`
+    #[serde(default)]
+    pub is_pinned: bool,
+    /// Optional highlight color for skimming long streams
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Optional category such as "idea", "todo" or "reference"
+    #[serde(default)]
+    pub label: Option<String>,
     // Optional: Include profile data when fetched with join
     #[serde(skip_serializing_if = "Option::is_none")]
     pub profile: Option<Profile>,
@@ -113,9 +203,39 @@ pub struct EntryVersion {
     pub content_snapshot: serde_json::Value,
     pub commit_message: Option<String>,
     pub committed_at: i64,
+    /// Optional milestone name such as "first draft"
+    #[serde(default)]
+    pub label: Option<String>,
+    /// Marked as a known-good snapshot for `revert_to_bookmark`
+    #[serde(default)]
+    pub bookmarked: bool,
+}
+
+/// Neighbors of an entry in sequence order; `None` at either end of the stream
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Adjacent {
+    pub prev: Option<Entry>,
+    pub next: Option<Entry>,
+}
+
+/// How context flowed between a stream's live entries. `nodes` are entry ids in
+/// sequence order; each edge goes from a parent context id to the entry it fed.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ContextGraph {
+    pub nodes: Vec<String>,
+    pub edges: Vec<(String, String)>,
+}
+
+/// One line of a version diff. `kind` is "add", "remove" or "unchanged".
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffOp {
+    pub kind: String,
+    pub text: String,
 }
 
-#[allow(dead_code)]
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Spotlight {
@@ -127,6 +247,84 @@ pub struct Spotlight {
     pub end_offset: i32,
 }
 
+/// Rough token count for a set of entries about to be sent to an AI.
+/// `per_entry` pairs each entry id with its approximate tokens, in request order.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenEstimate {
+    pub char_count: usize,
+    pub approx_tokens: usize,
+    pub per_entry: Vec<(String, usize)>,
+    /// Context window of the requested model, if it is known
+    pub context_window: Option<usize>,
+    pub exceeds_window: bool,
+}
+
+/// What the AI is asked to do with the staged context
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Directive {
+    /// Refactor the dumped thoughts
+    Dump,
+    /// Find gaps and weak points
+    Critique,
+    /// Expand on the ideas
+    Generate,
+}
+
+impl Directive {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Dump => "DUMP",
+            Self::Critique => "CRITIQUE",
+            Self::Generate => "GENERATE",
+        }
+    }
+}
+
+impl std::str::FromStr for Directive {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "DUMP" => Ok(Self::Dump),
+            "CRITIQUE" => Ok(Self::Critique),
+            "GENERATE" => Ok(Self::Generate),
+            _ => Err(format!(
+                "Unknown directive '{}'; expected DUMP, CRITIQUE or GENERATE",
+                s
+            )
+            .into()),
+        }
+    }
+}
+
+impl rusqlite::types::ToSql for Directive {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl rusqlite::types::FromSql for Directive {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e: AppError| rusqlite::types::FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// How the bridge key is written into payloads and found in pasted replies
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeMarkerStyle {
+    /// `<!-- bridge:KEY -->`
+    #[default]
+    HtmlComment,
+    /// `[[bridge:KEY]]`, for tools that strip HTML comments
+    WikiLink,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct PendingBlock {
@@ -135,8 +333,22 @@ pub struct PendingBlock {
     pub stream_id: String,
     pub bridge_key: String,
     pub staged_context_ids: Vec<String>,
-    pub directive: String,
+    pub directive: Directive,
+    pub created_at: i64,
+}
+
+/// A bridge still waiting for its AI response, with the stream it belongs to
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeKeyInfo {
+    pub pending_block_id: String,
+    pub bridge_key: String,
+    pub stream_id: String,
+    pub stream_title: String,
+    pub directive: Directive,
     pub created_at: i64,
+    /// Milliseconds since the block was created, as of the call
+    pub age_ms: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -161,6 +373,11 @@ pub struct CreateEntryInput {
     pub parent_context_ids: Option<Vec<String>>,
     pub insert_after_id: Option<String>,
     pub insert_before_id: Option<String>,
+    /// Original timestamps for imported notes; the current time is used when omitted
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    #[serde(default)]
+    pub updated_at: Option<i64>,
 }
 
 #[allow(dead_code)]
@@ -178,15 +395,204 @@ pub struct StreamWithEntries {
     pub entries: Vec<Entry>,
 }
 
-#[allow(dead_code)]
+/// A directed link between two entries, possibly in different streams
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryLink {
+    pub source_entry_id: String,
+    pub target_entry_id: String,
+    pub created_at: i64,
+}
+
+/// The entry at the other end of a link, with its stream for rendering references
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkedEntry {
+    pub entry: Entry,
+    pub stream_title: String,
+    /// When the link was created
+    pub linked_at: i64,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkedEntries {
+    /// Entries this entry links to
+    pub outbound: Vec<LinkedEntry>,
+    /// Entries that link to this entry
+    pub inbound: Vec<LinkedEntry>,
+}
+
+// ============================================================
+// EXPORT TYPES
+// ============================================================
+
+/// Bump when the export shape changes so importers can adapt
+pub const EXPORT_SCHEMA_VERSION: i32 = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedStream {
+    pub schema_version: i32,
+    pub exported_at: i64,
+    pub stream: Stream,
+    pub entries: Vec<ExportedEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportedEntry {
+    pub entry: Entry,
+    pub versions: Vec<EntryVersion>,
+    pub spotlights: Vec<Spotlight>,
+}
+
+/// Bump when the workspace archive layout changes
+pub const WORKSPACE_ARCHIVE_VERSION: i32 = 1;
+
+/// `manifest.json` at the root of a workspace archive written by `export_all`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceManifest {
+    pub archive_version: i32,
+    /// Database schema version of the exporting app
+    pub schema_version: i64,
+    pub exported_at: i64,
+    pub profile_count: usize,
+    pub stream_count: usize,
+    pub entry_count: usize,
+    /// Paths of the stream files inside the archive, in export order
+    pub streams: Vec<String>,
+}
+
+// ============================================================
+// UNDO TYPES
+// ============================================================
+
+/// What a destructive command removed, stored in `undo_log` so it can be put back
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum UndoSnapshot {
+    /// The whole stream, including trashed entries; `deleted_at` maps those entry ids
+    /// to when they were trashed
+    DeleteStream {
+        stream: Box<ExportedStream>,
+        deleted_at: HashMap<String, i64>,
+    },
+    /// Soft-deleted entries as `(entry_id, was_staged)`
+    DeleteEntries { entries: Vec<(String, bool)> },
+    /// Entries that were staged before `clear_all_staging`
+    ClearStaging { entry_ids: Vec<String> },
+}
+
+impl UndoSnapshot {
+    /// Value stored in `undo_log.operation`
+    pub fn operation(&self) -> &'static str {
+        match self {
+            Self::DeleteStream { .. } => "delete_stream",
+            Self::DeleteEntries { .. } => "delete_entries",
+            Self::ClearStaging { .. } => "clear_staging",
+        }
+    }
+}
+
+/// A search hit with the stream it belongs to and a short excerpt
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub entry: Entry,
+    pub stream_id: String,
+    pub stream_title: String,
+    /// Plain text around the first matched term
+    pub snippet: String,
+    /// `snippet` with each matched term wrapped in `**`
+    pub highlighted_snippet: String,
+}
+
+/// A version whose snapshot matched `search_versions`, with the entry and stream it belongs to
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionSearchResult {
+    pub version: EntryVersion,
+    pub entry_id: String,
+    pub stream_id: String,
+    pub stream_title: String,
+    /// Snapshot text around the first matched term
+    pub snippet: String,
+    /// `snippet` with each matched term wrapped in `**`
+    pub highlighted_snippet: String,
+}
+
+/// Optional narrowing for `search_entries_filtered`; unset fields don't filter.
+/// Timestamps are epoch milliseconds, `created_after` inclusive and `created_before` exclusive.
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchFilters {
+    pub stream_id: Option<String>,
+    /// "user" or "ai"
+    pub role: Option<String>,
+    pub profile_id: Option<String>,
+    pub created_after: Option<i64>,
+    pub created_before: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    pub total_count: i64,
+    pub has_more: bool,
+}
+
+// ============================================================
+// DATABASE TYPES
+// ============================================================
+
+/// Health of the SQLite file, for diagnostics and bug reports
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbInfo {
+    pub path: String,
+    pub page_count: i64,
+    pub page_size: i64,
+    pub size_bytes: i64,
+    pub journal_mode: String,
+    /// Highest migration applied, from `schema_version`
+    pub user_version: i64,
+    /// Whether `PRAGMA integrity_check` reported "ok"
+    pub integrity_ok: bool,
+}
+
+/// File size of the database before and after `optimize_database`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OptimizeReport {
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+}
+
+/// Entries whose references point at rows that no longer exist, by entry id
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrphanReport {
+    pub missing_stream: Vec<String>,
+    pub missing_profile: Vec<String>,
+}
+
+// ============================================================
+// ERROR TYPES
+// ============================================================
+
+/// Error returned by every command. `code` is stable for the frontend to branch on
+/// (e.g. "NOT_FOUND", "DATABASE_BUSY"); `message` is for display.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct AppError {
     pub code: String,
     pub message: String,
     pub details: Option<String>,
 }
 
-#[allow(dead_code)]
 impl AppError {
     pub fn new(code: &str, message: &str) -> Self {
         Self {
@@ -203,4 +609,92 @@ impl AppError {
             details: Some(details.to_string()),
         }
     }
+
+    /// For single-row lookups: a missing row becomes NOT_FOUND with a readable
+    /// `message` instead of "Query returned no rows"; other errors convert as usual.
+    pub fn from_lookup(e: rusqlite::Error, message: impl FnOnce() -> String) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => Self::new("NOT_FOUND", &message()),
+            e => e.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// Validation messages built in commands, such as "Tag name cannot be empty"
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        Self::new("INVALID_INPUT", &message)
+    }
+}
+
+impl From<&str> for AppError {
+    fn from(message: &str) -> Self {
+        Self::new("INVALID_INPUT", message)
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        let code = match &e {
+            rusqlite::Error::QueryReturnedNoRows => "NOT_FOUND",
+            rusqlite::Error::SqliteFailure(failure, _)
+                if matches!(
+                    failure.code,
+                    rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked
+                ) =>
+            {
+                "DATABASE_BUSY"
+            }
+            _ => "DATABASE",
+        };
+        Self::new(code, &e.to_string())
+    }
+}
+
+impl From<r2d2::Error> for AppError {
+    fn from(e: r2d2::Error) -> Self {
+        Self::new("DATABASE_BUSY", &e.to_string())
+    }
+}
+
+impl From<crate::database::DatabaseError> for AppError {
+    fn from(e: crate::database::DatabaseError) -> Self {
+        match e {
+            crate::database::DatabaseError::Sqlite(e) => e.into(),
+            crate::database::DatabaseError::Pool(e) => e.into(),
+            e => Self::new("DATABASE", &e.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::new("SERIALIZATION", &e.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        Self::new("IO", &e.to_string())
+    }
+}
+
+impl From<zip::result::ZipError> for AppError {
+    fn from(e: zip::result::ZipError) -> Self {
+        Self::new("ARCHIVE", &e.to_string())
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for AppError {
+    fn from(e: std::sync::PoisonError<T>) -> Self {
+        Self::new("LOCK_POISONED", &e.to_string())
+    }
 }
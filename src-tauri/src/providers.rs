@@ -0,0 +1,49 @@
+// ============================================================
+// AI PROVIDERS
+// ============================================================
+
+/// An AI provider the bridge knows about, with the model ids it commonly reports
+pub struct Provider {
+    /// Matches `AiMetadata.provider` and the frontend's `AIProvider`
+    pub id: &'static str,
+    pub name: &'static str,
+    pub models: &'static [&'static str],
+}
+
+/// Providers offered in the bridge dropdown, in display order
+pub const PROVIDERS: &[Provider] = &[
+    Provider {
+        id: "openai",
+        name: "OpenAI",
+        models: &[
+            "gpt-4o",
+            "gpt-4o-mini",
+            "gpt-4-turbo",
+            "o1",
+            "o1-mini",
+            "o3-mini",
+        ],
+    },
+    Provider {
+        id: "anthropic",
+        name: "Anthropic",
+        models: &[
+            "claude-3-5-sonnet",
+            "claude-3-5-haiku",
+            "claude-3-opus",
+            "claude-3-haiku",
+        ],
+    },
+    Provider {
+        id: "google",
+        name: "Google",
+        models: &["gemini-2.0-flash", "gemini-1.5-pro", "gemini-1.5-flash"],
+    },
+];
+
+/// Looks up a provider by id, ignoring case
+pub fn find(id: &str) -> Option<&'static Provider> {
+    PROVIDERS
+        .iter()
+        .find(|provider| provider.id.eq_ignore_ascii_case(id))
+}
@@ -0,0 +1,174 @@
+use crate::content::paragraph;
+use serde_json::{json, Value};
+
+// ============================================================
+// STREAM TEMPLATES
+// ============================================================
+
+/// A stream layout that new streams can be seeded from
+pub struct StreamTemplate {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub tags: &'static [&'static str],
+    pub pinned: bool,
+    /// Starter entries as ProseMirror documents, in stream order
+    pub entries: fn() -> Vec<Value>,
+}
+
+/// Templates offered in the stream picker
+pub const TEMPLATES: &[StreamTemplate] = &[
+    StreamTemplate {
+        id: "daily-journal",
+        name: "Daily Journal",
+        description: "A page a day: what happened, how it felt, what comes next.",
+        tags: &["journal"],
+        pinned: false,
+        entries: || {
+            vec![
+                doc(vec![heading(2, "Today"), paragraph("What happened today?")]),
+                doc(vec![
+                    heading(2, "Reflections"),
+                    paragraph("How did it feel, and what did you notice?"),
+                ]),
+                doc(vec![
+                    heading(2, "Tomorrow"),
+                    paragraph("One thing to carry forward."),
+                ]),
+            ]
+        },
+    },
+    StreamTemplate {
+        id: "decision-log",
+        name: "Decision Log",
+        description: "Record a decision with its context, options and outcome.",
+        tags: &["decision"],
+        pinned: false,
+        entries: || {
+            vec![
+                doc(vec![
+                    heading(2, "Context"),
+                    paragraph("What needs deciding, and why now?"),
+                ]),
+                doc(vec![
+                    heading(2, "Options"),
+                    paragraph("List the options with their trade-offs."),
+                ]),
+                doc(vec![
+                    heading(2, "Decision"),
+                    paragraph("What was chosen, and what would change your mind?"),
+                ]),
+            ]
+        },
+    },
+    StreamTemplate {
+        id: "brainstorm",
+        name: "Brainstorm",
+        description: "Dump ideas quickly, then stage the best ones for the AI to expand.",
+        tags: &["brainstorm"],
+        pinned: false,
+        entries: || {
+            vec![
+                doc(vec![
+                    heading(2, "Prompt"),
+                    paragraph("What are you brainstorming about?"),
+                ]),
+                doc(vec![paragraph("")]),
+            ]
+        },
+    },
+];
+
+/// The stream created on first run
+pub const TUTORIAL: StreamTemplate = StreamTemplate {
+    id: "tutorial",
+    name: "Welcome to Kolam Ikan",
+    description: "Your first stream - feel free to experiment here!",
+    tags: &["tutorial"],
+    pinned: true,
+    entries: || {
+        vec![
+            json!({
+                "type": "doc",
+                "content": [
+                    {
+                        "type": "heading",
+                        "attrs": { "level": 1 },
+                        "content": [
+                            { "type": "text", "text": "Welcome! 👋" }
+                        ]
+                    },
+                    {
+                        "type": "paragraph",
+                        "content": [
+                            { "type": "text", "text": "Kolam Ikan is your personal thinking space. Here's how it works:" }
+                        ]
+                    },
+                    {
+                        "type": "orderedList",
+                        "content": [
+                            {
+                                "type": "listItem",
+                                "content": [{
+                                    "type": "paragraph",
+                                    "content": [
+                                        { "type": "text", "marks": [{ "type": "bold" }], "text": "Write freely" },
+                                        { "type": "text", "text": " - Just start typing your thoughts." }
+                                    ]
+                                }]
+                            },
+                            {
+                                "type": "listItem",
+                                "content": [{
+                                    "type": "paragraph",
+                                    "content": [
+                                        { "type": "text", "marks": [{ "type": "bold" }], "text": "Stage context" },
+                                        { "type": "text", "text": " - Check the boxes next to entries you want to send to AI." }
+                                    ]
+                                }]
+                            },
+                            {
+                                "type": "listItem",
+                                "content": [{
+                                    "type": "paragraph",
+                                    "content": [
+                                        { "type": "text", "marks": [{ "type": "bold" }], "text": "Choose a directive" },
+                                        { "type": "text", "text": " - DUMP (refactor), CRITIQUE (find gaps), or GENERATE (expand)." }
+                                    ]
+                                }]
+                            },
+                            {
+                                "type": "listItem",
+                                "content": [{
+                                    "type": "paragraph",
+                                    "content": [
+                                        { "type": "text", "marks": [{ "type": "bold" }], "text": "Copy & paste" },
+                                        { "type": "text", "text": " - Use the bridge buttons to connect with ChatGPT, Claude, or Gemini." }
+                                    ]
+                                }]
+                            }
+                        ]
+                    }
+                ]
+            }),
+            // An empty entry to start typing in
+            doc(vec![paragraph("")]),
+        ]
+    },
+};
+
+pub fn find(id: &str) -> Option<&'static StreamTemplate> {
+    TEMPLATES.iter().find(|template| template.id == id)
+}
+
+fn doc(content: Vec<Value>) -> Value {
+    json!({ "type": "doc", "content": content })
+}
+
+fn heading(level: u8, text: &str) -> Value {
+    json!({
+        "type": "heading",
+        "attrs": { "level": level },
+        "content": [{ "type": "text", "text": text }]
+    })
+}
@@ -85,6 +85,8 @@ export function Sidebar() {
   // Fetch stream details when active stream changes
   useEffect(() => {
     if (activeStreamId) {
+      // Remember it so the next launch reopens this stream
+      api.setLastOpenedStream(activeStreamId).catch(console.error);
       setLoadingEntries(true);
       api
         .getStreamDetails(activeStreamId)
@@ -4,16 +4,42 @@ import { devLog } from "@/lib/devLogger";
 const isTauri = () =>
   typeof window !== "undefined" && !!(window as any).__TAURI_INTERNALS__;
 import type {
+  AppError,
   Stream,
   StreamMetadata,
   Entry,
+  EntryRole,
+  Adjacent,
+  EntryLink,
+  LinkedEntries,
   EntryVersion,
+  DiffOp,
   PendingBlock,
+  BridgeKeyInfo,
+  AiMetadata,
+  DirectiveType,
+  BridgeMarkerStyle,
   CreateStreamInput,
   CreateEntryInput,
   Profile,
   CreateProfileInput,
   UpdateProfileInput,
+  SearchFilters,
+  SearchPage,
+  SearchResult,
+  VersionSearchResult,
+  TagCount,
+  StreamStats,
+  RoleBreakdown,
+  ContextGraph,
+  TemplateInfo,
+  ProviderInfo,
+  WorkspaceStats,
+  TokenEstimate,
+  ModelType,
+  DbInfo,
+  OptimizeReport,
+  OrphanReport,
 } from "@/types";
 import type { JSONContent } from "@tiptap/react";
 
@@ -21,6 +47,28 @@ import type { JSONContent } from "@tiptap/react";
 // API WRAPPER WITH LOGGING
 // ============================================================
 
+/**
+ * A failed command. Keeps the backend's `code` so callers can tell, say,
+ * "NOT_FOUND" from "DATABASE_BUSY", while still being a regular `Error`.
+ */
+export class CommandError extends Error implements AppError {
+  code: string;
+  details?: string | null;
+
+  constructor(error: AppError) {
+    super(error.message);
+    this.name = "CommandError";
+    this.code = error.code;
+    this.details = error.details;
+  }
+}
+
+const isAppError = (error: unknown): error is AppError =>
+  typeof error === "object" &&
+  error !== null &&
+  typeof (error as AppError).code === "string" &&
+  typeof (error as AppError).message === "string";
+
 async function invokeWithLogging<T>(
   command: string,
   args?: Record<string, unknown>
@@ -45,8 +93,9 @@ async function invokeWithLogging<T>(
     devLog.apiSuccess(command, { hasResult: result !== undefined });
     return result;
   } catch (error) {
-    devLog.apiError(command, error);
-    throw error;
+    const failure = isAppError(error) ? new CommandError(error) : error;
+    devLog.apiError(command, failure);
+    throw failure;
   }
 }
 
@@ -82,14 +131,28 @@ export async function deleteProfile(
   return invokeWithLogging("delete_profile", { profileId, reassignToId });
 }
 
-export async function getDefaultProfile(): Promise<Profile> {
+export async function getDefaultProfile(): Promise<Profile | null> {
   return invokeWithLogging("get_default_profile");
 }
 
+export async function setDefaultProfile(profileId: string): Promise<void> {
+  return invokeWithLogging("set_default_profile", { profileId });
+}
+
 export async function getProfileEntryCount(profileId: string): Promise<number> {
   return invokeWithLogging("get_profile_entry_count", { profileId });
 }
 
+export async function reassignProfileEntries(
+  fromProfileId: string,
+  toProfileId: string
+): Promise<number> {
+  return invokeWithLogging("reassign_profile_entries", {
+    fromProfileId,
+    toProfileId,
+  });
+}
+
 // ============================================================
 // STREAM API
 // ============================================================
@@ -98,15 +161,33 @@ export async function createStream(input: CreateStreamInput): Promise<Stream> {
   return invokeWithLogging("create_stream", { input });
 }
 
-export async function getAllStreams(userId: string): Promise<StreamMetadata[]> {
-  return invokeWithLogging("get_all_streams", { userId });
+export async function getAllStreams(
+  userId: string,
+  includeArchived?: boolean
+): Promise<StreamMetadata[]> {
+  return invokeWithLogging("get_all_streams", { userId, includeArchived });
 }
 
-export async function getStreamDetails(streamId: string): Promise<{
+export async function getStreamDetails(
+  streamId: string,
+  limit?: number,
+  offset?: number
+): Promise<{
   stream: Stream;
   entries: Entry[];
 }> {
-  return invokeWithLogging("get_stream_details", { streamId });
+  return invokeWithLogging("get_stream_details", { streamId, limit, offset });
+}
+
+export async function getLastOpenedStream(): Promise<{
+  stream: Stream;
+  entries: Entry[];
+} | null> {
+  return invokeWithLogging("get_last_opened_stream");
+}
+
+export async function setLastOpenedStream(streamId: string): Promise<void> {
+  return invokeWithLogging("set_last_opened_stream", { streamId });
 }
 
 export async function deleteStream(streamId: string): Promise<void> {
@@ -119,6 +200,8 @@ export async function updateStream(
     title?: string;
     description?: string;
     pinned?: boolean;
+    color?: string;
+    tags?: string[];
   }
 ): Promise<void> {
   return invokeWithLogging("update_stream", {
@@ -126,9 +209,136 @@ export async function updateStream(
     title: updates.title,
     description: updates.description,
     pinned: updates.pinned,
+    color: updates.color,
+    tags: updates.tags,
+  });
+}
+
+export async function updateStreamColor(
+  streamId: string,
+  color: string | null
+): Promise<void> {
+  return invokeWithLogging("update_stream_color", { streamId, color });
+}
+
+export async function setStreamDefaultProfile(
+  streamId: string,
+  profileId: string | null
+): Promise<void> {
+  return invokeWithLogging("set_stream_default_profile", {
+    streamId,
+    profileId,
+  });
+}
+
+export async function setStreamGoal(
+  streamId: string,
+  goal: number | null
+): Promise<void> {
+  return invokeWithLogging("set_stream_goal", { streamId, goal });
+}
+
+/** While locked, entry edits in the stream fail with a LOCKED error */
+export async function setStreamLocked(
+  streamId: string,
+  locked: boolean
+): Promise<void> {
+  return invokeWithLogging("set_stream_locked", { streamId, locked });
+}
+
+export async function reorderPinnedStreams(
+  orderedStreamIds: string[]
+): Promise<void> {
+  return invokeWithLogging("reorder_pinned_streams", { orderedStreamIds });
+}
+
+export async function getAvailableTemplates(): Promise<TemplateInfo[]> {
+  return invokeWithLogging("get_available_templates");
+}
+
+export async function resetTutorial(): Promise<Stream> {
+  return invokeWithLogging("reset_tutorial");
+}
+
+export async function createStreamFromTemplate(
+  userId: string,
+  templateId: string,
+  title: string
+): Promise<Stream> {
+  return invokeWithLogging("create_stream_from_template", {
+    userId,
+    templateId,
+    title,
   });
 }
 
+export async function mergeStreams(
+  sourceStreamId: string,
+  targetStreamId: string
+): Promise<void> {
+  return invokeWithLogging("merge_streams", { sourceStreamId, targetStreamId });
+}
+
+export async function splitStream(
+  streamId: string,
+  atSequenceId: number,
+  newTitle: string
+): Promise<Stream> {
+  return invokeWithLogging("split_stream", {
+    streamId,
+    atSequenceId,
+    newTitle,
+  });
+}
+
+export async function getStreamStats(
+  streamId: string,
+  includeAi?: boolean
+): Promise<StreamStats> {
+  return invokeWithLogging("get_stream_stats", { streamId, includeAi });
+}
+
+/** Words in user entries created since `sinceTimestamp`; edits to older entries aren't counted */
+export async function getSessionWordDelta(
+  streamId: string,
+  sinceTimestamp: number
+): Promise<number> {
+  return invokeWithLogging("get_session_word_delta", {
+    streamId,
+    sinceTimestamp,
+  });
+}
+
+export async function getStreamRoleBreakdown(
+  streamId: string
+): Promise<RoleBreakdown> {
+  return invokeWithLogging("get_stream_role_breakdown", { streamId });
+}
+
+export async function getWorkspaceStats(): Promise<WorkspaceStats> {
+  return invokeWithLogging("get_workspace_stats");
+}
+
+// ============================================================
+// TAG API
+// ============================================================
+
+export async function getAllTags(): Promise<TagCount[]> {
+  return invokeWithLogging("get_all_tags");
+}
+
+export async function getStreamsByTag(tag: string): Promise<StreamMetadata[]> {
+  return invokeWithLogging("get_streams_by_tag", { tag });
+}
+
+export async function renameTag(oldTag: string, newTag: string): Promise<number> {
+  return invokeWithLogging("rename_tag", { old: oldTag, new: newTag });
+}
+
+export async function deleteTag(tag: string): Promise<number> {
+  return invokeWithLogging("delete_tag", { tag });
+}
+
 // ============================================================
 // ENTRY API
 // ============================================================
@@ -137,6 +347,29 @@ export async function createEntry(input: CreateEntryInput): Promise<Entry> {
   return invokeWithLogging("create_entry", { input });
 }
 
+export async function createEntryAt(
+  input: CreateEntryInput,
+  afterSequenceId: number
+): Promise<Entry> {
+  return invokeWithLogging("create_entry_at", { input, afterSequenceId });
+}
+
+export async function bulkCreateEntries(
+  streamId: string,
+  entries: CreateEntryInput[]
+): Promise<Entry[]> {
+  return invokeWithLogging("bulk_create_entries", { streamId, entries });
+}
+
+// In the requested order; missing or deleted ids are skipped
+export async function getEntriesByIds(entryIds: string[]): Promise<Entry[]> {
+  return invokeWithLogging("get_entries_by_ids", { entryIds });
+}
+
+export async function getAdjacentEntries(entryId: string): Promise<Adjacent> {
+  return invokeWithLogging("get_adjacent_entries", { entryId });
+}
+
 export async function updateEntryContent(
   entryId: string,
   content: JSONContent
@@ -144,6 +377,22 @@ export async function updateEntryContent(
   return invokeWithLogging("update_entry_content", { entryId, content });
 }
 
+/** Adds `text` as a new paragraph at the end of the entry without sending the whole doc */
+export async function appendParagraphToEntry(
+  entryId: string,
+  text: string
+): Promise<void> {
+  return invokeWithLogging("append_paragraph_to_entry", { entryId, text });
+}
+
+/** Appends a streamed chunk to the end of the entry's last block */
+export async function appendTextToLastNode(
+  entryId: string,
+  text: string
+): Promise<void> {
+  return invokeWithLogging("append_text_to_last_node", { entryId, text });
+}
+
 export async function updateEntryProfile(
   entryId: string,
   profileId: string | null
@@ -154,13 +403,28 @@ export async function updateEntryProfile(
 export async function bulkUpdateEntryProfile(
   entryIds: string[],
   profileId: string | null
-): Promise<void> {
+): Promise<number> {
   return invokeWithLogging("bulk_update_entry_profile", {
     entryIds,
     profileId,
   });
 }
 
+/** Sets role, profile and AI metadata together; null clears the profile or metadata */
+export async function reclassifyEntry(
+  entryId: string,
+  role: EntryRole,
+  profileId: string | null,
+  aiMetadata: AiMetadata | null
+): Promise<Entry> {
+  return invokeWithLogging("reclassify_entry", {
+    entryId,
+    role,
+    profileId,
+    aiMetadata,
+  });
+}
+
 export async function toggleEntryStaging(
   entryId: string,
   isStaged: boolean
@@ -168,6 +432,25 @@ export async function toggleEntryStaging(
   return invokeWithLogging("toggle_entry_staging", { entryId, isStaged });
 }
 
+export async function updateEntryAppearance(
+  entryId: string,
+  color?: string,
+  label?: string
+): Promise<void> {
+  return invokeWithLogging("update_entry_appearance", {
+    entryId,
+    color,
+    label,
+  });
+}
+
+export async function toggleEntryPin(
+  entryId: string,
+  pinned: boolean
+): Promise<void> {
+  return invokeWithLogging("toggle_entry_pin", { entryId, pinned });
+}
+
 export async function deleteEntry(entryId: string): Promise<void> {
   return invokeWithLogging("delete_entry", { entryId });
 }
@@ -176,14 +459,61 @@ export async function bulkDeleteEntries(entryIds: string[]): Promise<void> {
   return invokeWithLogging("bulk_delete_entries", { entryIds });
 }
 
+export async function deleteEntries(entryIds: string[]): Promise<number> {
+  return invokeWithLogging("delete_entries", { entryIds });
+}
+
+export async function findEmptyEntries(streamId: string): Promise<string[]> {
+  return invokeWithLogging("find_empty_entries", { streamId });
+}
+
+/** Trashes the stream's empty entries, keeping one if all are empty; returns the count */
+export async function deleteEmptyEntries(streamId: string): Promise<number> {
+  return invokeWithLogging("delete_empty_entries", { streamId });
+}
+
 export async function getStagedEntries(streamId: string): Promise<Entry[]> {
   return invokeWithLogging("get_staged_entries", { streamId });
 }
 
+export async function getPinnedEntries(streamId: string): Promise<Entry[]> {
+  return invokeWithLogging("get_pinned_entries", { streamId });
+}
+
+export async function getChildrenOfEntry(entryId: string): Promise<Entry[]> {
+  return invokeWithLogging("get_children_of_entry", { entryId });
+}
+
+export async function getContextGraph(
+  streamId: string
+): Promise<ContextGraph> {
+  return invokeWithLogging("get_context_graph", { streamId });
+}
+
+/** Groups of entry ids whose plain text is identical, in sequence order */
+export async function findDuplicateEntries(
+  streamId: string
+): Promise<string[][]> {
+  return invokeWithLogging("find_duplicate_entries", { streamId });
+}
+
+/** Deletes `removeIds` and points their children at `keepId`; returns how many were removed */
+export async function mergeDuplicateEntries(
+  keepId: string,
+  removeIds: string[]
+): Promise<number> {
+  return invokeWithLogging("merge_duplicate_entries", { keepId, removeIds });
+}
+
 export async function clearAllStaging(streamId: string): Promise<void> {
   return invokeWithLogging("clear_all_staging", { streamId });
 }
 
+/** Renumbers the stream's entries to 1..N without changing their order */
+export async function compactSequenceIds(streamId: string): Promise<void> {
+  return invokeWithLogging("compact_sequence_ids", { streamId });
+}
+
 // ============================================================
 // VERSION API
 // ============================================================
@@ -198,6 +528,20 @@ export async function commitEntryVersion(
   });
 }
 
+export async function commitIfChanged(
+  entryId: string,
+  minIntervalMs: number
+): Promise<EntryVersion | null> {
+  return invokeWithLogging("commit_if_changed", { entryId, minIntervalMs });
+}
+
+// Versions entries whose last content update is older than idleThresholdMs
+export async function flushAutosaveVersions(
+  idleThresholdMs: number
+): Promise<EntryVersion[]> {
+  return invokeWithLogging("flush_autosave_versions", { idleThresholdMs });
+}
+
 export async function getEntryVersions(
   entryId: string
 ): Promise<EntryVersion[]> {
@@ -227,25 +571,138 @@ export async function revertToVersion(
   return invokeWithLogging("revert_to_version", { entryId, versionNumber });
 }
 
+export async function toggleVersionBookmark(
+  versionId: string,
+  bookmarked: boolean
+): Promise<void> {
+  return invokeWithLogging("toggle_version_bookmark", {
+    versionId,
+    bookmarked,
+  });
+}
+
+/** Reverts to the newest bookmarked version and returns it */
+export async function revertToBookmark(
+  entryId: string
+): Promise<EntryVersion> {
+  return invokeWithLogging("revert_to_bookmark", { entryId });
+}
+
+// The returned snapshot has id "working" to mark it as uncommitted
+export async function getWorkingSnapshot(
+  entryId: string
+): Promise<EntryVersion> {
+  return invokeWithLogging("get_working_snapshot", { entryId });
+}
+
+export async function labelVersion(
+  entryId: string,
+  versionNumber: number,
+  label: string
+): Promise<void> {
+  return invokeWithLogging("label_version", { entryId, versionNumber, label });
+}
+
+// Empty when the entry has no versions yet or matches its latest one
+export async function getUncommittedDiff(entryId: string): Promise<DiffOp[]> {
+  return invokeWithLogging("get_uncommitted_diff", { entryId });
+}
+
+export async function getLabeledVersions(
+  entryId: string
+): Promise<EntryVersion[]> {
+  return invokeWithLogging("get_labeled_versions", { entryId });
+}
+
+// ============================================================
+// LINK API
+// ============================================================
+
+// Linking the same pair again keeps the original link
+export async function createEntryLink(
+  sourceEntryId: string,
+  targetEntryId: string
+): Promise<EntryLink> {
+  return invokeWithLogging("create_entry_link", {
+    sourceEntryId,
+    targetEntryId,
+  });
+}
+
+export async function deleteEntryLink(
+  sourceEntryId: string,
+  targetEntryId: string
+): Promise<void> {
+  return invokeWithLogging("delete_entry_link", {
+    sourceEntryId,
+    targetEntryId,
+  });
+}
+
+export async function getLinkedEntries(entryId: string): Promise<LinkedEntries> {
+  return invokeWithLogging("get_linked_entries", { entryId });
+}
+
 // ============================================================
 // BRIDGE API
 // ============================================================
 
-export async function generateBridgeKey(): Promise<string> {
-  return invokeWithLogging("generate_bridge_key");
+export async function generateBridgeKey(length?: number): Promise<string> {
+  return invokeWithLogging("generate_bridge_key", { length });
 }
 
+// Marker style defaults to the stored preference
 export async function validateBridgeKey(
   inputText: string,
-  expectedKey: string
+  expectedKey: string,
+  style?: BridgeMarkerStyle
 ): Promise<boolean> {
-  return invokeWithLogging("validate_bridge_key", { inputText, expectedKey });
+  return invokeWithLogging("validate_bridge_key", {
+    inputText,
+    expectedKey,
+    style,
+  });
 }
 
 export async function extractBridgeKey(
-  inputText: string
+  inputText: string,
+  style?: BridgeMarkerStyle
 ): Promise<string | null> {
-  return invokeWithLogging("extract_bridge_key", { inputText });
+  return invokeWithLogging("extract_bridge_key", { inputText, style });
+}
+
+// Directive instructions, staged entries as Markdown, then the bridge marker
+export async function assembleBridgePayload(
+  streamId: string,
+  stagedContextIds: string[],
+  directive: DirectiveType,
+  bridgeKey: string,
+  style?: BridgeMarkerStyle
+): Promise<string> {
+  return invokeWithLogging("assemble_bridge_payload", {
+    streamId,
+    stagedContextIds,
+    directive,
+    bridgeKey,
+    style,
+  });
+}
+
+export async function getBridgeMarkerStyle(): Promise<BridgeMarkerStyle> {
+  return invokeWithLogging("get_bridge_marker_style");
+}
+
+export async function setBridgeMarkerStyle(
+  style: BridgeMarkerStyle
+): Promise<void> {
+  return invokeWithLogging("set_bridge_marker_style", { style });
+}
+
+export async function estimateContextTokens(
+  entryIds: string[],
+  model?: ModelType
+): Promise<TokenEstimate> {
+  return invokeWithLogging("estimate_context_tokens", { entryIds, model });
 }
 
 export async function createPendingBlock(
@@ -253,7 +710,7 @@ export async function createPendingBlock(
   streamId: string,
   bridgeKey: string,
   stagedContextIds: string[],
-  directive: string
+  directive: DirectiveType
 ): Promise<PendingBlock> {
   return invokeWithLogging("create_pending_block", {
     userId,
@@ -270,16 +727,173 @@ export async function getPendingBlock(
   return invokeWithLogging("get_pending_block", { streamId });
 }
 
+export async function getAllPendingBlocks(
+  streamId: string
+): Promise<PendingBlock[]> {
+  return invokeWithLogging("get_all_pending_blocks", { streamId });
+}
+
+// Across all streams, newest first
+export async function getActiveBridgeKeys(): Promise<BridgeKeyInfo[]> {
+  return invokeWithLogging("get_active_bridge_keys");
+}
+
 export async function deletePendingBlock(
   pendingBlockId: string
 ): Promise<void> {
   return invokeWithLogging("delete_pending_block", { pendingBlockId });
 }
 
+// Appends the AI entry, unstages its context and removes the block in one step
+export async function resolvePendingBlock(
+  pendingBlockId: string,
+  content: JSONContent,
+  aiMetadata: AiMetadata
+): Promise<Entry> {
+  return invokeWithLogging("resolve_pending_block", {
+    pendingBlockId,
+    content,
+    aiMetadata,
+  });
+}
+
+export async function cleanupExpiredPendingBlocks(
+  maxAgeMs: number
+): Promise<number> {
+  return invokeWithLogging("cleanup_expired_pending_blocks", { maxAgeMs });
+}
+
+export async function getKnownProviders(): Promise<ProviderInfo[]> {
+  return invokeWithLogging("get_known_providers");
+}
+
 // ============================================================
 // SEARCH API
 // ============================================================
 
-export async function searchEntries(query: string): Promise<Entry[]> {
-  return invokeWithLogging("search_entries", { query });
+export async function searchEntries(
+  query: string,
+  streamId?: string,
+  limit?: number,
+  offset?: number
+): Promise<SearchPage> {
+  return invokeWithLogging("search_entries", {
+    query,
+    streamId,
+    limit,
+    offset,
+  });
+}
+
+export async function searchEntriesFuzzy(
+  query: string,
+  maxDistance = 2
+): Promise<SearchResult[]> {
+  return invokeWithLogging("search_entries_fuzzy", { query, maxDistance });
+}
+
+export async function searchEntriesFiltered(
+  query: string,
+  filters: SearchFilters
+): Promise<SearchResult[]> {
+  return invokeWithLogging("search_entries_filtered", { query, filters });
+}
+
+/** Matches in committed version snapshots, newest first, for text since edited out */
+export async function searchVersions(
+  query: string
+): Promise<VersionSearchResult[]> {
+  return invokeWithLogging("search_versions", { query });
+}
+
+/** Entries whose content mentions `entryId`; `referenceType` defaults to "mention" */
+export async function getBacklinks(
+  entryId: string,
+  referenceType?: string
+): Promise<SearchResult[]> {
+  return invokeWithLogging("get_backlinks", { entryId, referenceType });
+}
+
+export async function getRecentEntries(limit = 20): Promise<SearchResult[]> {
+  return invokeWithLogging("get_recent_entries", { limit });
+}
+
+// ============================================================
+// UNDO API
+// ============================================================
+
+/** Restores what the most recent stream delete, entry delete or staging clear removed */
+export async function undoLastOperation(): Promise<void> {
+  return invokeWithLogging("undo_last_operation");
+}
+
+// ============================================================
+// SETTINGS API
+// ============================================================
+
+export async function getMeta(key: string): Promise<string | null> {
+  return invokeWithLogging("get_meta", { key });
+}
+
+export async function setMeta(key: string, value: string): Promise<void> {
+  return invokeWithLogging("set_meta", { key, value });
+}
+
+// ============================================================
+// WINDOW API
+// ============================================================
+
+// macOS only; resolves without doing anything elsewhere
+export async function setTrafficLightPosition(
+  x: number,
+  y: number
+): Promise<void> {
+  return invokeWithLogging("set_traffic_light_position", { x, y });
+}
+
+// ============================================================
+// DATABASE API
+// ============================================================
+
+export async function isDatabaseLocked(): Promise<boolean> {
+  return invokeWithLogging("is_database_locked");
+}
+
+export async function setDatabaseKey(key: string): Promise<void> {
+  return invokeWithLogging("set_database_key", { key });
+}
+
+export async function getDatabaseInfo(): Promise<DbInfo> {
+  return invokeWithLogging("get_database_info");
+}
+
+export async function optimizeDatabase(): Promise<OptimizeReport> {
+  return invokeWithLogging("optimize_database");
+}
+
+export async function backupDatabase(destinationPath: string): Promise<void> {
+  return invokeWithLogging("backup_database", { destinationPath });
+}
+
+export async function restoreDatabase(sourcePath: string): Promise<void> {
+  return invokeWithLogging("restore_database", { sourcePath });
+}
+
+// Zip of every stream, profile and app setting as JSON files
+export async function exportAll(destinationPath: string): Promise<void> {
+  return invokeWithLogging("export_all", { destinationPath });
+}
+
+export async function importAll(zipPath: string): Promise<Stream[]> {
+  return invokeWithLogging("import_all", { zipPath });
+}
+
+export async function findOrphanedEntries(): Promise<OrphanReport> {
+  return invokeWithLogging("find_orphaned_entries");
+}
+
+export async function repairOrphanedEntries(
+  strategy: "reassign" | "delete"
+): Promise<number> {
+  return invokeWithLogging("repair_orphaned_entries", { strategy });
 }
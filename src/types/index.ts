@@ -53,6 +53,7 @@ export interface UpdateProfileInput {
   initials?: string;
   bio?: string;
   avatarUrl?: string;
+  isDefault?: boolean;
 }
 
 // Profile color palette for visual distinction
@@ -93,6 +94,13 @@ export interface Stream {
   pinned: boolean;
   createdAt: number;
   updatedAt: number;
+  archived?: boolean;
+  /** Profile given to new entries that don't name one */
+  defaultProfileId?: string | null;
+  /** Target word count, cleared with null */
+  wordGoal?: number | null;
+  /** Locked streams reject entry edits */
+  locked?: boolean;
 }
 
 export interface StreamMetadata {
@@ -104,6 +112,54 @@ export interface StreamMetadata {
   pinned: boolean;
   color?: string;
   tags: string[];
+  archived?: boolean;
+}
+
+export interface TemplateInfo {
+  id: string;
+  name: string;
+  description: string;
+}
+
+// An AI provider and its common model ids, for the bridge dropdown
+export interface ProviderInfo {
+  id: AIProvider;
+  name: string;
+  models: string[];
+}
+
+export interface StreamStats {
+  entryCount: number;
+  wordCount: number;
+  characterCount: number;
+  versionCount: number;
+  firstEntryAt?: number;
+  lastUpdatedAt?: number;
+  goal?: number | null;
+  /** wordCount / goal, capped at 1 */
+  progressRatio?: number | null;
+}
+
+export interface RoleBreakdown {
+  userCount: number;
+  aiCount: number;
+  // [profileId, name, count]; a null id means the entries have no profile
+  byProfile: [string | null, string, number][];
+}
+
+export interface WorkspaceStats {
+  totalStreams: number;
+  totalEntries: number;
+  totalWords: number;
+  totalAiEntries: number;
+  totalCommits: number;
+  // [UTC day start in ms, words written that day], oldest first
+  wordsByDay: [number, number][];
+}
+
+export interface TagCount {
+  tag: string;
+  count: number;
 }
 
 export interface CreateStreamInput {
@@ -138,6 +194,9 @@ export interface Entry {
   sequenceId: number;
   versionHead: number;
   isStaged: boolean;
+  isPinned: boolean;
+  color?: string;
+  label?: string;
   parentContextIds: string[] | null;
   createdAt: number;
   updatedAt: number;
@@ -163,6 +222,9 @@ export interface CreateEntryInput {
   parentContextIds?: string[];
   insertAfterId?: string;
   insertBeforeId?: string;
+  /** Original timestamps for imported notes; defaults to now */
+  createdAt?: number;
+  updatedAt?: number;
 }
 
 export interface UpdateEntryContentInput {
@@ -170,6 +232,12 @@ export interface UpdateEntryContentInput {
   content: JSONContent;
 }
 
+/** Neighbors of an entry in sequence order; null at either end of the stream */
+export interface Adjacent {
+  prev: Entry | null;
+  next: Entry | null;
+}
+
 // ============================================================
 // VERSION TYPES
 // ============================================================
@@ -181,6 +249,9 @@ export interface EntryVersion {
   contentSnapshot: JSONContent;
   commitMessage?: string;
   committedAt: number;
+  label?: string;
+  /** Known-good snapshot that `revertToBookmark` returns to */
+  bookmarked?: boolean;
 }
 
 export interface CreateVersionInput {
@@ -188,6 +259,12 @@ export interface CreateVersionInput {
   commitMessage?: string;
 }
 
+// One line of a plain-text diff between two versions of an entry
+export interface DiffOp {
+  kind: "unchanged" | "add" | "remove";
+  text: string;
+}
+
 export interface VersionDiff {
   added: string[];
   removed: string[];
@@ -216,12 +293,37 @@ export interface CreateSpotlightInput {
   endOffset: number;
 }
 
+// ============================================================
+// LINK TYPES
+// ============================================================
+
+export interface EntryLink {
+  sourceEntryId: string;
+  targetEntryId: string;
+  createdAt: number;
+}
+
+// The entry at the other end of a link, with its stream for navigation
+export interface LinkedEntry {
+  entry: Entry;
+  streamTitle: string;
+  linkedAt: number;
+}
+
+export interface LinkedEntries {
+  outbound: LinkedEntry[];
+  inbound: LinkedEntry[];
+}
+
 // ============================================================
 // DIRECTIVE TYPES
 // ============================================================
 
 export type DirectiveType = "DUMP" | "CRITIQUE" | "GENERATE";
 
+// How the bridge key is written into prompts: <!-- bridge:KEY --> or [[bridge:KEY]]
+export type BridgeMarkerStyle = "html_comment" | "wiki_link";
+
 export interface DirectiveConfig {
   type: DirectiveType;
   label: string;
@@ -383,6 +485,17 @@ export interface PendingBlock {
   createdAt: number;
 }
 
+// A bridge still waiting for its AI response
+export interface BridgeKeyInfo {
+  pendingBlockId: string;
+  bridgeKey: string;
+  streamId: string;
+  streamTitle: string;
+  directive: DirectiveType;
+  createdAt: number;
+  ageMs: number;
+}
+
 // ============================================================
 // AI PROVIDER UTILITIES
 // ============================================================
@@ -546,6 +659,15 @@ export const MODEL_CONFIGS: Record<ModelType, ModelConfig> = {
   },
 };
 
+export interface TokenEstimate {
+  charCount: number;
+  approxTokens: number;
+  // [entryId, approxTokens] in request order
+  perEntry: [string, number][];
+  contextWindow?: number;
+  exceedsWindow: boolean;
+}
+
 export interface TokenUsage {
   used: number;
   limit: number;
@@ -566,23 +688,74 @@ export interface AppState {
   theme: "light" | "dark" | "system";
 }
 
+export interface SearchPage {
+  results: SearchResult[];
+  totalCount: number;
+  hasMore: boolean;
+}
+
+export interface SearchFilters {
+  streamId?: string;
+  role?: "user" | "ai";
+  profileId?: string;
+  createdAfter?: number;
+  createdBefore?: number;
+}
+
 export interface SearchResult {
+  entry: Entry;
   streamId: string;
   streamTitle: string;
+  snippet: string;
+  /** `snippet` with each matched term wrapped in `**` */
+  highlightedSnippet: string;
+}
+
+/** A committed version whose snapshot matched `searchVersions` */
+export interface VersionSearchResult {
+  version: EntryVersion;
   entryId: string;
+  streamId: string;
+  streamTitle: string;
   snippet: string;
-  matchStart: number;
-  matchEnd: number;
+  highlightedSnippet: string;
+}
+
+// ============================================================
+// DATABASE TYPES
+// ============================================================
+
+export interface DbInfo {
+  path: string;
+  pageCount: number;
+  pageSize: number;
+  sizeBytes: number;
+  journalMode: string;
+  userVersion: number;
+  integrityOk: boolean;
+}
+
+export interface OptimizeReport {
+  bytesBefore: number;
+  bytesAfter: number;
+}
+
+/** Entry ids whose stream or profile no longer exists */
+export interface OrphanReport {
+  missingStream: string[];
+  missingProfile: string[];
 }
 
 // ============================================================
 // ERROR TYPES
 // ============================================================
 
+/** Error shape returned by every Tauri command */
 export interface AppError {
+  /** Stable code such as "NOT_FOUND", "INVALID_INPUT" or "DATABASE_BUSY" */
   code: string;
   message: string;
-  details?: string;
+  details?: string | null;
 }
 
 export type ClipboardErrorType =
@@ -611,6 +784,13 @@ export interface IpcResult<T> {
 // BRANCH VISUALIZATION TYPES
 // ============================================================
 
+export interface ContextGraph {
+  // Live entry ids in sequence order
+  nodes: string[];
+  // [parentId, childId] pairs
+  edges: [string, string][];
+}
+
 export interface BranchNode {
   id: string;
   entryId: string;